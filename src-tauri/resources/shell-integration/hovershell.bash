@@ -0,0 +1,97 @@
+# HoverShell shell integration for bash
+#
+# Reports prompt, command and exit status boundaries with OSC 133 and the
+# working directory with OSC 7 so HoverShell can track command blocks.
+#
+# HoverShell starts bash with --rcfile pointing here, so load the user's
+# own startup files first. bash ignores --rcfile in a login shell, so login
+# sessions start without -l and set HOVERSHELL_BASH_LOGIN instead; they get
+# the login files a login shell would read.
+
+if [ -n "$HOVERSHELL_BASH_LOGIN" ]; then
+  unset HOVERSHELL_BASH_LOGIN
+  if [ -f /etc/profile ]; then
+    . /etc/profile
+  fi
+  if [ -f "$HOME/.bash_profile" ]; then
+    . "$HOME/.bash_profile"
+  elif [ -f "$HOME/.bash_login" ]; then
+    . "$HOME/.bash_login"
+  elif [ -f "$HOME/.profile" ]; then
+    . "$HOME/.profile"
+  fi
+elif [ -f "$HOME/.bashrc" ]; then
+  . "$HOME/.bashrc"
+fi
+
+[[ $- == *i* ]] || return 0
+[ -n "$HOVERSHELL_INTEGRATION_LOADED" ] && return 0
+HOVERSHELL_INTEGRATION_LOADED=1
+
+__hovershell_urlencode() {
+  local LC_ALL=C str="$1" out="" c i
+  for (( i = 0; i < ${#str}; i++ )); do
+    c="${str:i:1}"
+    case "$c" in
+      [a-zA-Z0-9.~_/-]) out+="$c" ;;
+      *) printf -v c '%%%02X' "'$c"; out+="$c" ;;
+    esac
+  done
+  printf '%s' "$out"
+}
+
+__hovershell_save_status() {
+  __hovershell_status=$?
+}
+
+# Reads the number and text of the newest history entry, keeping any
+# leading space of the command
+__hovershell_last_history() {
+  local line
+  line=$(HISTTIMEFORMAT= builtin history 1)
+  [[ $line =~ ^\ *([0-9]+)\*?\ \ (.*)$ ]] || return 1
+  __hovershell_history_number=${BASH_REMATCH[1]}
+  __hovershell_history_command=${BASH_REMATCH[2]}
+}
+
+__hovershell_prompt_command() {
+  if [ -n "$__hovershell_command_running" ]; then
+    printf '\e]133;D;%s\a' "$__hovershell_status"
+    __hovershell_command_running=
+  fi
+  printf '\e]7;file://%s%s\a' "$HOSTNAME" "$(__hovershell_urlencode "$PWD")"
+  printf '\e]133;A\a'
+
+  if [[ "$PS1" != *'133;B'* ]]; then
+    PS1="$PS1"'\[\e]133;B\a\]'
+  fi
+  __hovershell_history_number=
+  __hovershell_last_history
+  __hovershell_prompt_history=$__hovershell_history_number
+  __hovershell_at_prompt=1
+}
+
+# bash has no preexec hook; the DEBUG trap fires before every simple command,
+# so only the first one after a prompt marks the start of a command line.
+__hovershell_debug_trap() {
+  # An empty command line goes straight back to PROMPT_COMMAND
+  if [ "$BASH_COMMAND" = "__hovershell_save_status" ]; then
+    __hovershell_at_prompt=
+    return
+  fi
+  if [ -n "$__hovershell_at_prompt" ] && [ -z "$COMP_LINE" ]; then
+    __hovershell_at_prompt=
+    # A line that didn't make it into the history (HISTCONTROL=ignorespace,
+    # ignoredups, HISTIGNORE) leaves the previous entry on top, so send no
+    # command line and let HoverShell read it off the screen
+    if __hovershell_last_history && [ "$__hovershell_history_number" != "$__hovershell_prompt_history" ]; then
+      printf '\e]133;C;cmdline_url=%s\a' "$(__hovershell_urlencode "$__hovershell_history_command")"
+    else
+      printf '\e]133;C\a'
+    fi
+    __hovershell_command_running=1
+  fi
+}
+
+PROMPT_COMMAND="__hovershell_save_status${PROMPT_COMMAND:+; $PROMPT_COMMAND}; __hovershell_prompt_command"
+trap '__hovershell_debug_trap' DEBUG
@@ -0,0 +1,30 @@
+# HoverShell shell integration for fish
+#
+# Reports prompt, command and exit status boundaries with OSC 133 and the
+# working directory with OSC 7 so HoverShell can track command blocks.
+
+status is-interactive; or exit 0
+set -q HOVERSHELL_INTEGRATION_LOADED; and exit 0
+set -g HOVERSHELL_INTEGRATION_LOADED 1
+
+function __hovershell_prompt --on-event fish_prompt
+    printf '\e]7;file://%s%s\a' (hostname) (string escape --style=url -- $PWD)
+    printf '\e]133;A\a'
+end
+
+function __hovershell_preexec --on-event fish_preexec
+    printf '\e]133;C;cmdline_url=%s\a' (string escape --style=url -- "$argv")
+end
+
+function __hovershell_postexec --on-event fish_postexec
+    printf '\e]133;D;%s\a' $status
+end
+
+# Mark the end of the prompt, where command input begins
+if functions -q fish_prompt
+    functions -c fish_prompt __hovershell_original_prompt
+    function fish_prompt
+        __hovershell_original_prompt
+        printf '\e]133;B\a'
+    end
+end
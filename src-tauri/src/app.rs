@@ -1,23 +1,67 @@
 use crate::{
     config::Config,
+    conversations::ConversationManager,
     core::Core,
     error::HoverShellError,
+    history::HistoryManager,
     hotkeys::HotkeyManager,
-    providers::ProviderManager,
-    terminal::TerminalManager,
+    jobs::JobManager,
+    providers::{ProviderManager, RequestRegistry},
+    terminal::{CommandFinishedEvent, TerminalManager},
     tray::TrayManager,
     ui::UIManager,
+    utils::format_duration,
 };
 use log::{error, info};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tauri::{AppHandle, Manager};
+use tauri::AppHandle;
 use tokio::sync::RwLock;
 
+// Whether the panel is on screen. Every show and hide path goes through
+// here, including quick hide in the frontend, which hides the native window
+// through the `hide_window` command.
+#[derive(Clone)]
+pub struct PanelVisibility {
+    visible: Arc<AtomicBool>,
+    ai_requests: RequestRegistry,
+}
+
+impl PanelVisibility {
+    pub fn new(ai_requests: RequestRegistry) -> Self {
+        Self {
+            visible: Arc::new(AtomicBool::new(false)),
+            ai_requests,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible.load(Ordering::SeqCst)
+    }
+
+    pub fn show(&self) {
+        self.visible.store(true, Ordering::SeqCst);
+    }
+
+    // Nobody is around to read answers that arrive after the panel is
+    // hidden, so in-flight AI requests are cancelled. Returns how many were.
+    pub async fn hide(&self) -> usize {
+        self.visible.store(false, Ordering::SeqCst);
+        self.ai_requests.cancel_all().await
+    }
+}
+
 pub struct HoverShellApp {
     pub config: Arc<RwLock<Config>>,
     pub core: Arc<RwLock<Core>>,
     pub providers: Arc<RwLock<ProviderManager>>,
+    // Cancels AI requests without waiting on the providers lock
+    pub ai_requests: RequestRegistry,
+    pub panel: PanelVisibility,
     pub terminal: Arc<RwLock<TerminalManager>>,
+    pub history: Arc<RwLock<HistoryManager>>,
+    pub conversations: Arc<RwLock<ConversationManager>>,
+    pub jobs: Arc<RwLock<JobManager>>,
     pub ui: Arc<RwLock<UIManager>>,
     pub hotkeys: Arc<RwLock<HotkeyManager>>,
     pub tray: Arc<RwLock<TrayManager>>,
@@ -36,13 +80,28 @@ impl HoverShellApp {
         info!("Core initialized");
 
         // Initialize providers
-        let providers = Arc::new(RwLock::new(ProviderManager::new().await?));
+        let providers = ProviderManager::new().await?;
+        let ai_requests = providers.requests();
+        let panel = PanelVisibility::new(ai_requests.clone());
+        let providers = Arc::new(RwLock::new(providers));
         info!("Provider manager initialized");
 
         // Initialize terminal
         let terminal = Arc::new(RwLock::new(TerminalManager::new().await?));
         info!("Terminal manager initialized");
 
+        // Initialize command history
+        let history = Arc::new(RwLock::new(HistoryManager::new().await?));
+        info!("History manager initialized");
+
+        // Initialize AI conversations
+        let conversations = Arc::new(RwLock::new(ConversationManager::new().await?));
+        info!("Conversation manager initialized");
+
+        // Initialize background jobs
+        let jobs = Arc::new(RwLock::new(JobManager::new().await?));
+        info!("Job manager initialized");
+
         // Initialize UI
         let ui = Arc::new(RwLock::new(UIManager::new().await?));
         info!("UI manager initialized");
@@ -59,7 +118,12 @@ impl HoverShellApp {
             config,
             core,
             providers,
+            ai_requests,
+            panel,
             terminal,
+            history,
+            conversations,
+            jobs,
             ui,
             hotkeys,
             tray,
@@ -89,11 +153,76 @@ impl HoverShellApp {
             providers.load_from_config(&config).await?;
         }
 
+        // Load stored AI conversations
+        {
+            let config = self.config.read().await;
+            let mut conversations = self.conversations.write().await;
+            conversations.initialize(&config).await?;
+        }
+
+        // Load command history before any session can run commands
+        {
+            let config = self.config.read().await;
+            let mut history = self.history.write().await;
+            history.initialize(&config).await?;
+        }
+
         // Initialize terminal with default shell
         {
             let config = self.config.read().await;
             let mut terminal = self.terminal.write().await;
-            terminal.initialize(&config).await?;
+            terminal.initialize(&config, &app_handle).await?;
+
+            // Shells that exit on their own are reported back to the manager
+            if let Some(mut exits) = terminal.take_exit_receiver() {
+                let terminal = self.terminal.clone();
+                tauri::async_runtime::spawn(async move {
+                    while let Some(exit) = exits.recv().await {
+                        if let Err(e) = terminal.write().await.handle_session_exit(exit).await {
+                            error!("Error handling terminal session exit: {}", e);
+                        }
+                    }
+                });
+            }
+
+            // Finished commands from every session go into the history, and
+            // long ones that finish while the window is hidden are announced
+            if let Some(mut commands) = terminal.take_command_receiver() {
+                let history = self.history.clone();
+                let config = self.config.clone();
+                let terminal = self.terminal.clone();
+                let tray = self.tray.clone();
+                let panel = self.panel.clone();
+                tauri::async_runtime::spawn(async move {
+                    while let Some(finished) = commands.recv().await {
+                        if let Err(e) = history.write().await.record(&finished.session_id, &finished.block).await {
+                            error!("Error recording command history: {}", e);
+                        }
+                        if let Err(e) = notify_long_command(&finished, &config, &terminal, &tray, &panel).await {
+                            error!("Error notifying about finished command: {}", e);
+                        }
+                    }
+                });
+            }
+        }
+
+        // Initialize background jobs; their notifications go through the tray
+        {
+            let config = self.config.read().await;
+            let mut jobs = self.jobs.write().await;
+            jobs.initialize(&config, &app_handle).await?;
+
+            if let Some(mut notifications) = jobs.take_notification_receiver() {
+                let tray = self.tray.clone();
+                tauri::async_runtime::spawn(async move {
+                    while let Some(notification) = notifications.recv().await {
+                        let tray = tray.read().await;
+                        if let Err(e) = tray.show_notification(&notification.title, &notification.body).await {
+                            error!("Error showing job notification: {}", e);
+                        }
+                    }
+                });
+            }
         }
 
         // Initialize UI
@@ -116,6 +245,16 @@ impl HoverShellApp {
             config.save().await?;
         }
 
+        // Save terminal sessions so they can be restored on the next launch
+        {
+            let config = self.config.read().await;
+            if config.terminal.restore_sessions {
+                if let Err(e) = self.terminal.read().await.save_sessions().await {
+                    error!("Error saving terminal sessions: {}", e);
+                }
+            }
+        }
+
         // Shutdown components in reverse order
         if let Err(e) = self.tray.write().await.shutdown().await {
             error!("Error shutting down tray manager: {}", e);
@@ -129,6 +268,18 @@ impl HoverShellApp {
             error!("Error shutting down terminal manager: {}", e);
         }
 
+        if let Err(e) = self.jobs.write().await.shutdown().await {
+            error!("Error shutting down job manager: {}", e);
+        }
+
+        if let Err(e) = self.history.write().await.shutdown().await {
+            error!("Error shutting down history manager: {}", e);
+        }
+
+        if let Err(e) = self.conversations.write().await.shutdown().await {
+            error!("Error shutting down conversation manager: {}", e);
+        }
+
         if let Err(e) = self.providers.write().await.shutdown().await {
             error!("Error shutting down provider manager: {}", e);
         }
@@ -144,4 +295,46 @@ impl HoverShellApp {
         info!("HoverShell application shutdown completed");
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Long commands usually finish after the user has hidden the window, so the
+// notification carries the exit code and the last line of output
+async fn notify_long_command(
+    finished: &CommandFinishedEvent,
+    config: &RwLock<Config>,
+    terminal: &RwLock<TerminalManager>,
+    tray: &RwLock<TrayManager>,
+    panel: &PanelVisibility,
+) -> Result<(), HoverShellError> {
+    let settings = config.read().await.terminal.command_notifications.clone();
+    let block = &finished.block;
+    let Some(duration_ms) = block.duration_ms else {
+        return Ok(());
+    };
+    if !settings.enabled || duration_ms < settings.threshold * 1000 {
+        return Ok(());
+    }
+
+    if panel.is_visible() {
+        return Ok(());
+    }
+
+    let duration = format_duration(duration_ms / 1000);
+    let command = block.command.trim();
+    let (title, status) = match block.exit_code {
+        Some(0) | None => ("Command finished".to_string(), format!("{} finished", command)),
+        Some(code) => (format!("Command failed with exit code {}", code), format!("{} failed", command)),
+    };
+    let mut body = format!("{} ({})", command, duration);
+    if let Some(line) = terminal.read().await.last_output_line(&finished.session_id, block.id).await {
+        body.push('\n');
+        body.push_str(&line);
+    }
+
+    let mut tray = tray.write().await;
+    tray.show_notification(&title, &body).await?;
+    if settings.update_tray_status {
+        tray.update_status(&status).await?;
+    }
+    Ok(())
+}
@@ -1,12 +1,28 @@
 use crate::{
     app::HoverShellApp,
     config::{Config, ProviderConfig},
+    conversations::{self, Conversation, ConversationInfo, ConversationMessage, ExportFormat},
     error::{HoverShellError, Result},
+    history::{HistoryEntry, HistorySearchOptions, RankedCommand},
+    jobs::Job,
+    providers::{
+        router::{RouteDecision, RouteQuery},
+        usage::{UsageQuery, UsageRecord, UsageSummary},
+        ProviderResponse, RequestOptions, UsageInfo,
+    },
+    terminal::{
+        emulator::ScreenSnapshot,
+        layout::{FocusDirection, PaneLayout, SplitDirection},
+        recording::Redactor,
+        scrollback::OutputRange,
+        search::{SearchDirection, SearchMatch, SearchOptions, SearchResult},
+        shell_integration::CommandBlock,
+    },
     tools::*,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Manager, State};
 use tokio::sync::RwLock;
 
 type AppState = Arc<RwLock<HoverShellApp>>;
@@ -48,32 +64,42 @@ pub struct ThemeInfo {
 }
 
 #[tauri::command]
-pub async fn toggle_window(app_handle: AppHandle) -> Result<()> {
-    if let Some(window) = app_handle.get_webview_window("main") {
-        if window.is_visible().unwrap_or(false) {
-            window.hide().map_err(|e| HoverShellError::UI(e.to_string()))?;
-        } else {
-            window.show().map_err(|e| HoverShellError::UI(e.to_string()))?;
-            window.set_focus().map_err(|e| HoverShellError::UI(e.to_string()))?;
-        }
+pub async fn toggle_window(state: State<'_, AppState>, app_handle: AppHandle) -> Result<()> {
+    let visible = state.read().await.panel.is_visible();
+    if visible {
+        hide_window(state, app_handle).await
+    } else {
+        show_window(state, app_handle).await
     }
-    Ok(())
 }
 
 #[tauri::command]
-pub async fn show_window(app_handle: AppHandle) -> Result<()> {
-    if let Some(window) = app_handle.get_webview_window("main") {
+pub async fn show_window(state: State<'_, AppState>, app_handle: AppHandle) -> Result<()> {
+    if let Some(window) = app_handle.get_window("main") {
         window.show().map_err(|e| HoverShellError::UI(e.to_string()))?;
         window.set_focus().map_err(|e| HoverShellError::UI(e.to_string()))?;
+        state.read().await.panel.show();
+        clear_tray_status(&state).await?;
+    }
+    Ok(())
+}
+
+// A finished-command status has been seen once the window is shown again
+async fn clear_tray_status(state: &State<'_, AppState>) -> Result<()> {
+    let app = state.read().await;
+    let mut tray = app.tray.write().await;
+    if tray.status().is_some() {
+        tray.update_status("").await?;
     }
     Ok(())
 }
 
 #[tauri::command]
-pub async fn hide_window(app_handle: AppHandle) -> Result<()> {
-    if let Some(window) = app_handle.get_webview_window("main") {
+pub async fn hide_window(state: State<'_, AppState>, app_handle: AppHandle) -> Result<()> {
+    if let Some(window) = app_handle.get_window("main") {
         window.hide().map_err(|e| HoverShellError::UI(e.to_string()))?;
     }
+    state.read().await.panel.hide().await;
     Ok(())
 }
 
@@ -100,6 +126,13 @@ pub async fn get_providers(state: State<'_, AppState>) -> Result<Vec<ProviderCon
     Ok(config.providers.clone())
 }
 
+#[tauri::command]
+pub async fn list_provider_models(state: State<'_, AppState>, provider_id: Option<String>) -> Result<Vec<String>> {
+    let app = state.read().await;
+    let providers = app.providers.read().await;
+    providers.list_models(provider_id.as_deref()).await
+}
+
 #[tauri::command]
 pub async fn add_provider(state: State<'_, AppState>, provider: ProviderConfig) -> Result<()> {
     let app = state.read().await;
@@ -132,20 +165,179 @@ pub async fn execute_command(
     state: State<'_, AppState>,
     command: String,
     provider_id: Option<String>,
-) -> Result<String> {
+    options: Option<RequestOptions>,
+) -> Result<ProviderResponse> {
     let app = state.read().await;
-    let terminal = app.terminal.read().await;
+    let options = with_working_directory(&app, options).await;
     let providers = app.providers.read().await;
     
-    let result = if let Some(pid) = provider_id {
+    // The metadata and usage say which provider answered and what it cost
+    if let Some(pid) = provider_id {
         // Execute with specific provider
-        providers.execute_with_provider(&command, &pid).await?
+        providers.execute_with_provider(&command, &pid, &options).await
     } else {
         // Execute with default provider
-        providers.execute(&command).await?
-    };
-    
-    Ok(result)
+        providers.execute(&command, &options).await
+    }
+}
+
+#[tauri::command]
+pub async fn stream_ai_response(
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+    prompt: String,
+    context: Option<String>,
+    provider_id: Option<String>,
+    options: Option<RequestOptions>,
+) -> Result<String> {
+    let app = state.read().await;
+    let options = with_working_directory(&app, options).await;
+    let providers = app.providers.read().await;
+    providers.start_stream(
+        &prompt,
+        context.as_deref(),
+        provider_id.as_deref(),
+        &options,
+        &app_handle,
+    ).await
+}
+
+// Shows where a prompt would be sent and why, without sending it
+#[tauri::command]
+pub async fn explain_ai_route(state: State<'_, AppState>, mut query: RouteQuery) -> Result<RouteDecision> {
+    let app = state.read().await;
+    if query.working_directory.is_none() {
+        query.working_directory = active_working_directory(&app).await;
+    }
+    let providers = app.providers.read().await;
+    providers.route(&query)
+}
+
+// Requests without a working directory are routed as if made from the
+// active terminal session
+async fn with_working_directory(app: &HoverShellApp, options: Option<RequestOptions>) -> RequestOptions {
+    let mut options = options.unwrap_or_default();
+    if options.working_directory.is_none() {
+        options.working_directory = active_working_directory(app).await;
+    }
+    options
+}
+
+async fn active_working_directory(app: &HoverShellApp) -> Option<String> {
+    let terminal = app.terminal.read().await;
+    terminal.get_active_session().await.map(|session| session.working_directory)
+}
+
+#[tauri::command]
+pub async fn cancel_ai_request(state: State<'_, AppState>, request_id: String) -> Result<bool> {
+    let app = state.read().await;
+    Ok(app.ai_requests.cancel(&request_id).await)
+}
+
+#[tauri::command]
+pub async fn get_ai_usage(
+    state: State<'_, AppState>,
+    query: Option<UsageQuery>,
+) -> Result<Vec<UsageRecord>> {
+    let app = state.read().await;
+    let providers = app.providers.read().await;
+    Ok(providers.usage_records(&query.unwrap_or_default()).await)
+}
+
+#[tauri::command]
+pub async fn get_ai_usage_summary(state: State<'_, AppState>) -> Result<UsageSummary> {
+    let app = state.read().await;
+    let providers = app.providers.read().await;
+    Ok(providers.usage_summary().await)
+}
+
+#[tauri::command]
+pub async fn create_conversation(state: State<'_, AppState>, title: Option<String>) -> Result<Conversation> {
+    let app = state.read().await;
+    let mut conversations = app.conversations.write().await;
+    conversations.create(title).await
+}
+
+#[tauri::command]
+pub async fn list_conversations(state: State<'_, AppState>) -> Result<Vec<ConversationInfo>> {
+    let app = state.read().await;
+    let conversations = app.conversations.read().await;
+    Ok(conversations.list())
+}
+
+#[tauri::command]
+pub async fn get_conversation(state: State<'_, AppState>, conversation_id: String) -> Result<Conversation> {
+    let app = state.read().await;
+    let conversations = app.conversations.read().await;
+    conversations.get(&conversation_id).cloned()
+}
+
+#[tauri::command]
+pub async fn rename_conversation(state: State<'_, AppState>, conversation_id: String, title: String) -> Result<()> {
+    let app = state.read().await;
+    let mut conversations = app.conversations.write().await;
+    conversations.rename(&conversation_id, &title).await
+}
+
+#[tauri::command]
+pub async fn delete_conversation(state: State<'_, AppState>, conversation_id: String) -> Result<()> {
+    let app = state.read().await;
+    let mut conversations = app.conversations.write().await;
+    conversations.delete(&conversation_id).await
+}
+
+// Asks the AI and returns its answer, which is stored with the question
+#[tauri::command]
+pub async fn send_conversation_message(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    content: String,
+    provider_id: Option<String>,
+    options: Option<RequestOptions>,
+) -> Result<ConversationMessage> {
+    let app = state.read().await;
+    let options = with_working_directory(&app, options).await;
+    let providers = app.providers.read().await;
+    conversations::send_message(
+        &app.conversations,
+        &providers,
+        &conversation_id,
+        &content,
+        provider_id.as_deref(),
+        &options,
+    ).await
+}
+
+// Stores a message obtained elsewhere, e.g. a streamed answer
+#[tauri::command]
+pub async fn append_conversation_message(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    role: String,
+    content: String,
+    provider_id: Option<String>,
+    usage: Option<UsageInfo>,
+) -> Result<ConversationMessage> {
+    let app = state.read().await;
+    let mut conversations = app.conversations.write().await;
+    conversations.append(&conversation_id, &role, &content, provider_id, usage).await
+}
+
+// Returns the exported text and also writes it when a path is given
+#[tauri::command]
+pub async fn export_conversation(
+    state: State<'_, AppState>,
+    conversation_id: String,
+    format: ExportFormat,
+    file_path: Option<String>,
+) -> Result<String> {
+    let app = state.read().await;
+    let conversations = app.conversations.read().await;
+    let content = conversations.export(&conversation_id, format)?;
+    if let Some(file_path) = file_path {
+        tokio::fs::write(&file_path, &content).await?;
+    }
+    Ok(content)
 }
 
 #[tauri::command]
@@ -167,6 +359,332 @@ pub async fn send_terminal_input(
     Ok(())
 }
 
+#[tauri::command]
+pub async fn write_terminal_input(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    data: String,
+) -> Result<()> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.write_input(&terminal_id, data.as_bytes()).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_terminal(
+    state: State<'_, AppState>,
+    title: Option<String>,
+    working_directory: Option<String>,
+    profile: Option<String>,
+) -> Result<String> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    let profile = terminal.resolve_profile(working_directory.as_deref(), profile.as_deref());
+    let session_id = terminal.create_session(title, profile).await?;
+    terminal.set_active_session(&session_id).await?;
+    Ok(session_id)
+}
+
+// Ends the session's shell and removes its pane, so it isn't restored on the
+// next launch
+#[tauri::command]
+pub async fn close_terminal(state: State<'_, AppState>, terminal_id: String) -> Result<()> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.close_session(&terminal_id).await
+}
+
+#[tauri::command]
+pub async fn get_terminal_output(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<OutputRange> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal.get_output(&terminal_id, from, to).await
+}
+
+#[tauri::command]
+pub async fn get_terminal_snapshot(state: State<'_, AppState>, terminal_id: String) -> Result<ScreenSnapshot> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal.get_snapshot(&terminal_id).await
+}
+
+#[tauri::command]
+pub async fn resize_terminal(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    cols: u16,
+    rows: u16,
+) -> Result<()> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.resize_terminal(&terminal_id, cols, rows).await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_command_blocks(state: State<'_, AppState>, terminal_id: String) -> Result<Vec<CommandBlock>> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal.get_command_blocks(&terminal_id).await
+}
+
+#[tauri::command]
+pub async fn get_command_history(state: State<'_, AppState>, terminal_id: String) -> Result<Vec<String>> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal.get_command_history(&terminal_id).await
+}
+
+#[tauri::command]
+pub async fn search_history(
+    state: State<'_, AppState>,
+    query: String,
+    options: Option<HistorySearchOptions>,
+) -> Result<Vec<RankedCommand>> {
+    let app = state.read().await;
+    let history = app.history.read().await;
+    Ok(history.search(&query, &options.unwrap_or_default()))
+}
+
+#[tauri::command]
+pub async fn get_directory_history(
+    state: State<'_, AppState>,
+    working_directory: String,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>> {
+    let app = state.read().await;
+    let history = app.history.read().await;
+    Ok(history.directory_history(&working_directory, limit.unwrap_or(50)))
+}
+
+#[tauri::command]
+pub async fn get_frequent_commands(
+    state: State<'_, AppState>,
+    working_directory: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<RankedCommand>> {
+    let app = state.read().await;
+    let history = app.history.read().await;
+    let mut options = HistorySearchOptions {
+        working_directory,
+        ..Default::default()
+    };
+    if let Some(limit) = limit {
+        options.limit = limit;
+    }
+    Ok(history.search("", &options))
+}
+
+#[tauri::command]
+pub async fn delete_history_command(state: State<'_, AppState>, command: String) -> Result<usize> {
+    let app = state.read().await;
+    let mut history = app.history.write().await;
+    history.delete_command(&command).await
+}
+
+#[tauri::command]
+pub async fn clear_history(state: State<'_, AppState>) -> Result<()> {
+    let app = state.read().await;
+    let mut history = app.history.write().await;
+    history.clear().await
+}
+
+#[tauri::command]
+pub async fn run_background_job(
+    state: State<'_, AppState>,
+    command: String,
+    working_directory: Option<String>,
+) -> Result<Job> {
+    let app = state.read().await;
+    let mut jobs = app.jobs.write().await;
+    jobs.run_job(&command, working_directory).await
+}
+
+#[tauri::command]
+pub async fn list_jobs(state: State<'_, AppState>) -> Result<Vec<Job>> {
+    let app = state.read().await;
+    let jobs = app.jobs.read().await;
+    Ok(jobs.list_jobs().await)
+}
+
+#[tauri::command]
+pub async fn get_job_output(
+    state: State<'_, AppState>,
+    job_id: String,
+    max_bytes: Option<u64>,
+) -> Result<String> {
+    let app = state.read().await;
+    let jobs = app.jobs.read().await;
+    jobs.get_job_output(&job_id, max_bytes).await
+}
+
+#[tauri::command]
+pub async fn cancel_job(state: State<'_, AppState>, job_id: String) -> Result<()> {
+    let app = state.read().await;
+    let mut jobs = app.jobs.write().await;
+    jobs.cancel_job(&job_id).await
+}
+
+#[tauri::command]
+pub async fn remove_job(state: State<'_, AppState>, job_id: String) -> Result<()> {
+    let app = state.read().await;
+    let mut jobs = app.jobs.write().await;
+    jobs.remove_job(&job_id).await
+}
+
+#[tauri::command]
+pub async fn get_command_output(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    block_id: u64,
+) -> Result<String> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal.get_command_output(&terminal_id, block_id).await
+}
+
+#[tauri::command]
+pub async fn get_pane_layouts(state: State<'_, AppState>) -> Result<Vec<PaneLayout>> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    Ok(terminal.get_layouts().await)
+}
+
+#[tauri::command]
+pub async fn split_pane(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    direction: SplitDirection,
+) -> Result<PaneLayout> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.split_pane(&terminal_id, direction).await
+}
+
+#[tauri::command]
+pub async fn close_pane(state: State<'_, AppState>, terminal_id: String) -> Result<Option<PaneLayout>> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.close_pane(&terminal_id).await
+}
+
+#[tauri::command]
+pub async fn focus_pane(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    direction: FocusDirection,
+) -> Result<Option<String>> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.focus_pane(&terminal_id, direction).await
+}
+
+#[tauri::command]
+pub async fn resize_pane_split(
+    state: State<'_, AppState>,
+    layout_id: String,
+    split_id: String,
+    ratio: f32,
+) -> Result<PaneLayout> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.resize_split(&layout_id, &split_id, ratio).await
+}
+
+#[tauri::command]
+pub async fn search_scrollback(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    pattern: String,
+    options: Option<SearchOptions>,
+) -> Result<SearchResult> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal
+        .search_scrollback(&terminal_id, &pattern, &options.unwrap_or_default())
+        .await
+}
+
+#[tauri::command]
+pub async fn search_all_sessions(
+    state: State<'_, AppState>,
+    pattern: String,
+    options: Option<SearchOptions>,
+) -> Result<Vec<SearchResult>> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal.search_all_sessions(&pattern, &options.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn find_scrollback_match(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    pattern: String,
+    options: Option<SearchOptions>,
+    line: u64,
+    column: usize,
+    direction: SearchDirection,
+) -> Result<Option<SearchMatch>> {
+    let app = state.read().await;
+    let terminal = app.terminal.read().await;
+    terminal
+        .find_match(&terminal_id, &pattern, &options.unwrap_or_default(), line, column, direction)
+        .await
+}
+
+#[tauri::command]
+pub async fn start_recording(
+    state: State<'_, AppState>,
+    terminal_id: String,
+    path: Option<String>,
+) -> Result<String> {
+    let app = state.read().await;
+    let redactor = {
+        let config = app.config.read().await;
+        Redactor::from_config(&config)
+    };
+    let mut terminal = app.terminal.write().await;
+    let path = terminal
+        .start_recording(&terminal_id, path.map(std::path::PathBuf::from), redactor)
+        .await?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn stop_recording(state: State<'_, AppState>, terminal_id: String) -> Result<String> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    let path = terminal.stop_recording(&terminal_id).await?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn play_recording(
+    state: State<'_, AppState>,
+    path: String,
+    speed: Option<f64>,
+) -> Result<String> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal
+        .play_recording(std::path::Path::new(&path), speed.unwrap_or(1.0))
+        .await
+}
+
+#[tauri::command]
+pub async fn stop_playback(state: State<'_, AppState>, terminal_id: String) -> Result<()> {
+    let app = state.read().await;
+    let mut terminal = app.terminal.write().await;
+    terminal.stop_playback(&terminal_id).await
+}
+
 #[tauri::command]
 pub async fn get_plugin_list(state: State<'_, AppState>) -> Result<Vec<String>> {
     let app = state.read().await;
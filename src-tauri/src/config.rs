@@ -1,4 +1,5 @@
 use crate::error::{HoverShellError, Result};
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -48,6 +49,42 @@ pub struct HotkeyConfig {
     pub close_tab: String,
     pub next_tab: String,
     pub prev_tab: String,
+    #[serde(default = "default_split_horizontal")]
+    pub split_horizontal: String,
+    #[serde(default = "default_split_vertical")]
+    pub split_vertical: String,
+    #[serde(default = "default_focus_pane_left")]
+    pub focus_pane_left: String,
+    #[serde(default = "default_focus_pane_right")]
+    pub focus_pane_right: String,
+    #[serde(default = "default_focus_pane_up")]
+    pub focus_pane_up: String,
+    #[serde(default = "default_focus_pane_down")]
+    pub focus_pane_down: String,
+}
+
+fn default_split_horizontal() -> String {
+    "cmd+d".to_string()
+}
+
+fn default_split_vertical() -> String {
+    "cmd+shift+d".to_string()
+}
+
+fn default_focus_pane_left() -> String {
+    "cmd+alt+left".to_string()
+}
+
+fn default_focus_pane_right() -> String {
+    "cmd+alt+right".to_string()
+}
+
+fn default_focus_pane_up() -> String {
+    "cmd+alt+up".to_string()
+}
+
+fn default_focus_pane_down() -> String {
+    "cmd+alt+down".to_string()
 }
 
 impl Default for HotkeyConfig {
@@ -60,6 +97,12 @@ impl Default for HotkeyConfig {
             close_tab: "cmd+w".to_string(),
             next_tab: "cmd+shift+]".to_string(),
             prev_tab: "cmd+shift+[".to_string(),
+            split_horizontal: default_split_horizontal(),
+            split_vertical: default_split_vertical(),
+            focus_pane_left: default_focus_pane_left(),
+            focus_pane_right: default_focus_pane_right(),
+            focus_pane_up: default_focus_pane_up(),
+            focus_pane_down: default_focus_pane_down(),
         }
     }
 }
@@ -125,6 +168,51 @@ pub struct TerminalConfig {
     pub cursor_style: String,
     pub bell_sound: bool,
     pub auto_close: bool,
+    #[serde(default = "default_restore_sessions")]
+    pub restore_sessions: bool,
+    #[serde(default)]
+    pub shell_args: Vec<String>,
+    #[serde(default)]
+    pub login_shell: bool,
+    // Named overrides selected by workspace rules or when opening a session
+    #[serde(default)]
+    pub profiles: std::collections::HashMap<String, TerminalProfile>,
+    #[serde(default)]
+    pub command_notifications: CommandNotificationsConfig,
+}
+
+// Notifications for long commands that finish while the window is hidden
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandNotificationsConfig {
+    pub enabled: bool,
+    // Minimum command duration in seconds
+    pub threshold: u64,
+    pub update_tray_status: bool,
+}
+
+impl Default for CommandNotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 10,
+            update_tray_status: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerminalProfile {
+    pub shell: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub login_shell: Option<bool>,
+    pub environment: std::collections::HashMap<String, String>,
+    pub working_directory: Option<String>,
+}
+
+fn default_restore_sessions() -> bool {
+    true
 }
 
 impl Default for TerminalConfig {
@@ -138,6 +226,11 @@ impl Default for TerminalConfig {
             cursor_style: "block".to_string(),
             bell_sound: true,
             auto_close: false,
+            restore_sessions: true,
+            shell_args: vec![],
+            login_shell: false,
+            profiles: std::collections::HashMap::new(),
+            command_notifications: CommandNotificationsConfig::default(),
         }
     }
 }
@@ -168,6 +261,18 @@ pub struct Config {
     pub plugins: std::collections::HashMap<String, PluginConfig>,
     pub workspace_rules: Vec<WorkspaceRule>,
     pub security: SecurityConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default)]
+    pub conversations: ConversationsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -185,6 +290,20 @@ pub struct SecurityConfig {
     pub minimal_scopes: bool,
     pub auto_lock: bool,
     pub lock_timeout: u64,
+    // Regexes whose matches are masked before terminal output is written
+    // to disk, e.g. in session recordings
+    #[serde(default = "default_redaction_patterns")]
+    pub redaction_patterns: Vec<String>,
+}
+
+fn default_redaction_patterns() -> Vec<String> {
+    vec![
+        r"AKIA[0-9A-Z]{16}".to_string(),
+        r"sk-[A-Za-z0-9_-]{20,}".to_string(),
+        r"gh[pousr]_[A-Za-z0-9]{36,}".to_string(),
+        r"(?i)bearer\s+[A-Za-z0-9._~+/-]+=*".to_string(),
+        r"(?i)(password|passwd|secret|token|api[_-]?key)\s*[=:]\s*\S+".to_string(),
+    ]
 }
 
 impl Default for SecurityConfig {
@@ -195,6 +314,149 @@ impl Default for SecurityConfig {
             minimal_scopes: true,
             auto_lock: false,
             lock_timeout: 300, // 5 minutes
+            redaction_patterns: default_redaction_patterns(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    pub max_entries: usize,
+    // Commands typed with a leading space are not recorded, like bash's
+    // HISTCONTROL=ignorespace
+    pub ignore_space_prefixed: bool,
+    // Regexes for commands that are never recorded
+    pub exclude_patterns: Vec<String>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 10000,
+            ignore_space_prefixed: true,
+            exclude_patterns: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobsConfig {
+    pub notify_on_finish: bool,
+    // Seconds after which a still running job raises a notification; 0
+    // disables it
+    pub long_running_threshold: u64,
+    // Finished jobs kept in the list, oldest are forgotten first
+    pub max_finished_jobs: usize,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            notify_on_finish: true,
+            long_running_threshold: 300,
+            max_finished_jobs: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    pub enabled: bool,
+    // Spending cap in USD for the calendar month across all providers;
+    // requests are refused once it is reached. Providers can set their own
+    // cap with `monthly_budget` in their `config`.
+    pub monthly_budget: Option<f64>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            monthly_budget: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailoverConfig {
+    // Retries per provider for rate limits, server and network errors
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    // Also the longest Retry-After honoured; a provider asking for more is
+    // skipped in favour of the next one
+    pub max_backoff_ms: u64,
+    // Provider IDs tried in order when the requested provider fails
+    pub fallback_chain: Vec<String>,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 10_000,
+            fallback_chain: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub rules: Vec<RoutingRule>,
+}
+
+// A rule applies when every condition it sets holds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingRule {
+    pub name: String,
+    // Leading word of the prompt, e.g. "@local"; removed before sending
+    pub prefix: Option<String>,
+    // Glob matched against the working directory and its parents
+    pub workspace: Option<String>,
+    // "text", "chat" or "streaming"
+    pub capability: Option<String>,
+    pub min_prompt_tokens: Option<u32>,
+    // Provider to use; the first matching rule that names one wins
+    pub provider: Option<String>,
+    // Providers with any of these tags are never used, e.g. ["cloud"]
+    pub deny_tags: Vec<String>,
+}
+
+// What happens to messages that no longer fit the model's context
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationOverflow {
+    // Leave them out
+    #[default]
+    Truncate,
+    // Have the provider replace them with a summary
+    Summarize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversationsConfig {
+    pub overflow: ConversationOverflow,
+    // Used for providers that don't report their context window
+    pub default_context_window: u32,
+    // Tokens kept free for the answer
+    pub reserve_tokens: u32,
+}
+
+impl Default for ConversationsConfig {
+    fn default() -> Self {
+        Self {
+            overflow: ConversationOverflow::Truncate,
+            default_context_window: 8192,
+            reserve_tokens: 1024,
         }
     }
 }
@@ -209,15 +471,44 @@ impl Default for Config {
             plugins: std::collections::HashMap::new(),
             workspace_rules: vec![],
             security: SecurityConfig::default(),
+            history: HistoryConfig::default(),
+            jobs: JobsConfig::default(),
+            usage: UsageConfig::default(),
+            failover: FailoverConfig::default(),
+            routing: RoutingConfig::default(),
+            conversations: ConversationsConfig::default(),
         }
     }
 }
 
+// Where the config and all saved state live: $HOVERSHELL_CONFIG_DIR, or
+// ~/.hovershell. Resolved once, so everything agrees on it for the life of
+// the process.
+static CONFIG_DIR: OnceCell<PathBuf> = OnceCell::new();
+
 impl Config {
     pub fn config_dir() -> PathBuf {
-        dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".hovershell")
+        CONFIG_DIR
+            .get_or_init(|| match std::env::var_os("HOVERSHELL_CONFIG_DIR") {
+                Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+                _ => dirs::home_dir()
+                    .unwrap_or_else(|| PathBuf::from("."))
+                    .join(".hovershell"),
+            })
+            .clone()
+    }
+
+    // Points the config directory somewhere else, e.g. a temporary one in
+    // tests. Only possible before anything has used it.
+    pub fn set_config_dir(dir: PathBuf) -> Result<()> {
+        let current = CONFIG_DIR.get_or_init(|| dir.clone());
+        if *current != dir {
+            return Err(HoverShellError::Config(format!(
+                "Config directory already in use: {}",
+                current.display()
+            )));
+        }
+        Ok(())
     }
 
     pub fn config_file() -> PathBuf {
@@ -0,0 +1,491 @@
+use crate::{
+    config::{Config, ConversationOverflow, ConversationsConfig},
+    error::{HoverShellError, Result},
+    providers::{
+        router::{estimate_tokens, RouteQuery},
+        ChatMessage, ProviderManager, RequestOptions, UsageInfo,
+    },
+    utils::write_atomic,
+};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+const UNTITLED: &str = "New conversation";
+// Titles taken from the first message are cut to this many characters
+const TITLE_LENGTH: usize = 60;
+// The role and separators cost a few tokens on top of a message's text
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    pub id: String,
+    // "system", "user" or "assistant"
+    pub role: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    // The provider that wrote an assistant message
+    pub provider_id: Option<String>,
+    pub usage: Option<UsageInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub messages: Vec<ConversationMessage>,
+    // Stands in for the first `summarized_count` messages once they no
+    // longer fit the model's context
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub summarized_count: usize,
+}
+
+impl Conversation {
+    // The given title, or the start of the first question
+    pub fn display_title(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        match self.messages.iter().find(|m| m.role == "user") {
+            Some(message) => {
+                let line = message.content.lines().next().unwrap_or_default().trim();
+                let mut title: String = line.chars().take(TITLE_LENGTH).collect();
+                if line.chars().count() > TITLE_LENGTH {
+                    title.push('…');
+                }
+                title
+            }
+            None => UNTITLED.to_string(),
+        }
+    }
+}
+
+// A conversation without its messages, for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationInfo {
+    pub id: String,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub message_count: usize,
+    pub total_tokens: u32,
+    pub last_provider_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+// The messages to send for a conversation and the ones left out for lack of
+// room, not counting those already summarized
+pub struct FittedContext {
+    pub messages: Vec<ChatMessage>,
+    pub dropped: Vec<ConversationMessage>,
+    // Index of the first message sent after the dropped ones
+    pub first_kept: usize,
+}
+
+pub struct ConversationManager {
+    conversations: HashMap<String, Conversation>,
+    conversations_config: ConversationsConfig,
+}
+
+impl ConversationManager {
+    pub async fn new() -> Result<Self> {
+        info!("Initializing conversation manager");
+
+        Ok(Self {
+            conversations: HashMap::new(),
+            conversations_config: ConversationsConfig::default(),
+        })
+    }
+
+    pub async fn initialize(&mut self, config: &Config) -> Result<()> {
+        self.conversations_config = config.conversations.clone();
+        self.conversations = load().await?
+            .into_iter()
+            .map(|conversation| (conversation.id.clone(), conversation))
+            .collect();
+
+        info!("Conversation manager loaded {} conversations", self.conversations.len());
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> Result<()> {
+        info!("Shutting down conversation manager");
+        Ok(())
+    }
+
+    pub fn config(&self) -> &ConversationsConfig {
+        &self.conversations_config
+    }
+
+    pub async fn create(&mut self, title: Option<String>) -> Result<Conversation> {
+        let now = Utc::now();
+        let conversation = Conversation {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+            created_at: now,
+            updated_at: now,
+            messages: Vec::new(),
+            summary: None,
+            summarized_count: 0,
+        };
+
+        save(&conversation).await?;
+        self.conversations.insert(conversation.id.clone(), conversation.clone());
+        info!("Created conversation {}", conversation.id);
+        Ok(conversation)
+    }
+
+    // Most recently updated first
+    pub fn list(&self) -> Vec<ConversationInfo> {
+        let mut list: Vec<ConversationInfo> = self.conversations.values()
+            .map(|conversation| ConversationInfo {
+                id: conversation.id.clone(),
+                title: conversation.display_title(),
+                created_at: conversation.created_at,
+                updated_at: conversation.updated_at,
+                message_count: conversation.messages.len(),
+                total_tokens: conversation.messages.iter()
+                    .filter_map(|m| m.usage.as_ref())
+                    .map(|usage| usage.total_tokens)
+                    .sum(),
+                last_provider_id: conversation.messages.iter()
+                    .rev()
+                    .find_map(|m| m.provider_id.clone()),
+            })
+            .collect();
+        list.sort_by_key(|info| std::cmp::Reverse(info.updated_at));
+        list
+    }
+
+    pub fn get(&self, conversation_id: &str) -> Result<&Conversation> {
+        self.conversations.get(conversation_id)
+            .ok_or_else(|| HoverShellError::Core(format!("Conversation not found: {}", conversation_id)))
+    }
+
+    fn get_mut(&mut self, conversation_id: &str) -> Result<&mut Conversation> {
+        self.conversations.get_mut(conversation_id)
+            .ok_or_else(|| HoverShellError::Core(format!("Conversation not found: {}", conversation_id)))
+    }
+
+    // An empty title goes back to the one taken from the first message
+    pub async fn rename(&mut self, conversation_id: &str, title: &str) -> Result<()> {
+        let conversation = self.get_mut(conversation_id)?;
+        let title = title.trim();
+        conversation.title = (!title.is_empty()).then(|| title.to_string());
+        conversation.updated_at = Utc::now();
+        save(conversation).await
+    }
+
+    pub async fn delete(&mut self, conversation_id: &str) -> Result<()> {
+        if self.conversations.remove(conversation_id).is_none() {
+            return Err(HoverShellError::Core(format!("Conversation not found: {}", conversation_id)));
+        }
+
+        let path = conversation_file(conversation_id);
+        if path.exists() {
+            tokio::fs::remove_file(&path).await?;
+        }
+        info!("Deleted conversation {}", conversation_id);
+        Ok(())
+    }
+
+    pub async fn append(
+        &mut self,
+        conversation_id: &str,
+        role: &str,
+        content: &str,
+        provider_id: Option<String>,
+        usage: Option<UsageInfo>,
+    ) -> Result<ConversationMessage> {
+        let message = new_message(role, content, provider_id, usage)?;
+        self.append_messages(conversation_id, vec![message.clone()]).await?;
+        Ok(message)
+    }
+
+    async fn append_messages(&mut self, conversation_id: &str, messages: Vec<ConversationMessage>) -> Result<()> {
+        let conversation = self.get_mut(conversation_id)?;
+        conversation.messages.extend(messages);
+        conversation.updated_at = Utc::now();
+        save(conversation).await
+    }
+
+    async fn set_summary(&mut self, conversation_id: &str, summary: String, summarized_count: usize) -> Result<()> {
+        let conversation = self.get_mut(conversation_id)?;
+        conversation.summary = Some(summary);
+        conversation.summarized_count = summarized_count;
+        save(conversation).await
+    }
+
+    pub fn export(&self, conversation_id: &str, format: ExportFormat) -> Result<String> {
+        let conversation = self.get(conversation_id)?;
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(conversation)
+                .map_err(|e| HoverShellError::Serialization(format!("Failed to serialize conversation: {}", e))),
+            ExportFormat::Markdown => Ok(to_markdown(conversation)),
+        }
+    }
+}
+
+// Sends a question in a conversation and stores it with the answer. Older
+// messages that don't fit the context of the provider the question is
+// routed to are left out or summarized, as configured.
+pub async fn send_message(
+    conversations: &RwLock<ConversationManager>,
+    providers: &ProviderManager,
+    conversation_id: &str,
+    content: &str,
+    provider_id: Option<&str>,
+    options: &RequestOptions,
+) -> Result<ConversationMessage> {
+    let question = new_message("user", content, None, None)?;
+    let (mut conversation, settings) = {
+        let conversations = conversations.read().await;
+        (conversations.get(conversation_id)?.clone(), conversations.config().clone())
+    };
+    conversation.messages.push(question.clone());
+
+    let route = providers.route(&RouteQuery {
+        prompt: content.to_string(),
+        context: None,
+        provider_id: provider_id.map(|id| id.to_string()),
+        capability: Some("chat".to_string()),
+        working_directory: options.working_directory.clone(),
+    })?;
+    let context_window = providers.get_provider_info(&route.provider_id)
+        .and_then(|info| info.max_tokens)
+        .unwrap_or(settings.default_context_window);
+    let budget = context_window.saturating_sub(settings.reserve_tokens);
+
+    let mut fitted = fit_context(&conversation, budget);
+    if settings.overflow == ConversationOverflow::Summarize && !fitted.dropped.is_empty() {
+        let prompt = summary_prompt(conversation.summary.as_deref(), &fitted.dropped, budget);
+        let summary_options = RequestOptions {
+            working_directory: options.working_directory.clone(),
+            ..Default::default()
+        };
+        match providers.execute_with_provider(&prompt, &route.provider_id, &summary_options).await {
+            Ok(summary) => {
+                conversations.write().await
+                    .set_summary(conversation_id, summary.content.clone(), fitted.first_kept)
+                    .await?;
+                conversation.summary = Some(summary.content);
+                conversation.summarized_count = fitted.first_kept;
+                fitted = fit_context(&conversation, budget);
+            }
+            Err(e) => warn!("Summarizing conversation {} failed, leaving old messages out: {}", conversation_id, e),
+        }
+    }
+    if !fitted.dropped.is_empty() {
+        info!(
+            "Left {} messages of conversation {} out of the context",
+            fitted.dropped.len(),
+            conversation_id
+        );
+    }
+
+    let response = providers.chat(fitted.messages, Some(&route.provider_id), options).await?;
+    let answered_by = response.metadata.get("provider_id")
+        .and_then(|id| id.as_str())
+        .unwrap_or(&route.provider_id)
+        .to_string();
+    let answer = new_message("assistant", &response.content, Some(answered_by), response.usage)?;
+
+    conversations.write().await
+        .append_messages(conversation_id, vec![question, answer.clone()])
+        .await?;
+    Ok(answer)
+}
+
+// System messages are always sent, then the summary, then as many of the
+// latest messages as fit in `budget` tokens. The newest message is sent
+// even if it alone is over.
+pub fn fit_context(conversation: &Conversation, budget: u32) -> FittedContext {
+    let start = conversation.summarized_count.min(conversation.messages.len());
+    let system: Vec<&ConversationMessage> = conversation.messages.iter()
+        .filter(|m| m.role == "system")
+        .collect();
+    let mut used: u32 = system.iter().map(|m| message_tokens(&m.content)).sum();
+    let summary = conversation.summary.as_ref()
+        .filter(|_| start > 0)
+        .map(|summary| format!("Summary of the earlier conversation:\n{}", summary));
+    used += summary.as_deref().map(message_tokens).unwrap_or(0);
+
+    let mut first_kept = conversation.messages.len();
+    for (index, message) in conversation.messages.iter().enumerate().skip(start).rev() {
+        if message.role == "system" {
+            continue;
+        }
+        let tokens = message_tokens(&message.content);
+        if used + tokens > budget && first_kept < conversation.messages.len() {
+            break;
+        }
+        used += tokens;
+        first_kept = index;
+    }
+
+    let mut messages: Vec<ChatMessage> = system.iter().map(|m| chat_message(m)).collect();
+    if let Some(summary) = summary {
+        messages.push(ChatMessage {
+            role: "system".to_string(),
+            content: summary,
+            timestamp: Utc::now(),
+        });
+    }
+    messages.extend(conversation.messages[first_kept..].iter()
+        .filter(|m| m.role != "system")
+        .map(chat_message));
+
+    FittedContext {
+        messages,
+        dropped: conversation.messages[start..first_kept].iter()
+            .filter(|m| m.role != "system")
+            .cloned()
+            .collect(),
+        first_kept,
+    }
+}
+
+fn message_tokens(content: &str) -> u32 {
+    estimate_tokens(content) + MESSAGE_OVERHEAD_TOKENS
+}
+
+fn chat_message(message: &ConversationMessage) -> ChatMessage {
+    ChatMessage {
+        role: message.role.clone(),
+        content: message.content.clone(),
+        timestamp: message.timestamp,
+    }
+}
+
+fn new_message(
+    role: &str,
+    content: &str,
+    provider_id: Option<String>,
+    usage: Option<UsageInfo>,
+) -> Result<ConversationMessage> {
+    if !matches!(role, "system" | "user" | "assistant") {
+        return Err(HoverShellError::Core(format!("Unknown message role: {}", role)));
+    }
+    Ok(ConversationMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        role: role.to_string(),
+        content: content.to_string(),
+        timestamp: Utc::now(),
+        provider_id,
+        usage,
+    })
+}
+
+// Asks for a summary that extends the previous one. When the transcript
+// itself is over budget, its newest messages are kept.
+fn summary_prompt(previous: Option<&str>, dropped: &[ConversationMessage], budget: u32) -> String {
+    let mut prompt = String::from(
+        "Summarize the following conversation in a few short paragraphs. Keep facts, \
+         decisions, names, commands and open questions that later messages may refer to.\n\n",
+    );
+    if let Some(previous) = previous {
+        prompt.push_str(&format!("Summary so far:\n{}\n\n", previous));
+    }
+
+    let mut used = estimate_tokens(&prompt);
+    let mut lines: Vec<String> = Vec::new();
+    for message in dropped.iter().rev() {
+        let line = format!("{}: {}", message.role, message.content);
+        let tokens = message_tokens(&line);
+        if used + tokens > budget && !lines.is_empty() {
+            break;
+        }
+        used += tokens;
+        lines.push(line);
+    }
+    lines.reverse();
+
+    prompt.push_str("Conversation:\n");
+    prompt.push_str(&lines.join("\n\n"));
+    prompt
+}
+
+fn to_markdown(conversation: &Conversation) -> String {
+    let mut markdown = format!(
+        "# {}\n\nStarted {}, {} messages\n",
+        conversation.display_title(),
+        conversation.created_at.format("%Y-%m-%d %H:%M UTC"),
+        conversation.messages.len()
+    );
+
+    for message in &conversation.messages {
+        let role = match message.role.as_str() {
+            "user" => "User",
+            "assistant" => "Assistant",
+            _ => "System",
+        };
+        let mut heading = format!("{} · {}", role, message.timestamp.format("%Y-%m-%d %H:%M UTC"));
+        if let Some(provider_id) = &message.provider_id {
+            heading.push_str(&format!(" · {}", provider_id));
+        }
+        if let Some(usage) = &message.usage {
+            heading.push_str(&format!(" · {} tokens", usage.total_tokens));
+        }
+        markdown.push_str(&format!("\n## {}\n\n{}\n", heading, message.content.trim_end()));
+    }
+
+    markdown
+}
+
+pub fn conversations_dir() -> PathBuf {
+    Config::config_dir().join("conversations")
+}
+
+fn conversation_file(conversation_id: &str) -> PathBuf {
+    conversations_dir().join(format!("{}.json", conversation_id))
+}
+
+// One file per conversation, so a message only rewrites its own thread
+async fn save(conversation: &Conversation) -> Result<()> {
+    let content = serde_json::to_string(conversation)
+        .map_err(|e| HoverShellError::Serialization(format!("Failed to serialize conversation: {}", e)))?;
+    write_atomic(&conversation_file(&conversation.id), content.as_bytes()).await
+}
+
+async fn load() -> Result<Vec<Conversation>> {
+    let dir = conversations_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut conversations = Vec::new();
+    let mut entries = tokio::fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        // One damaged file should not cost the other conversations
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Skipping unreadable conversation {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        match serde_json::from_str(&content) {
+            Ok(conversation) => conversations.push(conversation),
+            Err(e) => warn!("Skipping malformed conversation {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(conversations)
+}
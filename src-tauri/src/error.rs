@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 use thiserror::Error;
 
-#[derive(Error, Debug, Serialize, Deserialize)]
+#[derive(Error, Debug, Clone, Serialize, Deserialize)]
 pub enum HoverShellError {
     #[error("Configuration error: {0}")]
     Config(String),
@@ -10,6 +10,23 @@ pub enum HoverShellError {
     #[error("Provider error: {0}")]
     Provider(String),
 
+    // Failures reported by a provider's API, split out so the UI can react
+    // (ask for a new key, wait, shorten the prompt, pick another model)
+    #[error("Authentication failed for provider {provider}: {message}")]
+    ProviderAuth { provider: String, message: String },
+
+    #[error("Provider {provider} is rate limited: {message}")]
+    RateLimited { provider: String, message: String, retry_after: Option<u64> },
+
+    #[error("Prompt is too long for provider {provider}: {message}")]
+    ContextTooLong { provider: String, message: String },
+
+    #[error("Model not found for provider {provider}: {message}")]
+    ModelNotFound { provider: String, message: String },
+
+    #[error("Provider {provider} server error ({status}): {message}")]
+    ProviderServer { provider: String, status: u16, message: String },
+
     #[error("Terminal error: {0}")]
     Terminal(String),
 
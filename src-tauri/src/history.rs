@@ -0,0 +1,505 @@
+use crate::{
+    config::{Config, HistoryConfig},
+    error::{HoverShellError, Result},
+    terminal::{recording::Redactor, shell_integration::CommandBlock},
+    utils::write_atomic,
+};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_LIMIT: usize = 50;
+// A command's weight in the ranking halves every three days since it was run
+const RECENCY_HALF_LIFE_HOURS: f64 = 72.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub command: String,
+    pub working_directory: Option<String>,
+    pub session_id: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+    pub timestamp: DateTime<Utc>,
+    pub git_branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySearchOptions {
+    // Restricts results to commands run in this directory
+    pub working_directory: Option<String>,
+    pub session_id: Option<String>,
+    pub successful_only: bool,
+    pub limit: usize,
+}
+
+impl Default for HistorySearchOptions {
+    fn default() -> Self {
+        Self {
+            working_directory: None,
+            session_id: None,
+            successful_only: false,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+// One distinct command with its usage across all matching entries. Details
+// such as the exit code come from the most recent run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedCommand {
+    pub command: String,
+    pub count: usize,
+    pub last_used: DateTime<Utc>,
+    pub last_exit_code: Option<i32>,
+    pub working_directory: Option<String>,
+    pub git_branch: Option<String>,
+    pub score: f64,
+    // Character offsets of the query's matches, for highlighting
+    pub positions: Vec<usize>,
+}
+
+pub struct HistoryManager {
+    entries: Vec<HistoryEntry>,
+    // Lines in the history file, including ones already dropped from memory
+    file_entries: usize,
+    history_config: HistoryConfig,
+    exclude_patterns: Vec<Regex>,
+    redactor: Option<Redactor>,
+}
+
+impl HistoryManager {
+    pub async fn new() -> Result<Self> {
+        info!("Initializing history manager");
+
+        Ok(Self {
+            entries: Vec::new(),
+            file_entries: 0,
+            history_config: HistoryConfig::default(),
+            exclude_patterns: Vec::new(),
+            redactor: None,
+        })
+    }
+
+    pub async fn initialize(&mut self, config: &Config) -> Result<()> {
+        self.history_config = config.history.clone();
+        self.exclude_patterns = config.history.exclude_patterns.iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("Ignoring invalid history exclude pattern {}: {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        self.redactor = Some(Redactor::from_config(config));
+
+        self.entries = load().await?;
+        self.file_entries = self.entries.len();
+        if self.trim() {
+            self.rewrite().await?;
+        }
+
+        info!("History manager initialized with {} entries", self.entries.len());
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> Result<()> {
+        info!("Shutting down history manager");
+        Ok(())
+    }
+
+    // Records a finished command. Returns `None` if it was filtered out.
+    pub async fn record(&mut self, session_id: &str, block: &CommandBlock) -> Result<Option<HistoryEntry>> {
+        if !self.history_config.enabled || !self.should_record(&block.command) {
+            return Ok(None);
+        }
+
+        let command = match &self.redactor {
+            Some(redactor) => redactor.redact(block.command.trim()),
+            None => block.command.trim().to_string(),
+        };
+        let entry = HistoryEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            command,
+            working_directory: block.working_directory.clone(),
+            session_id: session_id.to_string(),
+            exit_code: block.exit_code,
+            duration_ms: block.duration_ms,
+            timestamp: block.finished_at.unwrap_or_else(Utc::now),
+            git_branch: block.working_directory.as_deref().and_then(|dir| git_branch(Path::new(dir))),
+        };
+
+        append(&entry).await?;
+        self.entries.push(entry.clone());
+        self.file_entries += 1;
+
+        // Compact the file once it has grown well past the limit rather than
+        // rewriting it on every command
+        self.trim();
+        if self.file_entries > self.history_config.max_entries + self.history_config.max_entries / 10 {
+            self.rewrite().await?;
+        }
+
+        Ok(Some(entry))
+    }
+
+    // Takes the command line as typed; it is only trimmed once recorded
+    fn should_record(&self, command: &str) -> bool {
+        let trimmed = command.trim();
+        if trimmed.is_empty() {
+            return false;
+        }
+        if self.history_config.ignore_space_prefixed && command.starts_with([' ', '\t']) {
+            return false;
+        }
+        !self.exclude_patterns.iter().any(|regex| regex.is_match(trimmed))
+    }
+
+    // Fuzzy search over distinct commands, ranked by how well they match
+    // and how often and how recently they were run
+    pub fn search(&self, query: &str, options: &HistorySearchOptions) -> Vec<RankedCommand> {
+        let query: Vec<char> = query.trim().chars().collect();
+        // Smart case: an uppercase letter in the query makes it case sensitive
+        let case_sensitive = query.iter().any(|c| c.is_uppercase());
+        let now = Utc::now();
+
+        let mut commands: HashMap<&str, RankedCommand> = HashMap::new();
+        for entry in self.entries.iter().filter(|entry| matches_options(entry, options)) {
+            let age_hours = (now - entry.timestamp).num_seconds().max(0) as f64 / 3600.0;
+            let weight = 0.5f64.powf(age_hours / RECENCY_HALF_LIFE_HOURS);
+
+            let ranked = commands.entry(entry.command.as_str()).or_insert_with(|| RankedCommand {
+                command: entry.command.clone(),
+                count: 0,
+                last_used: entry.timestamp,
+                last_exit_code: entry.exit_code,
+                working_directory: entry.working_directory.clone(),
+                git_branch: entry.git_branch.clone(),
+                score: 0.0,
+                positions: Vec::new(),
+            });
+            ranked.count += 1;
+            ranked.score += weight;
+            if entry.timestamp >= ranked.last_used {
+                ranked.last_used = entry.timestamp;
+                ranked.last_exit_code = entry.exit_code;
+                ranked.working_directory = entry.working_directory.clone();
+                ranked.git_branch = entry.git_branch.clone();
+            }
+        }
+
+        let mut results: Vec<RankedCommand> = commands
+            .into_values()
+            .filter_map(|mut ranked| {
+                let (match_score, positions) = fuzzy_match(&query, &ranked.command, case_sensitive)?;
+                ranked.score = match_score * (1.0 + ranked.score.ln_1p());
+                ranked.positions = positions;
+                Some(ranked)
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.score.total_cmp(&a.score).then_with(|| b.last_used.cmp(&a.last_used))
+        });
+        results.truncate(options.limit);
+        results
+    }
+
+    // Entries run in a directory, newest first
+    pub fn directory_history(&self, working_directory: &str, limit: usize) -> Vec<HistoryEntry> {
+        self.entries.iter()
+            .rev()
+            .filter(|entry| entry.working_directory.as_deref() == Some(working_directory))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Forgets every run of a command, e.g. one that leaked a secret
+    pub async fn delete_command(&mut self, command: &str) -> Result<usize> {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.command != command);
+        let removed = before - self.entries.len();
+        if removed > 0 {
+            self.rewrite().await?;
+        }
+        Ok(removed)
+    }
+
+    pub async fn clear(&mut self) -> Result<()> {
+        self.entries.clear();
+        self.rewrite().await?;
+        info!("Cleared command history");
+        Ok(())
+    }
+
+    // Drops the oldest entries beyond the limit. Returns true if any were
+    // dropped.
+    fn trim(&mut self) -> bool {
+        let excess = self.entries.len().saturating_sub(self.history_config.max_entries);
+        self.entries.drain(..excess);
+        excess > 0
+    }
+
+    async fn rewrite(&mut self) -> Result<()> {
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(&serialize_entry(entry)?);
+            content.push('\n');
+        }
+        write_atomic(&history_file(), content.as_bytes()).await?;
+
+        self.file_entries = self.entries.len();
+        Ok(())
+    }
+}
+
+pub fn history_file() -> PathBuf {
+    Config::config_dir().join("history.jsonl")
+}
+
+// History is an append-only JSON Lines file, so recording a command never
+// rewrites what is already on disk
+async fn append(entry: &HistoryEntry) -> Result<()> {
+    tokio::fs::create_dir_all(Config::config_dir()).await?;
+
+    let mut line = serialize_entry(entry)?;
+    line.push('\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_file())
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+async fn load() -> Result<Vec<HistoryEntry>> {
+    let path = history_file();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = tokio::fs::read_to_string(&path).await?;
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A line cut short by a crash should not cost the rest of the history
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("Skipping malformed history line {}: {}", index + 1, e),
+        }
+    }
+
+    Ok(entries)
+}
+
+fn serialize_entry(entry: &HistoryEntry) -> Result<String> {
+    serde_json::to_string(entry)
+        .map_err(|e| HoverShellError::Serialization(format!("Failed to serialize history entry: {}", e)))
+}
+
+fn matches_options(entry: &HistoryEntry, options: &HistorySearchOptions) -> bool {
+    if let Some(dir) = &options.working_directory {
+        if entry.working_directory.as_ref() != Some(dir) {
+            return false;
+        }
+    }
+    if let Some(session_id) = &options.session_id {
+        if &entry.session_id != session_id {
+            return false;
+        }
+    }
+    !options.successful_only || entry.exit_code == Some(0)
+}
+
+// Scores `candidate` against a query whose characters must appear in order.
+// Substrings score highest, then subsequences with consecutive characters
+// and matches at the start of words. An empty query matches everything.
+fn fuzzy_match(query: &[char], candidate: &str, case_sensitive: bool) -> Option<(f64, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((1.0, Vec::new()));
+    }
+
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let chars: Vec<char> = candidate.chars().map(fold).collect();
+    let query: Vec<char> = query.iter().copied().map(fold).collect();
+    let is_boundary = |index: usize| {
+        index == 0 || matches!(chars[index - 1], ' ' | '/' | '-' | '_' | '.' | '=' | '|' | ';')
+    };
+
+    if let Some(start) = chars.windows(query.len()).position(|window| window == query.as_slice()) {
+        let bonus = if start == 0 { 1.0 } else if is_boundary(start) { 0.5 } else { 0.0 };
+        return Some((2.0 + bonus, (start..start + query.len()).collect()));
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut points = 0.0;
+    let mut next = 0;
+    for (index, c) in chars.iter().enumerate() {
+        if next == query.len() {
+            break;
+        }
+        if *c != query[next] {
+            continue;
+        }
+        points += 1.0;
+        if positions.last().is_some_and(|last| last + 1 == index) {
+            points += 1.0;
+        }
+        if is_boundary(index) {
+            points += 1.0;
+        }
+        positions.push(index);
+        next += 1;
+    }
+    if next < query.len() {
+        return None;
+    }
+
+    // Normalized so a subsequence always ranks below a substring
+    let span = positions[positions.len() - 1] - positions[0] + 1;
+    let compactness = query.len() as f64 / span as f64;
+    Some((points / (3.0 * query.len() as f64) + compactness, positions))
+}
+
+// The checked-out branch of the repository containing `dir`, or the short
+// commit id when HEAD is detached
+fn git_branch(dir: &Path) -> Option<String> {
+    for dir in dir.ancestors() {
+        let dot_git = dir.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else if dot_git.is_file() {
+            // Worktrees and submodules point to the real git directory
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            dir.join(content.strip_prefix("gitdir:")?.trim())
+        } else {
+            continue;
+        };
+
+        let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        return Some(match head.strip_prefix("ref: refs/heads/") {
+            Some(branch) => branch.to_string(),
+            None => head.chars().take(7).collect(),
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn manager(ignore_space_prefixed: bool, exclude_patterns: &[&str], entries: Vec<HistoryEntry>) -> HistoryManager {
+        HistoryManager {
+            file_entries: entries.len(),
+            entries,
+            history_config: HistoryConfig {
+                ignore_space_prefixed,
+                ..Default::default()
+            },
+            exclude_patterns: exclude_patterns.iter().map(|pattern| Regex::new(pattern).unwrap()).collect(),
+            redactor: None,
+        }
+    }
+
+    fn entry(command: &str, hours_ago: i64, exit_code: i32) -> HistoryEntry {
+        HistoryEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            command: command.to_string(),
+            working_directory: None,
+            session_id: "session".to_string(),
+            exit_code: Some(exit_code),
+            duration_ms: None,
+            timestamp: Utc::now() - Duration::hours(hours_ago),
+            git_branch: None,
+        }
+    }
+
+    fn commands(results: &[RankedCommand]) -> Vec<&str> {
+        results.iter().map(|ranked| ranked.command.as_str()).collect()
+    }
+
+    #[test]
+    fn skips_space_prefixed_and_blank_commands() {
+        let history = manager(true, &[], Vec::new());
+        assert!(history.should_record("ls -la"));
+        assert!(!history.should_record(" export TOKEN=abc"));
+        assert!(!history.should_record("\tvault login"));
+        assert!(!history.should_record(""));
+        assert!(!history.should_record("   \n"));
+
+        let history = manager(false, &[], Vec::new());
+        assert!(history.should_record(" export TOKEN=abc"));
+        assert!(!history.should_record("   "));
+    }
+
+    #[test]
+    fn skips_excluded_commands() {
+        let history = manager(false, &["^export ", "password"], Vec::new());
+        assert!(!history.should_record("export AWS_SECRET=1"));
+        // Patterns see the command without the space it was typed with
+        assert!(!history.should_record("  export AWS_SECRET=1"));
+        assert!(!history.should_record("mysql --password=hunter2"));
+        assert!(history.should_record("echo $PATH"));
+    }
+
+    #[test]
+    fn ranks_substrings_above_subsequences() {
+        let query: Vec<char> = "stat".chars().collect();
+        let (start, positions) = fuzzy_match(&query, "status --short", false).unwrap();
+        assert_eq!(start, 3.0);
+        assert_eq!(positions, vec![0, 1, 2, 3]);
+
+        let (boundary, _) = fuzzy_match(&query, "git status", false).unwrap();
+        let (inside, _) = fuzzy_match(&query, "ecstatic", false).unwrap();
+        let (subsequence, positions) = fuzzy_match(&query, "sort -t a t", false).unwrap();
+        assert!(start > boundary && boundary > inside && inside > subsequence);
+        assert!(subsequence < 2.0);
+        assert_eq!(positions, vec![0, 3, 8, 10]);
+
+        assert!(fuzzy_match(&query, "tats", false).is_none());
+        assert!(fuzzy_match(&['S'], "git status", true).is_none());
+    }
+
+    #[test]
+    fn ranks_by_frequency_and_recency() {
+        let mut entries = Vec::new();
+        // Run often, but a month ago
+        for _ in 0..5 {
+            entries.push(entry("make build", 24 * 30, 0));
+        }
+        entries.push(entry("make test", 2, 0));
+        entries.push(entry("make lint", 1, 0));
+        entries.push(entry("make lint", 0, 2));
+        let history = manager(true, &[], entries);
+
+        let results = history.search("make", &HistorySearchOptions::default());
+        assert_eq!(commands(&results), vec!["make lint", "make test", "make build"]);
+        assert_eq!(results[0].count, 2);
+        assert_eq!(results[0].last_exit_code, Some(2));
+        assert_eq!(results[2].count, 5);
+        assert_eq!(results[0].positions, vec![0, 1, 2, 3]);
+
+        // Smart case: uppercase in the query only matches uppercase
+        assert!(history.search("Make", &HistorySearchOptions::default()).is_empty());
+
+        let options = HistorySearchOptions {
+            successful_only: true,
+            limit: 1,
+            ..Default::default()
+        };
+        assert_eq!(commands(&history.search("mk", &options)), vec!["make lint"]);
+    }
+}
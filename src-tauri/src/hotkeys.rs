@@ -45,22 +45,28 @@ impl HotkeyManager {
     pub async fn register_default_hotkeys(&mut self, app_handle: &AppHandle, config: &Config) -> Result<()> {
         self.app_handle = Some(app_handle.clone());
         
-        let triggers = &config.triggers;
-        
-        // Register toggle hotkey
-        self.register(app_handle, &triggers.hotkeys.toggle, "toggle_window").await?;
-        
-        // Register paste and run hotkey
-        self.register(app_handle, &triggers.hotkeys.paste_run, "paste_run").await?;
-        
-        // Register quick hide hotkey
-        self.register(app_handle, &triggers.hotkeys.quick_hide, "quick_hide").await?;
-        
-        // Register tab management hotkeys
-        self.register(app_handle, &triggers.hotkeys.new_tab, "new_tab").await?;
-        self.register(app_handle, &triggers.hotkeys.close_tab, "close_tab").await?;
-        self.register(app_handle, &triggers.hotkeys.next_tab, "next_tab").await?;
-        self.register(app_handle, &triggers.hotkeys.prev_tab, "prev_tab").await?;
+        let hotkeys = &config.triggers.hotkeys;
+        
+        // Pane bindings (split, focus) are handled in-window by the frontend;
+        // registering them globally would take keys like Ctrl+D away from
+        // every other app
+        let bindings = [
+            (&hotkeys.toggle, "toggle_window"),
+            (&hotkeys.paste_run, "paste_run"),
+            (&hotkeys.quick_hide, "quick_hide"),
+            (&hotkeys.new_tab, "new_tab"),
+            (&hotkeys.close_tab, "close_tab"),
+            (&hotkeys.next_tab, "next_tab"),
+            (&hotkeys.prev_tab, "prev_tab"),
+        ];
+        
+        // A binding another app already holds shouldn't keep the rest from
+        // registering
+        for (hotkey, callback) in bindings {
+            if let Err(e) = self.register(app_handle, hotkey, callback).await {
+                error!("Skipping hotkey for {}: {}", callback, e);
+            }
+        }
         
         info!("Registered default hotkeys");
         Ok(())
@@ -0,0 +1,519 @@
+use crate::{
+    config::{Config, JobsConfig, TerminalConfig, WorkspaceRule},
+    error::{HoverShellError, Result},
+    terminal::profile,
+    utils::format_duration,
+};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::process::Child;
+use tokio::sync::{mpsc, oneshot, RwLock};
+
+pub const JOB_UPDATED_EVENT: &str = "job-updated";
+
+// How long a cancelled job gets to exit after SIGTERM before it is killed
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+const DEFAULT_OUTPUT_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub command: String,
+    pub working_directory: String,
+    pub status: JobStatus,
+    pub pid: Option<u32>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<u64>,
+    pub output_path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct JobNotification {
+    pub title: String,
+    pub body: String,
+}
+
+type Jobs = Arc<RwLock<HashMap<String, Job>>>;
+
+struct RunningJob {
+    cancel: oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+// Runs commands detached from any terminal session. Output goes to a log
+// file per job under ~/.hovershell/jobs.
+pub struct JobManager {
+    jobs: Jobs,
+    running: HashMap<String, RunningJob>,
+    app_handle: Option<AppHandle>,
+    jobs_config: JobsConfig,
+    terminal_config: TerminalConfig,
+    workspace_rules: Vec<WorkspaceRule>,
+    notification_tx: mpsc::UnboundedSender<JobNotification>,
+    notification_rx: Option<mpsc::UnboundedReceiver<JobNotification>>,
+}
+
+impl JobManager {
+    pub async fn new() -> Result<Self> {
+        info!("Initializing job manager");
+
+        let (notification_tx, notification_rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            running: HashMap::new(),
+            app_handle: None,
+            jobs_config: JobsConfig::default(),
+            terminal_config: TerminalConfig::default(),
+            workspace_rules: Vec::new(),
+            notification_tx,
+            notification_rx: Some(notification_rx),
+        })
+    }
+
+    pub async fn initialize(&mut self, config: &Config, app_handle: &AppHandle) -> Result<()> {
+        self.app_handle = Some(app_handle.clone());
+        self.jobs_config = config.jobs.clone();
+        self.terminal_config = config.terminal.clone();
+        self.workspace_rules = config.workspace_rules.clone();
+
+        info!("Job manager initialized");
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> Result<()> {
+        info!("Shutting down job manager");
+
+        // Jobs are children of the app, so stop them rather than leave them
+        // running unsupervised
+        let mut tasks = Vec::new();
+        for (_, running) in self.running.drain() {
+            let _ = running.cancel.send(());
+            tasks.push(running.task);
+        }
+        futures_util::future::join_all(tasks).await;
+
+        Ok(())
+    }
+
+    // Notifications are delivered through this channel so the caller can
+    // show them without holding the manager lock. Returns `None` after the
+    // first call.
+    pub fn take_notification_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<JobNotification>> {
+        self.notification_rx.take()
+    }
+
+    pub async fn run_job(&mut self, command: &str, working_directory: Option<String>) -> Result<Job> {
+        if command.trim().is_empty() {
+            return Err(HoverShellError::Terminal("Job command is empty".to_string()));
+        }
+
+        // Jobs run with the same shell and environment a new session in that
+        // directory would get
+        let profile = profile::resolve(
+            &self.terminal_config,
+            &self.workspace_rules,
+            working_directory.as_deref(),
+            None,
+        );
+        if !Path::new(&profile.working_directory).is_dir() {
+            return Err(HoverShellError::Terminal(format!(
+                "Working directory does not exist: {}",
+                profile.working_directory
+            )));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(jobs_dir()).await?;
+        let output_path = jobs_dir().join(format!("{}.log", id));
+        let output = std::fs::File::create(&output_path)?;
+
+        let mut shell = tokio::process::Command::new(&profile.shell);
+        if profile.login_shell {
+            shell.arg("-l");
+        }
+        let child = shell
+            .arg("-c")
+            .arg(command)
+            .current_dir(&profile.working_directory)
+            .envs(&profile.environment)
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(output.try_clone()?))
+            .stderr(Stdio::from(output))
+            // A group of its own lets cancel reach everything the job started
+            .process_group(0)
+            .spawn()
+            .map_err(|e| HoverShellError::Terminal(format!("Failed to start job: {}", e)))?;
+
+        let job = Job {
+            id: id.clone(),
+            command: command.to_string(),
+            working_directory: profile.working_directory,
+            status: JobStatus::Running,
+            pid: child.id(),
+            exit_code: None,
+            signal: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            duration_ms: None,
+            output_path,
+        };
+        self.jobs.write().await.insert(id.clone(), job.clone());
+        emit_job(&self.app_handle, &job);
+
+        let (cancel, cancel_rx) = oneshot::channel();
+        let task = tokio::spawn(supervise_job(
+            id.clone(),
+            child,
+            cancel_rx,
+            self.jobs.clone(),
+            self.jobs_config.clone(),
+            self.app_handle.clone(),
+            self.notification_tx.clone(),
+        ));
+        self.running.retain(|_, running| !running.task.is_finished());
+        self.running.insert(id.clone(), RunningJob { cancel, task });
+        self.prune_finished().await;
+
+        info!("Started job {}: {}", id, command);
+        Ok(job)
+    }
+
+    // Jobs, newest first
+    pub async fn list_jobs(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+        jobs
+    }
+
+    pub async fn get_job(&self, job_id: &str) -> Result<Job> {
+        self.jobs.read().await
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| HoverShellError::Terminal(format!("Job not found: {}", job_id)))
+    }
+
+    // The end of a job's output, at most `max_bytes` long
+    pub async fn get_job_output(&self, job_id: &str, max_bytes: Option<u64>) -> Result<String> {
+        let job = self.get_job(job_id).await?;
+        let max_bytes = max_bytes.unwrap_or(DEFAULT_OUTPUT_BYTES);
+
+        let mut file = std::fs::File::open(&job.output_path)?;
+        let length = file.metadata()?.len();
+        file.seek(SeekFrom::Start(length.saturating_sub(max_bytes)))?;
+        let mut output = Vec::new();
+        file.read_to_end(&mut output)?;
+
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+
+    pub async fn cancel_job(&mut self, job_id: &str) -> Result<()> {
+        let job = self.get_job(job_id).await?;
+        if job.status != JobStatus::Running {
+            return Err(HoverShellError::Terminal(format!("Job is not running: {}", job_id)));
+        }
+
+        if let Some(running) = self.running.remove(job_id) {
+            let _ = running.cancel.send(());
+        }
+
+        info!("Cancelling job: {}", job_id);
+        Ok(())
+    }
+
+    // Forgets a finished job and deletes its output
+    pub async fn remove_job(&mut self, job_id: &str) -> Result<()> {
+        let job = self.get_job(job_id).await?;
+        if job.status == JobStatus::Running {
+            return Err(HoverShellError::Terminal(format!("Job is still running: {}", job_id)));
+        }
+
+        self.jobs.write().await.remove(job_id);
+        remove_output(&job.output_path).await;
+        Ok(())
+    }
+
+    async fn prune_finished(&mut self) {
+        // Output is deleted after the lock is released
+        let outputs: Vec<PathBuf> = {
+            let mut jobs = self.jobs.write().await;
+            let mut finished: Vec<(DateTime<Utc>, String)> = jobs.values()
+                .filter(|job| job.status != JobStatus::Running)
+                .map(|job| (job.started_at, job.id.clone()))
+                .collect();
+            if finished.len() <= self.jobs_config.max_finished_jobs {
+                return;
+            }
+
+            finished.sort();
+            let excess = finished.len() - self.jobs_config.max_finished_jobs;
+            finished.into_iter()
+                .take(excess)
+                .filter_map(|(_, job_id)| jobs.remove(&job_id))
+                .map(|job| job.output_path)
+                .collect()
+        };
+
+        for path in outputs {
+            remove_output(&path).await;
+        }
+    }
+}
+
+pub fn jobs_dir() -> PathBuf {
+    Config::config_dir().join("jobs")
+}
+
+async fn remove_output(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        warn!("Failed to remove job output {}: {}", path.display(), e);
+    }
+}
+
+// Waits for a job to finish while watching for cancellation and the long
+// running threshold, then records how it ended
+async fn supervise_job(
+    job_id: String,
+    mut child: Child,
+    mut cancel: oneshot::Receiver<()>,
+    jobs: Jobs,
+    jobs_config: JobsConfig,
+    app_handle: Option<AppHandle>,
+    notifications: mpsc::UnboundedSender<JobNotification>,
+) {
+    let command = match jobs.read().await.get(&job_id) {
+        Some(job) => job.command.clone(),
+        None => return,
+    };
+
+    let threshold = Duration::from_secs(jobs_config.long_running_threshold);
+    let long_running = tokio::time::sleep(threshold);
+    tokio::pin!(long_running);
+    let mut long_running_notified = jobs_config.long_running_threshold == 0;
+    let mut cancel_closed = false;
+    let mut cancelled = false;
+
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status,
+            _ = &mut long_running, if !long_running_notified => {
+                long_running_notified = true;
+                let _ = notifications.send(JobNotification {
+                    title: "Job still running".to_string(),
+                    body: format!("{} has been running for {}", command, format_duration(threshold.as_secs())),
+                });
+            }
+            result = &mut cancel, if !cancel_closed => {
+                cancel_closed = true;
+                // The sender is only dropped without sending when the
+                // manager forgets the job, which is not a cancel
+                if result.is_ok() {
+                    cancelled = true;
+                    break terminate(&mut child).await;
+                }
+            }
+        }
+    };
+
+    let finished_at = Utc::now();
+    let job = {
+        let mut jobs = jobs.write().await;
+        let Some(job) = jobs.get_mut(&job_id) else {
+            return;
+        };
+
+        match status {
+            Ok(status) => {
+                job.exit_code = status.code();
+                job.signal = status.signal();
+                job.status = if cancelled {
+                    JobStatus::Cancelled
+                } else if status.success() {
+                    JobStatus::Succeeded
+                } else {
+                    JobStatus::Failed
+                };
+            }
+            Err(e) => {
+                error!("Failed to wait for job {}: {}", job_id, e);
+                job.status = JobStatus::Failed;
+            }
+        }
+        job.finished_at = Some(finished_at);
+        job.duration_ms = Some((finished_at - job.started_at).num_milliseconds().max(0) as u64);
+        job.clone()
+    };
+
+    info!("Job {} finished with status {:?}", job_id, job.status);
+    emit_job(&app_handle, &job);
+
+    if jobs_config.notify_on_finish && job.status != JobStatus::Cancelled {
+        let duration = format_duration(job.duration_ms.unwrap_or(0) / 1000);
+        let (title, body) = match (job.status, job.exit_code, job.signal) {
+            (JobStatus::Succeeded, _, _) => ("Job finished", format!("{} completed in {}", command, duration)),
+            (_, Some(code), _) => ("Job failed", format!("{} exited with code {} after {}", command, code, duration)),
+            (_, _, Some(signal)) => ("Job failed", format!("{} was killed by signal {} after {}", command, signal, duration)),
+            _ => ("Job failed", format!("{} failed after {}", command, duration)),
+        };
+        let _ = notifications.send(JobNotification {
+            title: title.to_string(),
+            body,
+        });
+    }
+}
+
+async fn terminate(child: &mut Child) -> std::io::Result<std::process::ExitStatus> {
+    if let Some(pid) = child.id() {
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGTERM);
+        }
+        if let Ok(status) = tokio::time::timeout(CANCEL_GRACE_PERIOD, child.wait()).await {
+            return status;
+        }
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+    child.wait().await
+}
+
+fn emit_job(app_handle: &Option<AppHandle>, job: &Job) {
+    if let Some(app_handle) = app_handle {
+        if let Err(e) = app_handle.emit_all(JOB_UPDATED_EVENT, job) {
+            error!("Failed to emit job update: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    async fn manager(max_finished_jobs: usize) -> JobManager {
+        // Keeps job logs out of the real config directory
+        let _ = Config::set_config_dir(std::env::temp_dir().join(format!("hovershell-unit-tests-{}", std::process::id())));
+
+        let mut manager = JobManager::new().await.unwrap();
+        manager.terminal_config.shell = "/bin/sh".to_string();
+        manager.terminal_config.login_shell = false;
+        manager.jobs_config = JobsConfig {
+            notify_on_finish: false,
+            long_running_threshold: 0,
+            max_finished_jobs,
+        };
+        manager
+    }
+
+    async fn run(manager: &mut JobManager, command: &str) -> Job {
+        let working_directory = std::env::temp_dir().to_string_lossy().to_string();
+        manager.run_job(command, Some(working_directory)).await.unwrap()
+    }
+
+    async fn wait_until_finished(manager: &JobManager, job_id: &str) -> Job {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let job = manager.get_job(job_id).await.unwrap();
+            if job.status != JobStatus::Running || Instant::now() > deadline {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn records_how_jobs_exit() {
+        let mut manager = manager(50).await;
+
+        let job = run(&mut manager, "echo hello").await;
+        assert_eq!(job.status, JobStatus::Running);
+        let job = wait_until_finished(&manager, &job.id).await;
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.exit_code, Some(0));
+        assert!(job.duration_ms.is_some());
+        assert_eq!(manager.get_job_output(&job.id, None).await.unwrap(), "hello\n");
+
+        let job = run(&mut manager, "echo oops >&2; exit 3").await;
+        let job = wait_until_finished(&manager, &job.id).await;
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.exit_code, Some(3));
+        assert_eq!(manager.get_job_output(&job.id, None).await.unwrap(), "oops\n");
+
+        assert!(manager.run_job("  ", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_the_whole_process_group() {
+        let mut manager = manager(50).await;
+
+        // The subshell would outlive its parent if only the shell was signalled
+        let job = run(&mut manager, "(sleep 1; echo survived) & wait").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        manager.cancel_job(&job.id).await.unwrap();
+
+        let job = wait_until_finished(&manager, &job.id).await;
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert_eq!(job.signal, Some(libc::SIGTERM));
+        assert!(manager.cancel_job(&job.id).await.is_err());
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(manager.get_job_output(&job.id, None).await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn returns_the_tail_of_job_output() {
+        let mut manager = manager(50).await;
+
+        let job = run(&mut manager, "printf 0123456789").await;
+        wait_until_finished(&manager, &job.id).await;
+        assert_eq!(manager.get_job_output(&job.id, Some(4)).await.unwrap(), "6789");
+        assert_eq!(manager.get_job_output(&job.id, Some(100)).await.unwrap(), "0123456789");
+        assert_eq!(manager.get_job_output(&job.id, None).await.unwrap(), "0123456789");
+    }
+
+    #[tokio::test]
+    async fn prunes_oldest_finished_jobs() {
+        let mut manager = manager(2).await;
+
+        let mut finished = Vec::new();
+        for i in 0..3 {
+            let job = run(&mut manager, &format!("echo {}", i)).await;
+            finished.push(wait_until_finished(&manager, &job.id).await);
+        }
+        for job in &finished {
+            assert!(job.output_path.exists());
+        }
+
+        // Pruning happens when the next job starts
+        let job = run(&mut manager, "true").await;
+        assert!(manager.get_job(&finished[0].id).await.is_err());
+        assert!(!finished[0].output_path.exists());
+        for job in &finished[1..] {
+            assert!(manager.get_job(&job.id).await.is_ok());
+            assert!(job.output_path.exists());
+        }
+
+        wait_until_finished(&manager, &job.id).await;
+        manager.shutdown().await.unwrap();
+    }
+}
@@ -4,9 +4,12 @@
 pub mod app;
 pub mod commands;
 pub mod config;
+pub mod conversations;
 pub mod core;
 pub mod error;
+pub mod history;
 pub mod hotkeys;
+pub mod jobs;
 pub mod menu;
 pub mod providers;
 pub mod terminal;
@@ -27,12 +27,57 @@ async fn main() -> Result<(), HoverShellError> {
             hovershell::commands::get_config,
             hovershell::commands::set_config,
             hovershell::commands::get_providers,
+            hovershell::commands::list_provider_models,
             hovershell::commands::add_provider,
             hovershell::commands::remove_provider,
             hovershell::commands::set_default_provider,
             hovershell::commands::execute_command,
+            hovershell::commands::stream_ai_response,
+            hovershell::commands::explain_ai_route,
+            hovershell::commands::cancel_ai_request,
+            hovershell::commands::get_ai_usage,
+            hovershell::commands::get_ai_usage_summary,
+            hovershell::commands::create_conversation,
+            hovershell::commands::list_conversations,
+            hovershell::commands::get_conversation,
+            hovershell::commands::rename_conversation,
+            hovershell::commands::delete_conversation,
+            hovershell::commands::send_conversation_message,
+            hovershell::commands::append_conversation_message,
+            hovershell::commands::export_conversation,
             hovershell::commands::get_terminal_state,
             hovershell::commands::send_terminal_input,
+            hovershell::commands::write_terminal_input,
+            hovershell::commands::create_terminal,
+            hovershell::commands::close_terminal,
+            hovershell::commands::get_terminal_output,
+            hovershell::commands::get_terminal_snapshot,
+            hovershell::commands::resize_terminal,
+            hovershell::commands::get_command_blocks,
+            hovershell::commands::get_command_output,
+            hovershell::commands::get_command_history,
+            hovershell::commands::search_history,
+            hovershell::commands::get_directory_history,
+            hovershell::commands::get_frequent_commands,
+            hovershell::commands::delete_history_command,
+            hovershell::commands::clear_history,
+            hovershell::commands::run_background_job,
+            hovershell::commands::list_jobs,
+            hovershell::commands::get_job_output,
+            hovershell::commands::cancel_job,
+            hovershell::commands::remove_job,
+            hovershell::commands::get_pane_layouts,
+            hovershell::commands::split_pane,
+            hovershell::commands::close_pane,
+            hovershell::commands::focus_pane,
+            hovershell::commands::resize_pane_split,
+            hovershell::commands::search_scrollback,
+            hovershell::commands::search_all_sessions,
+            hovershell::commands::find_scrollback_match,
+            hovershell::commands::start_recording,
+            hovershell::commands::stop_recording,
+            hovershell::commands::play_recording,
+            hovershell::commands::stop_playback,
             hovershell::commands::get_plugin_list,
             hovershell::commands::load_plugin,
             hovershell::commands::unload_plugin,
@@ -170,6 +215,12 @@ async fn main() -> Result<(), HoverShellError> {
                     if let Some(window) = event.window().get_webview_window() {
                         let _ = window.hide();
                     }
+
+                    // Hidden windows don't wait for AI answers
+                    let state = event.window().state::<AppState>().inner().clone();
+                    tauri::async_runtime::spawn(async move {
+                        state.read().await.panel.hide().await;
+                    });
                 }
                 _ => {}
             }
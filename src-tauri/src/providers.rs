@@ -1,12 +1,33 @@
+pub mod errors;
+pub mod failover;
+pub mod mock;
+pub mod openai_compatible;
+pub mod router;
+pub mod streaming;
+pub mod usage;
+
 use crate::{
-    config::{Config, ProviderConfig},
+    config::{Config, FailoverConfig, ProviderConfig},
     error::{HoverShellError, Result},
 };
+use futures_util::{
+    future::{AbortHandle, AbortRegistration, Abortable},
+    stream, Future, StreamExt,
+};
 use log::{error, info};
+use mock::MockProvider;
+use openai_compatible::OpenAICompatibleProvider;
+use router::{PromptRouter, RouteDecision, RouteQuery};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use streaming::{ProviderStream, StreamChunk, StreamStep};
+use tauri::{AppHandle, Manager};
 use tokio::sync::RwLock;
+use usage::{UsageQuery, UsageRecord, UsageSummary, UsageTracker};
+
+pub const AI_STREAM_CHUNK_EVENT: &str = "ai-stream-chunk";
+pub const AI_STREAM_END_EVENT: &str = "ai-stream-end";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderResponse {
@@ -23,6 +44,15 @@ pub struct UsageInfo {
     pub cost: Option<f64>,
 }
 
+impl UsageInfo {
+    // Adds up counts a stream reported in several pieces
+    pub fn add(&mut self, other: &UsageInfo) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
@@ -30,11 +60,20 @@ pub struct ChatMessage {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+#[async_trait::async_trait]
 pub trait AIProvider: Send + Sync {
     async fn execute(&self, prompt: &str, context: Option<&str>) -> Result<ProviderResponse>;
     async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ProviderResponse>;
-    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<Box<dyn futures_util::Stream<Item = Result<String>> + Unpin>>;
+    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<ProviderStream>;
     fn get_info(&self) -> ProviderInfo;
+
+    // Models the endpoint serves, for providers that can list them
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Err(HoverShellError::Provider(format!(
+            "Provider {} can't list its models",
+            self.get_info().id
+        )))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -48,9 +87,117 @@ pub struct ProviderInfo {
     pub supports_chat: bool,
 }
 
+// Payload of `ai-stream-chunk`, one per piece of text from the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamChunkEvent {
+    pub stream_id: String,
+    pub index: u64,
+    pub content: String,
+}
+
+// Payload of `ai-stream-end`, sent exactly once per stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamEndEvent {
+    pub stream_id: String,
+    // The provider that answered, which may be a fallback
+    pub provider_id: Option<String>,
+    pub error: Option<HoverShellError>,
+    pub cancelled: bool,
+    pub usage: Option<UsageInfo>,
+}
+
+// How a caller identifies a provider call. Without an ID one is generated;
+// a new request in a scope (e.g. a terminal session) cancels the previous one.
+// The working directory feeds workspace routing rules.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestOptions {
+    pub request_id: Option<String>,
+    pub scope: Option<String>,
+    pub working_directory: Option<String>,
+}
+
+struct ActiveRequest {
+    scope: Option<String>,
+    abort: AbortHandle,
+}
+
+// Running requests by ID. Clones share the same map, so a handle kept
+// outside the manager's lock can cancel requests while calls hold it.
+#[derive(Clone, Default)]
+pub struct RequestRegistry {
+    requests: Arc<RwLock<HashMap<String, ActiveRequest>>>,
+}
+
+impl RequestRegistry {
+    async fn register(&self, options: &RequestOptions) -> (String, AbortRegistration) {
+        let request_id = options.request_id.clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let (abort, registration) = AbortHandle::new_pair();
+
+        let mut requests = self.requests.write().await;
+        if let Some(scope) = &options.scope {
+            requests.retain(|id, request| {
+                if request.scope.as_ref() != Some(scope) {
+                    return true;
+                }
+                request.abort.abort();
+                info!("Cancelled AI request {} superseded in scope {}", id, scope);
+                false
+            });
+        }
+        if let Some(previous) = requests.insert(request_id.clone(), ActiveRequest {
+            scope: options.scope.clone(),
+            abort,
+        }) {
+            previous.abort.abort();
+        }
+
+        (request_id, registration)
+    }
+
+    // Forgets a request that finished on its own
+    async fn finish(&self, request_id: &str) {
+        self.requests.write().await.remove(request_id);
+    }
+
+    // Returns false when nothing is running under the ID, e.g. because the
+    // request already finished
+    pub async fn cancel(&self, request_id: &str) -> bool {
+        match self.requests.write().await.remove(request_id) {
+            Some(request) => {
+                request.abort.abort();
+                info!("Cancelled AI request {}", request_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn cancel_all(&self) -> usize {
+        let mut requests = self.requests.write().await;
+        let count = requests.len();
+        for (_, request) in requests.drain() {
+            request.abort.abort();
+        }
+        if count > 0 {
+            info!("Cancelled {} AI requests", count);
+        }
+        count
+    }
+
+    pub async fn active(&self) -> Vec<String> {
+        self.requests.read().await.keys().cloned().collect()
+    }
+}
+
 pub struct ProviderManager {
-    providers: HashMap<String, Box<dyn AIProvider>>,
+    providers: HashMap<String, Arc<dyn AIProvider>>,
     default_provider: Option<String>,
+    requests: RequestRegistry,
+    usage: Arc<RwLock<UsageTracker>>,
+    failover: FailoverConfig,
+    router: PromptRouter,
 }
 
 impl ProviderManager {
@@ -60,17 +207,26 @@ impl ProviderManager {
         Ok(Self {
             providers: HashMap::new(),
             default_provider: None,
+            requests: RequestRegistry::default(),
+            usage: Arc::new(RwLock::new(UsageTracker::new())),
+            failover: FailoverConfig::default(),
+            router: PromptRouter::new(),
         })
     }
 
     pub async fn shutdown(&mut self) -> Result<()> {
         info!("Shutting down provider manager");
+        self.cancel_all_requests().await;
         self.providers.clear();
         self.default_provider = None;
         Ok(())
     }
 
     pub async fn load_from_config(&mut self, config: &Config) -> Result<()> {
+        self.usage.write().await.initialize(config).await?;
+        self.failover = config.failover.clone();
+        self.router.initialize(&config.routing);
+
         for provider_config in &config.providers {
             if provider_config.enabled {
                 self.add_provider_from_config(provider_config).await?;
@@ -86,57 +242,289 @@ impl ProviderManager {
     }
 
     pub async fn add_provider_from_config(&mut self, config: &ProviderConfig) -> Result<()> {
-        let provider: Box<dyn AIProvider> = match config.provider_type.as_str() {
-            "openai" => Box::new(OpenAIProvider::new(config)?),
-            "anthropic" => Box::new(AnthropicProvider::new(config)?),
-            "ollama" => Box::new(OllamaProvider::new(config)?),
-            "cohere" => Box::new(CohereProvider::new(config)?),
+        let provider: Arc<dyn AIProvider> = match config.provider_type.as_str() {
+            "openai" => Arc::new(OpenAIProvider::new(config)?),
+            "anthropic" => Arc::new(AnthropicProvider::new(config)?),
+            "ollama" => Arc::new(OllamaProvider::new(config)?),
+            "cohere" => Arc::new(CohereProvider::new(config)?),
+            "openai-compatible" => Arc::new(OpenAICompatibleProvider::new(config)?),
+            "mock" => Arc::new(MockProvider::new(config)?),
             _ => return Err(HoverShellError::Provider(format!("Unknown provider type: {}", config.provider_type))),
         };
 
         self.providers.insert(config.id.clone(), provider);
+        self.usage.write().await.register_provider(config);
+        self.router.register_provider(config);
         info!("Added provider: {}", config.id);
         Ok(())
     }
 
-    pub async fn execute(&self, prompt: &str) -> Result<String> {
-        let provider = self.get_default_provider()?;
-        let response = provider.execute(prompt, None).await?;
-        Ok(response.content)
+    pub async fn execute(&self, prompt: &str, options: &RequestOptions) -> Result<ProviderResponse> {
+        self.execute_with_context(prompt, None, None, options).await
     }
 
-    pub async fn execute_with_provider(&self, prompt: &str, provider_id: &str) -> Result<String> {
-        let provider = self.providers.get(provider_id)
-            .ok_or_else(|| HoverShellError::Provider(format!("Provider not found: {}", provider_id)))?;
-        
-        let response = provider.execute(prompt, None).await?;
-        Ok(response.content)
+    pub async fn execute_with_provider(&self, prompt: &str, provider_id: &str, options: &RequestOptions) -> Result<ProviderResponse> {
+        self.execute_with_context(prompt, None, Some(provider_id), options).await
     }
 
-    pub async fn chat(&self, messages: Vec<ChatMessage>, provider_id: Option<&str>) -> Result<String> {
-        let provider = if let Some(id) = provider_id {
-            self.providers.get(id)
-                .ok_or_else(|| HoverShellError::Provider(format!("Provider not found: {}", id)))?
-        } else {
-            self.get_default_provider()?
+    pub async fn execute_with_context(
+        &self,
+        prompt: &str,
+        context: Option<&str>,
+        provider_id: Option<&str>,
+        options: &RequestOptions,
+    ) -> Result<ProviderResponse> {
+        let route = self.route(&RouteQuery {
+            prompt: prompt.to_string(),
+            context: context.map(|c| c.to_string()),
+            provider_id: provider_id.map(|id| id.to_string()),
+            capability: Some("text".to_string()),
+            working_directory: options.working_directory.clone(),
+        })?;
+        let prompt = route.prompt.as_str();
+        self.run_request(&route, options, |provider| async move {
+            provider.execute(prompt, context).await
+        }).await
+    }
+
+    // Routed on the last message, which is also where a prefix is removed
+    pub async fn chat(&self, mut messages: Vec<ChatMessage>, provider_id: Option<&str>, options: &RequestOptions) -> Result<ProviderResponse> {
+        let route = self.route(&RouteQuery {
+            prompt: messages.last().map(|m| m.content.clone()).unwrap_or_default(),
+            context: None,
+            provider_id: provider_id.map(|id| id.to_string()),
+            capability: Some("chat".to_string()),
+            working_directory: options.working_directory.clone(),
+        })?;
+        if let Some(last) = messages.last_mut() {
+            last.content = route.prompt.clone();
+        }
+
+        self.run_request(&route, options, |provider| {
+            let messages = messages.clone();
+            async move { provider.chat(messages).await }
+        }).await
+    }
+
+    // Opens a stream on the routed provider, or the first fallback that
+    // opens one, leaving the chunks to the caller. The request stays
+    // registered until the stream ends, so it can be cancelled like any
+    // other call, and reported usage is added up and recorded once it ends.
+    pub async fn stream(&self, prompt: &str, provider_id: Option<&str>, options: &RequestOptions) -> Result<ProviderStream> {
+        let route = self.route(&RouteQuery {
+            prompt: prompt.to_string(),
+            context: None,
+            provider_id: provider_id.map(|id| id.to_string()),
+            capability: Some("streaming".to_string()),
+            working_directory: options.working_directory.clone(),
+        })?;
+        let chain = self.route_chain(&route);
+
+        let (request_id, registration) = self.requests.register(options).await;
+        let answer = match open_stream(&chain, &self.failover, &self.usage, &route.prompt, None).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.requests.finish(&request_id).await;
+                return Err(e);
+            }
         };
 
-        let response = provider.chat(messages).await?;
-        Ok(response.content)
+        let state = StreamState {
+            chunks: stream::Abortable::new(answer.value, registration),
+            provider_id: answer.provider_id,
+            request_id,
+            requests: self.requests.clone(),
+            registered: true,
+            usage: self.usage.clone(),
+            total: None,
+            done: false,
+        };
+        Ok(Box::pin(stream::unfold(state, |mut state| async move {
+            if state.done {
+                return None;
+            }
+            let item = match state.chunks.next().await {
+                // Some APIs report usage more than once per response
+                Some(Ok(StreamChunk::Usage(reported))) => {
+                    match state.total.as_mut() {
+                        Some(total) => total.add(&reported),
+                        None => state.total = Some(reported.clone()),
+                    }
+                    Ok(StreamChunk::Usage(reported))
+                }
+                Some(Ok(chunk)) => Ok(chunk),
+                Some(Err(e)) => {
+                    state.done = true;
+                    state.finish().await;
+                    Err(e)
+                }
+                // Cancelled requests were already removed by whoever cancelled them
+                None if state.chunks.is_aborted() => {
+                    state.done = true;
+                    state.registered = false;
+                    state.finish().await;
+                    Err(HoverShellError::Provider(format!("Request cancelled: {}", state.request_id)))
+                }
+                None => {
+                    state.finish().await;
+                    return None;
+                }
+            };
+            Some((item, state))
+        })))
     }
 
-    pub async fn stream(&self, prompt: &str, provider_id: Option<&str>) -> Result<Box<dyn futures_util::Stream<Item = Result<String>> + Unpin>> {
-        let provider = if let Some(id) = provider_id {
-            self.providers.get(id)
-                .ok_or_else(|| HoverShellError::Provider(format!("Provider not found: {}", id)))?
-        } else {
-            self.get_default_provider()?
+    // Starts streaming a response in the background and returns its request
+    // ID. Chunks and the final outcome arrive as `ai-stream-chunk` and
+    // `ai-stream-end`.
+    pub async fn start_stream(
+        &self,
+        prompt: &str,
+        context: Option<&str>,
+        provider_id: Option<&str>,
+        options: &RequestOptions,
+        app_handle: &AppHandle,
+    ) -> Result<String> {
+        let route = self.route(&RouteQuery {
+            prompt: prompt.to_string(),
+            context: context.map(|c| c.to_string()),
+            provider_id: provider_id.map(|id| id.to_string()),
+            capability: Some("streaming".to_string()),
+            working_directory: options.working_directory.clone(),
+        })?;
+        let chain = self.route_chain(&route);
+
+        let (stream_id, registration) = self.requests.register(options).await;
+        let forward = Abortable::new(
+            forward_stream(
+                chain,
+                self.failover.clone(),
+                route.prompt,
+                context.map(|c| c.to_string()),
+                stream_id.clone(),
+                self.usage.clone(),
+                app_handle.clone(),
+            ),
+            registration,
+        );
+
+        let requests = self.requests.clone();
+        let app_handle = app_handle.clone();
+        let id = stream_id.clone();
+        tokio::spawn(async move {
+            match forward.await {
+                Ok(()) => {
+                    requests.finish(&id).await;
+                }
+                Err(_) => emit_stream_end(&app_handle, &id, None, None, true, None),
+            }
+        });
+
+        info!("Started AI stream {}", stream_id);
+        Ok(stream_id)
+    }
+
+    // A handle for cancelling requests without holding the manager
+    pub fn requests(&self) -> RequestRegistry {
+        self.requests.clone()
+    }
+
+    pub async fn cancel_request(&self, request_id: &str) -> bool {
+        self.requests.cancel(request_id).await
+    }
+
+    pub async fn cancel_all_requests(&self) -> usize {
+        self.requests.cancel_all().await
+    }
+
+    pub async fn active_requests(&self) -> Vec<String> {
+        self.requests.active().await
+    }
+
+    // Runs a call against the provider chain as one cancellable request and
+    // notes in the metadata which provider answered
+    async fn run_request<F, Fut>(
+        &self,
+        route: &RouteDecision,
+        options: &RequestOptions,
+        call: F,
+    ) -> Result<ProviderResponse>
+    where
+        F: Fn(Arc<dyn AIProvider>) -> Fut,
+        Fut: Future<Output = Result<ProviderResponse>>,
+    {
+        let chain = self.route_chain(route);
+        let requested = &route.provider_id;
+        let usage = &self.usage;
+        let attempt = failover::call_with_failover(&self.failover, &chain, |provider| {
+            let request = call(provider.clone());
+            async move {
+                usage.read().await.check_budget(&provider.get_info().id)?;
+                request.await
+            }
+        });
+
+        let (request_id, registration) = self.requests.register(options).await;
+        let answer = match Abortable::new(attempt, registration).await {
+            Ok(answer) => {
+                // Cancelled requests were already removed by whoever cancelled them
+                self.requests.finish(&request_id).await;
+                answer?
+            }
+            Err(_) => return Err(HoverShellError::Provider(format!("Request cancelled: {}", request_id))),
         };
 
-        provider.stream(prompt, None).await
+        let mut response = answer.value;
+        if let Some(usage) = response.usage.as_mut() {
+            record_usage(&self.usage, &answer.provider_id, usage).await;
+        }
+
+        response.metadata.insert("provider_id".to_string(), serde_json::json!(answer.provider_id));
+        response.metadata.insert("attempts".to_string(), serde_json::json!(answer.attempts));
+        if answer.provider_id != *requested {
+            info!("Request {} answered by fallback provider {}", request_id, answer.provider_id);
+            response.metadata.insert("fallback_from".to_string(), serde_json::json!(requested));
+        }
+        Ok(response)
+    }
+
+    pub async fn usage_records(&self, query: &UsageQuery) -> Vec<UsageRecord> {
+        self.usage.read().await.query(query)
+    }
+
+    pub async fn usage_summary(&self) -> UsageSummary {
+        self.usage.read().await.summary()
+    }
+
+    // Picks the provider for a request without calling it, which also
+    // serves as a dry run of the routing rules
+    pub fn route(&self, query: &RouteQuery) -> Result<RouteDecision> {
+        self.router.route(
+            query,
+            &self.providers,
+            self.default_provider.as_deref(),
+            &self.failover.fallback_chain,
+        )
+    }
+
+    // The routed provider followed by its fallbacks
+    fn route_chain(&self, route: &RouteDecision) -> Vec<Arc<dyn AIProvider>> {
+        std::iter::once(&route.provider_id)
+            .chain(&route.fallbacks)
+            .filter_map(|id| self.providers.get(id).cloned())
+            .collect()
+    }
+
+    fn resolve_provider(&self, provider_id: Option<&str>) -> Result<&Arc<dyn AIProvider>> {
+        if let Some(id) = provider_id {
+            self.providers.get(id)
+                .ok_or_else(|| HoverShellError::Provider(format!("Provider not found: {}", id)))
+        } else {
+            self.get_default_provider()
+        }
     }
 
-    pub fn get_default_provider(&self) -> Result<&Box<dyn AIProvider>> {
+    pub fn get_default_provider(&self) -> Result<&Arc<dyn AIProvider>> {
         if let Some(default_id) = &self.default_provider {
             self.providers.get(default_id)
                 .ok_or_else(|| HoverShellError::Provider(format!("Default provider not found: {}", default_id)))
@@ -155,6 +543,10 @@ impl ProviderManager {
         }
     }
 
+    pub async fn list_models(&self, provider_id: Option<&str>) -> Result<Vec<String>> {
+        self.resolve_provider(provider_id)?.list_models().await
+    }
+
     pub fn get_provider_list(&self) -> Vec<ProviderInfo> {
         self.providers.values().map(|p| p.get_info()).collect()
     }
@@ -164,6 +556,196 @@ impl ProviderManager {
     }
 }
 
+// Only opening the stream fails over; once text has been forwarded, a
+// failure ends the stream
+async fn forward_stream(
+    chain: Vec<Arc<dyn AIProvider>>,
+    failover: FailoverConfig,
+    prompt: String,
+    context: Option<String>,
+    stream_id: String,
+    usage: Arc<RwLock<UsageTracker>>,
+    app_handle: AppHandle,
+) {
+    let opened = open_stream(&chain, &failover, &usage, &prompt, context.as_deref()).await;
+    let (mut chunks, provider_id) = match opened {
+        Ok(answer) => (answer.value, answer.provider_id),
+        Err(e) => {
+            error!("Error starting AI stream {}: {}", stream_id, e);
+            emit_stream_end(&app_handle, &stream_id, None, Some(e), false, None);
+            return;
+        }
+    };
+
+    let mut index = 0;
+    let mut total: Option<UsageInfo> = None;
+    let mut failure = None;
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(StreamChunk::Text(content)) => {
+                let event = StreamChunkEvent {
+                    stream_id: stream_id.clone(),
+                    index,
+                    content,
+                };
+                if let Err(e) = app_handle.emit_all(AI_STREAM_CHUNK_EVENT, &event) {
+                    error!("Error emitting AI stream chunk: {}", e);
+                }
+                index += 1;
+            }
+            Ok(StreamChunk::Usage(reported)) => match total.as_mut() {
+                Some(total) => total.add(&reported),
+                None => total = Some(reported),
+            },
+            Err(e) => {
+                error!("Error in AI stream {}: {}", stream_id, e);
+                failure = Some(e);
+                break;
+            }
+        }
+    }
+
+    // Tokens reported before a failure were still spent
+    if let Some(total) = total.as_mut() {
+        record_usage(&usage, &provider_id, total).await;
+    }
+    emit_stream_end(&app_handle, &stream_id, Some(&provider_id), failure, false, total);
+}
+
+async fn open_stream(
+    chain: &[Arc<dyn AIProvider>],
+    failover: &FailoverConfig,
+    usage: &RwLock<UsageTracker>,
+    prompt: &str,
+    context: Option<&str>,
+) -> Result<failover::Answer<ProviderStream>> {
+    failover::call_with_failover(failover, chain, |provider| async move {
+        usage.read().await.check_budget(&provider.get_info().id)?;
+        provider.stream(prompt, context).await
+    }).await
+}
+
+// Where a stream returned by `ProviderManager::stream` is up to
+struct StreamState {
+    chunks: stream::Abortable<ProviderStream>,
+    provider_id: String,
+    request_id: String,
+    requests: RequestRegistry,
+    // Whether the registry entry is still ours to remove
+    registered: bool,
+    usage: Arc<RwLock<UsageTracker>>,
+    total: Option<UsageInfo>,
+    done: bool,
+}
+
+impl StreamState {
+    // Records the usage reported so far and forgets the request
+    async fn finish(&mut self) {
+        if let Some(mut total) = self.total.take() {
+            record_usage(&self.usage, &self.provider_id, &mut total).await;
+        }
+        if std::mem::take(&mut self.registered) {
+            self.requests.finish(&self.request_id).await;
+        }
+    }
+}
+
+// The caller may drop the stream before it ends
+impl Drop for StreamState {
+    fn drop(&mut self) {
+        if self.total.is_none() && !self.registered {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let total = self.total.take();
+        let registered = std::mem::take(&mut self.registered);
+        let usage = self.usage.clone();
+        let provider_id = self.provider_id.clone();
+        let requests = self.requests.clone();
+        let request_id = self.request_id.clone();
+        runtime.spawn(async move {
+            if let Some(mut total) = total {
+                record_usage(&usage, &provider_id, &mut total).await;
+            }
+            if registered {
+                requests.finish(&request_id).await;
+            }
+        });
+    }
+}
+
+async fn record_usage(tracker: &RwLock<UsageTracker>, provider_id: &str, usage: &mut UsageInfo) {
+    let mut tracker = tracker.write().await;
+    tracker.price(provider_id, usage);
+    if let Err(e) = tracker.record(provider_id, usage).await {
+        error!("Error recording AI usage: {}", e);
+    }
+}
+
+fn emit_stream_end(
+    app_handle: &AppHandle,
+    stream_id: &str,
+    provider_id: Option<&str>,
+    error: Option<HoverShellError>,
+    cancelled: bool,
+    usage: Option<UsageInfo>,
+) {
+    let event = StreamEndEvent {
+        stream_id: stream_id.to_string(),
+        provider_id: provider_id.map(|id| id.to_string()),
+        error,
+        cancelled,
+        usage,
+    };
+    if let Err(e) = app_handle.emit_all(AI_STREAM_END_EVENT, &event) {
+        error!("Error emitting AI stream end: {}", e);
+    }
+}
+
+// Chat APIs only take the role and content of each message
+fn role_and_content(messages: &[ChatMessage]) -> Vec<serde_json::Value> {
+    messages.iter()
+        .map(|m| serde_json::json!({ "role": m.role, "content": m.content }))
+        .collect()
+}
+
+fn prompt_with_context(prompt: &str, context: Option<&str>) -> String {
+    match context {
+        Some(ctx) => format!("Context: {}\n\nPrompt: {}", ctx, prompt),
+        None => prompt.to_string(),
+    }
+}
+
+fn parse_event_data(data: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(data)
+        .map_err(|e| HoverShellError::Parse(format!("Invalid JSON in stream event: {}", e)))
+}
+
+// Server-sent events from a /chat/completions request with `stream: true`
+fn openai_chat_stream(provider_id: String, response: reqwest::Response) -> ProviderStream {
+    let events = streaming::sse_events(streaming::response_bytes(response));
+    streaming::text_stream(events, move |event| {
+        if event.data == "[DONE]" {
+            return Ok(StreamStep::Done);
+        }
+        let chunk = parse_event_data(&event.data)?;
+        if !chunk["error"].is_null() {
+            return Err(errors::stream_error(&provider_id, &chunk));
+        }
+        // The last chunk before [DONE] has the usage and no choices
+        if let Some(usage) = usage::parse_usage(&chunk, "/usage/prompt_tokens", "/usage/completion_tokens") {
+            return Ok(StreamStep::Usage(usage));
+        }
+        match chunk["choices"][0]["delta"]["content"].as_str() {
+            Some(text) => Ok(StreamStep::Text(text.to_string())),
+            None => Ok(StreamStep::Skip),
+        }
+    })
+}
+
 // OpenAI Provider Implementation
 pub struct OpenAIProvider {
     config: ProviderConfig,
@@ -203,6 +785,7 @@ impl AIProvider for OpenAIProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -212,7 +795,7 @@ impl AIProvider for OpenAIProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/usage/prompt_tokens", "/usage/completion_tokens"),
             metadata: HashMap::new(),
         })
     }
@@ -222,7 +805,7 @@ impl AIProvider for OpenAIProvider {
         
         let body = serde_json::json!({
             "model": self.config.model.as_deref().unwrap_or("gpt-3.5-turbo"),
-            "messages": messages,
+            "messages": role_and_content(&messages),
             "max_tokens": 1000,
             "temperature": 0.7
         });
@@ -234,6 +817,7 @@ impl AIProvider for OpenAIProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -243,14 +827,39 @@ impl AIProvider for OpenAIProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/usage/prompt_tokens", "/usage/completion_tokens"),
             metadata: HashMap::new(),
         })
     }
 
-    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<Box<dyn futures_util::Stream<Item = Result<String>> + Unpin>> {
-        // TODO: Implement streaming
-        Err(HoverShellError::Provider("Streaming not implemented".to_string()))
+    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<ProviderStream> {
+        let url = format!("{}/v1/chat/completions", self.config.base_url.as_deref().unwrap_or("https://api.openai.com"));
+
+        let body = serde_json::json!({
+            "model": self.config.model.as_deref().unwrap_or("gpt-3.5-turbo"),
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt_with_context(prompt, context)
+                }
+            ],
+            "max_tokens": 1000,
+            "temperature": 0.7,
+            "stream": true,
+            "stream_options": {
+                "include_usage": true
+            }
+        });
+
+        let response = self.client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.config.api_key.as_deref().unwrap_or("")))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
+        Ok(openai_chat_stream(self.config.id.clone(), response))
     }
 
     fn get_info(&self) -> ProviderInfo {
@@ -258,7 +867,7 @@ impl AIProvider for OpenAIProvider {
             id: self.config.id.clone(),
             name: self.config.name.clone(),
             provider_type: "openai".to_string(),
-            capabilities: vec!["text", "chat", "streaming".to_string()],
+            capabilities: vec!["text".to_string(), "chat".to_string(), "streaming".to_string()],
             max_tokens: Some(4096),
             supports_streaming: true,
             supports_chat: true,
@@ -310,6 +919,7 @@ impl AIProvider for AnthropicProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -319,7 +929,7 @@ impl AIProvider for AnthropicProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/usage/input_tokens", "/usage/output_tokens"),
             metadata: HashMap::new(),
         })
     }
@@ -327,11 +937,18 @@ impl AIProvider for AnthropicProvider {
     async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ProviderResponse> {
         let url = format!("{}/v1/messages", self.config.base_url.as_deref().unwrap_or("https://api.anthropic.com"));
         
-        let body = serde_json::json!({
+        // System messages go in their own field rather than the list
+        let (system, messages): (Vec<ChatMessage>, Vec<ChatMessage>) = messages.into_iter()
+            .partition(|m| m.role == "system");
+        let mut body = serde_json::json!({
             "model": self.config.model.as_deref().unwrap_or("claude-3-sonnet-20240229"),
             "max_tokens": 1000,
-            "messages": messages
+            "messages": role_and_content(&messages)
         });
+        if !system.is_empty() {
+            let system: Vec<&str> = system.iter().map(|m| m.content.as_str()).collect();
+            body["system"] = serde_json::json!(system.join("\n\n"));
+        }
 
         let response = self.client
             .post(&url)
@@ -341,6 +958,7 @@ impl AIProvider for AnthropicProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -350,14 +968,89 @@ impl AIProvider for AnthropicProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/usage/input_tokens", "/usage/output_tokens"),
             metadata: HashMap::new(),
         })
     }
 
-    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<Box<dyn futures_util::Stream<Item = Result<String>> + Unpin>> {
-        // TODO: Implement Anthropic streaming
-        Err(HoverShellError::Provider("Anthropic streaming not implemented".to_string()))
+    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<ProviderStream> {
+        let url = format!("{}/v1/messages", self.config.base_url.as_deref().unwrap_or("https://api.anthropic.com"));
+
+        let body = serde_json::json!({
+            "model": self.config.model.as_deref().unwrap_or("claude-3-sonnet-20240229"),
+            "max_tokens": 1000,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt_with_context(prompt, context)
+                }
+            ],
+            "stream": true
+        });
+
+        let response = self.client
+            .post(&url)
+            .header("x-api-key", self.config.api_key.as_deref().unwrap_or(""))
+            .header("anthropic-version", "2023-06-01")
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
+
+        let provider_id = self.config.id.clone();
+        let events = streaming::sse_events(streaming::response_bytes(response));
+        // Completion tokens already passed on, since each delta carries the
+        // running total and chunks are summed downstream
+        let mut completion_reported = 0u32;
+        Ok(streaming::text_stream(events, move |event| {
+            match event.event.as_deref() {
+                // Prompt tokens come with the start of the message, completion
+                // tokens with its deltas as a running total
+                Some("message_start") => {
+                    let chunk = parse_event_data(&event.data)?;
+                    match chunk["message"]["usage"]["input_tokens"].as_u64() {
+                        Some(prompt_tokens) => Ok(StreamStep::Usage(UsageInfo {
+                            prompt_tokens: prompt_tokens as u32,
+                            completion_tokens: 0,
+                            total_tokens: prompt_tokens as u32,
+                            cost: None,
+                        })),
+                        None => Ok(StreamStep::Skip),
+                    }
+                }
+                Some("message_delta") => {
+                    let chunk = parse_event_data(&event.data)?;
+                    // Input tokens repeated here were counted at message_start
+                    match chunk["usage"]["output_tokens"].as_u64() {
+                        Some(total) => {
+                            let completion_tokens = (total as u32).saturating_sub(completion_reported);
+                            completion_reported = completion_reported.max(total as u32);
+                            Ok(StreamStep::Usage(UsageInfo {
+                                prompt_tokens: 0,
+                                completion_tokens,
+                                total_tokens: completion_tokens,
+                                cost: None,
+                            }))
+                        }
+                        None => Ok(StreamStep::Skip),
+                    }
+                }
+                Some("content_block_delta") => {
+                    let chunk = parse_event_data(&event.data)?;
+                    match chunk["delta"]["text"].as_str() {
+                        Some(text) => Ok(StreamStep::Text(text.to_string())),
+                        None => Ok(StreamStep::Skip),
+                    }
+                }
+                Some("message_stop") => Ok(StreamStep::Done),
+                Some("error") => {
+                    let chunk = parse_event_data(&event.data)?;
+                    Err(errors::stream_error(&provider_id, &chunk))
+                }
+                _ => Ok(StreamStep::Skip),
+            }
+        }))
     }
 
     fn get_info(&self) -> ProviderInfo {
@@ -365,9 +1058,9 @@ impl AIProvider for AnthropicProvider {
             id: self.config.id.clone(),
             name: self.config.name.clone(),
             provider_type: "anthropic".to_string(),
-            capabilities: vec!["text", "chat".to_string()],
+            capabilities: vec!["text".to_string(), "chat".to_string(), "streaming".to_string()],
             max_tokens: Some(100000),
-            supports_streaming: false,
+            supports_streaming: true,
             supports_chat: true,
         }
     }
@@ -406,6 +1099,7 @@ impl AIProvider for OllamaProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -415,7 +1109,7 @@ impl AIProvider for OllamaProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/prompt_eval_count", "/eval_count"),
             metadata: HashMap::new(),
         })
     }
@@ -425,7 +1119,7 @@ impl AIProvider for OllamaProvider {
         
         let body = serde_json::json!({
             "model": self.config.model.as_deref().unwrap_or("llama3.1:8b"),
-            "messages": messages,
+            "messages": role_and_content(&messages),
             "stream": false
         });
 
@@ -435,6 +1129,7 @@ impl AIProvider for OllamaProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -444,14 +1139,45 @@ impl AIProvider for OllamaProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/prompt_eval_count", "/eval_count"),
             metadata: HashMap::new(),
         })
     }
 
-    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<Box<dyn futures_util::Stream<Item = Result<String>> + Unpin>> {
-        // TODO: Implement Ollama streaming
-        Err(HoverShellError::Provider("Ollama streaming not implemented".to_string()))
+    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<ProviderStream> {
+        let url = format!("{}/api/generate", self.config.base_url.as_deref().unwrap_or("http://127.0.0.1:11434"));
+
+        let body = serde_json::json!({
+            "model": self.config.model.as_deref().unwrap_or("llama3.1:8b"),
+            "prompt": prompt_with_context(prompt, context),
+            "stream": true
+        });
+
+        let response = self.client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
+
+        let provider_id = self.config.id.clone();
+        let lines = streaming::ndjson(streaming::response_bytes(response));
+        Ok(streaming::text_stream(lines, move |chunk| {
+            if !chunk["error"].is_null() {
+                return Err(errors::stream_error(&provider_id, &chunk));
+            }
+            let text = chunk["response"].as_str().unwrap_or("").to_string();
+            // The final line has an empty response and carries the token
+            // counts; the server closes the stream after it
+            if chunk["done"].as_bool().unwrap_or(false) && text.is_empty() {
+                return match usage::parse_usage(&chunk, "/prompt_eval_count", "/eval_count") {
+                    Some(usage) => Ok(StreamStep::Usage(usage)),
+                    None => Ok(StreamStep::Done),
+                };
+            }
+            Ok(StreamStep::Text(text))
+        }))
     }
 
     fn get_info(&self) -> ProviderInfo {
@@ -459,7 +1185,7 @@ impl AIProvider for OllamaProvider {
             id: self.config.id.clone(),
             name: self.config.name.clone(),
             provider_type: "ollama".to_string(),
-            capabilities: vec!["text", "chat", "streaming".to_string()],
+            capabilities: vec!["text".to_string(), "chat".to_string(), "streaming".to_string()],
             max_tokens: Some(8192),
             supports_streaming: true,
             supports_chat: true,
@@ -506,6 +1232,7 @@ impl AIProvider for CohereProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -515,7 +1242,7 @@ impl AIProvider for CohereProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/meta/billed_units/input_tokens", "/meta/billed_units/output_tokens"),
             metadata: HashMap::new(),
         })
     }
@@ -523,10 +1250,25 @@ impl AIProvider for CohereProvider {
     async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ProviderResponse> {
         let url = format!("{}/v1/chat", self.config.base_url.as_deref().unwrap_or("https://api.cohere.ai"));
         
+        // The last message is the one to answer, the rest is history in
+        // Cohere's own roles
+        let (message, history) = messages.split_last()
+            .map(|(last, history)| (last.content.as_str(), history))
+            .unwrap_or(("", &[]));
+        let chat_history: Vec<serde_json::Value> = history.iter()
+            .map(|m| {
+                let role = match m.role.as_str() {
+                    "assistant" => "CHATBOT",
+                    "system" => "SYSTEM",
+                    _ => "USER",
+                };
+                serde_json::json!({ "role": role, "message": m.content })
+            })
+            .collect();
         let body = serde_json::json!({
             "model": self.config.model.as_deref().unwrap_or("command"),
-            "chat_history": messages,
-            "message": messages.last().map(|m| &m.content).unwrap_or(""),
+            "chat_history": chat_history,
+            "message": message,
             "max_tokens": 1000,
             "temperature": 0.7
         });
@@ -538,6 +1280,7 @@ impl AIProvider for CohereProvider {
             .json(&body)
             .send()
             .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
 
         let result: serde_json::Value = response.json().await?;
         
@@ -547,14 +1290,60 @@ impl AIProvider for CohereProvider {
 
         Ok(ProviderResponse {
             content,
-            usage: None,
+            usage: usage::parse_usage(&result, "/meta/billed_units/input_tokens", "/meta/billed_units/output_tokens"),
             metadata: HashMap::new(),
         })
     }
 
-    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<Box<dyn futures_util::Stream<Item = Result<String>> + Unpin>> {
-        // TODO: Implement Cohere streaming
-        Err(HoverShellError::Provider("Cohere streaming not implemented".to_string()))
+    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<ProviderStream> {
+        // Only the v2 chat endpoint streams server-sent events
+        let url = format!("{}/v2/chat", self.config.base_url.as_deref().unwrap_or("https://api.cohere.ai"));
+
+        let body = serde_json::json!({
+            "model": self.config.model.as_deref().unwrap_or("command"),
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt_with_context(prompt, context)
+                }
+            ],
+            "max_tokens": 1000,
+            "temperature": 0.7,
+            "stream": true
+        });
+
+        let response = self.client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.config.api_key.as_deref().unwrap_or("")))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+        let response = errors::check_response(&self.config.id, response).await?;
+
+        let events = streaming::sse_events(streaming::response_bytes(response));
+        Ok(streaming::text_stream(events, |event| {
+            let chunk = parse_event_data(&event.data)?;
+            let kind = event.event.as_deref()
+                .or_else(|| chunk["type"].as_str())
+                .unwrap_or("");
+            match kind {
+                "content-delta" => match chunk["delta"]["message"]["content"]["text"].as_str() {
+                    Some(text) => Ok(StreamStep::Text(text.to_string())),
+                    None => Ok(StreamStep::Skip),
+                },
+                // The server closes the stream after the final usage
+                "message-end" => match usage::parse_usage(
+                    &chunk,
+                    "/delta/usage/billed_units/input_tokens",
+                    "/delta/usage/billed_units/output_tokens",
+                ) {
+                    Some(usage) => Ok(StreamStep::Usage(usage)),
+                    None => Ok(StreamStep::Done),
+                },
+                _ => Ok(StreamStep::Skip),
+            }
+        }))
     }
 
     fn get_info(&self) -> ProviderInfo {
@@ -562,9 +1351,9 @@ impl AIProvider for CohereProvider {
             id: self.config.id.clone(),
             name: self.config.name.clone(),
             provider_type: "cohere".to_string(),
-            capabilities: vec!["text", "chat".to_string()],
+            capabilities: vec!["text".to_string(), "chat".to_string(), "streaming".to_string()],
             max_tokens: Some(2048),
-            supports_streaming: false,
+            supports_streaming: true,
             supports_chat: true,
         }
     }
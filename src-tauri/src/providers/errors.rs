@@ -0,0 +1,200 @@
+use crate::error::{HoverShellError, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{header::RETRY_AFTER, Response};
+
+// Longest piece of a non-JSON error body (e.g. a proxy's HTML page) kept in
+// the message
+const MAX_RAW_MESSAGE: usize = 200;
+
+struct ErrorDetails {
+    message: String,
+    code: Option<String>,
+}
+
+// Passes successful responses through and turns error statuses into typed
+// provider errors
+pub async fn check_response(provider_id: &str, response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response.headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response.text().await.unwrap_or_default();
+
+    let details = serde_json::from_str(&body).ok()
+        .and_then(|body| error_details(&body))
+        .unwrap_or_else(|| {
+            let raw: String = body.trim().chars().take(MAX_RAW_MESSAGE).collect();
+            ErrorDetails {
+                message: if raw.is_empty() {
+                    status.canonical_reason().unwrap_or("Unknown error").to_string()
+                } else {
+                    raw
+                },
+                code: None,
+            }
+        });
+
+    Err(classify(provider_id, Some(status.as_u16()), retry_after, details))
+}
+
+// Errors sent in the middle of a stream only carry a type, which stands in
+// for the status
+pub fn stream_error(provider_id: &str, body: &serde_json::Value) -> HoverShellError {
+    let details = error_details(body).unwrap_or_else(|| ErrorDetails {
+        message: "Unknown streaming error".to_string(),
+        code: None,
+    });
+    let status = details.code.as_deref().and_then(status_for_type);
+    classify(provider_id, status, None, details)
+}
+
+// Where each vendor puts the message:
+//   OpenAI:    {"error": {"message": "..", "type": "..", "code": ".."}}
+//   Anthropic: {"type": "error", "error": {"type": "..", "message": ".."}}
+//   Ollama:    {"error": ".."}
+//   Cohere:    {"message": ".."}
+fn error_details(body: &serde_json::Value) -> Option<ErrorDetails> {
+    let error = &body["error"];
+    if let Some(message) = error.as_str() {
+        return Some(ErrorDetails {
+            message: message.to_string(),
+            code: None,
+        });
+    }
+
+    let message = error["message"].as_str().or_else(|| body["message"].as_str())?;
+    let code = error["code"].as_str().or_else(|| error["type"].as_str());
+    Some(ErrorDetails {
+        message: message.to_string(),
+        code: code.map(|c| c.to_string()),
+    })
+}
+
+fn classify(provider_id: &str, status: Option<u16>, retry_after: Option<u64>, details: ErrorDetails) -> HoverShellError {
+    let provider = provider_id.to_string();
+    let ErrorDetails { message, code } = details;
+    let code = code.unwrap_or_default();
+    let lower = message.to_lowercase();
+
+    match status {
+        Some(401) | Some(403) => HoverShellError::ProviderAuth { provider, message },
+        Some(429) => HoverShellError::RateLimited { provider, message, retry_after },
+        _ if is_context_too_long(status, &code, &lower) => HoverShellError::ContextTooLong { provider, message },
+        _ if is_model_not_found(&code, &lower) => HoverShellError::ModelNotFound { provider, message },
+        Some(status @ 500..=599) => HoverShellError::ProviderServer { provider, status, message },
+        Some(status) => HoverShellError::Provider(format!(
+            "{} request failed with status {}: {}",
+            provider, status, message
+        )),
+        None => HoverShellError::Provider(format!("{}: {}", provider, message)),
+    }
+}
+
+fn is_context_too_long(status: Option<u16>, code: &str, message: &str) -> bool {
+    status == Some(413)
+        || matches!(code, "context_length_exceeded" | "request_too_large")
+        || message.contains("context length")
+        || message.contains("context size")
+        || message.contains("context window")
+        || message.contains("prompt is too long")
+        || message.contains("too many tokens")
+}
+
+fn is_model_not_found(code: &str, message: &str) -> bool {
+    matches!(code, "model_not_found" | "not_found_error")
+        || (message.contains("model") && (message.contains("not found") || message.contains("does not exist")))
+}
+
+// Anthropic and OpenAI error types as the status they'd come with
+fn status_for_type(error_type: &str) -> Option<u16> {
+    match error_type {
+        "authentication_error" | "invalid_api_key" => Some(401),
+        "permission_error" => Some(403),
+        "not_found_error" => Some(404),
+        "request_too_large" => Some(413),
+        "rate_limit_error" | "rate_limit_exceeded" => Some(429),
+        "api_error" | "server_error" => Some(500),
+        "overloaded_error" => Some(529),
+        _ => None,
+    }
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds >= 0.0).then(|| seconds.ceil() as u64);
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - Utc::now()).num_seconds().max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn classify_body(status: u16, body: serde_json::Value) -> HoverShellError {
+        classify("openai", Some(status), None, error_details(&body).unwrap())
+    }
+
+    #[test]
+    fn classifies_error_statuses() {
+        let error = classify_body(401, json!({ "error": { "message": "Incorrect API key", "code": "invalid_api_key" } }));
+        assert!(matches!(error, HoverShellError::ProviderAuth { ref message, .. } if message == "Incorrect API key"));
+        let error = classify_body(403, json!({ "type": "error", "error": { "type": "permission_error", "message": "Forbidden" } }));
+        assert!(matches!(error, HoverShellError::ProviderAuth { .. }));
+
+        let error = classify("openai", Some(429), Some(20), ErrorDetails {
+            message: "Slow down".to_string(),
+            code: None,
+        });
+        assert!(matches!(error, HoverShellError::RateLimited { retry_after: Some(20), .. }));
+
+        let error = classify_body(404, json!({ "error": { "message": "The model `gpt-9` does not exist", "code": "model_not_found" } }));
+        assert!(matches!(error, HoverShellError::ModelNotFound { .. }));
+        let error = classify_body(404, json!({ "error": "model 'llama9' not found, try pulling it first" }));
+        assert!(matches!(error, HoverShellError::ModelNotFound { .. }));
+        let error = classify_body(404, json!({ "message": "No such route" }));
+        assert!(matches!(error, HoverShellError::Provider(ref message) if message.contains("404")));
+
+        let error = classify_body(413, json!({ "type": "error", "error": { "type": "request_too_large", "message": "Request exceeds the maximum size" } }));
+        assert!(matches!(error, HoverShellError::ContextTooLong { .. }));
+        let error = classify_body(400, json!({ "error": { "message": "This model's maximum context length is 8192 tokens", "code": "context_length_exceeded" } }));
+        assert!(matches!(error, HoverShellError::ContextTooLong { .. }));
+
+        let error = classify_body(503, json!({ "message": "Service unavailable" }));
+        assert!(matches!(error, HoverShellError::ProviderServer { status: 503, .. }));
+        let error = classify_body(500, json!({ "error": { "message": "Internal error", "type": "server_error" } }));
+        assert!(matches!(error, HoverShellError::ProviderServer { status: 500, .. }));
+    }
+
+    #[test]
+    fn classifies_stream_errors_by_type() {
+        let error = stream_error("anthropic", &json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } }));
+        assert!(matches!(error, HoverShellError::ProviderServer { status: 529, .. }));
+        let error = stream_error("anthropic", &json!({ "type": "error", "error": { "type": "rate_limit_error", "message": "Too many" } }));
+        assert!(matches!(error, HoverShellError::RateLimited { retry_after: None, .. }));
+        assert!(matches!(stream_error("ollama", &json!({})), HoverShellError::Provider(_)));
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(120));
+        assert_eq!(parse_retry_after(" 1.5 "), Some(2));
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+
+        let date = (Utc::now() + chrono::Duration::seconds(90)).format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let seconds = parse_retry_after(&date).unwrap();
+        assert!((88..=90).contains(&seconds), "{}", seconds);
+
+        // A date in the past means now
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+    }
+}
@@ -0,0 +1,154 @@
+use super::AIProvider;
+use crate::{
+    config::FailoverConfig,
+    error::{HoverShellError, Result},
+};
+use futures_util::Future;
+use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+
+// A successful call and where it came from
+pub struct Answer<T> {
+    pub value: T,
+    pub provider_id: String,
+    pub attempts: u32,
+}
+
+// Calls each provider of the chain in turn until one answers. Transient
+// failures are retried on the same provider first, waiting as long as its
+// Retry-After asks or backing off exponentially. If every provider fails,
+// the last error is returned.
+pub async fn call_with_failover<T, F, Fut>(
+    config: &FailoverConfig,
+    chain: &[Arc<dyn AIProvider>],
+    mut call: F,
+) -> Result<Answer<T>>
+where
+    F: FnMut(Arc<dyn AIProvider>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = 0;
+    let mut last_error = None;
+
+    for provider in chain {
+        let provider_id = provider.get_info().id;
+        let mut retries = 0;
+        loop {
+            attempts += 1;
+            let error = match call(provider.clone()).await {
+                Ok(value) => return Ok(Answer { value, provider_id, attempts }),
+                Err(e) => e,
+            };
+
+            match retry_delay(config, retries, &error) {
+                Some(delay) => {
+                    warn!("Provider {} failed, retrying in {:?}: {}", provider_id, delay, error);
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                }
+                None => {
+                    warn!("Provider {} failed: {}", provider_id, error);
+                    last_error = Some(error);
+                    break;
+                }
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| HoverShellError::Provider("No provider available".to_string())))
+}
+
+// How long to wait before trying the same provider again, or None to move on
+fn retry_delay(config: &FailoverConfig, retries: u32, error: &HoverShellError) -> Option<Duration> {
+    if retries >= config.max_retries {
+        return None;
+    }
+
+    let backoff = config.initial_backoff_ms
+        .saturating_mul(1 << retries.min(16))
+        .min(config.max_backoff_ms);
+    match error {
+        HoverShellError::RateLimited { retry_after: Some(seconds), .. } => {
+            let wait = seconds.saturating_mul(1000);
+            (wait <= config.max_backoff_ms).then(|| Duration::from_millis(wait))
+        }
+        HoverShellError::RateLimited { .. }
+        | HoverShellError::ProviderServer { .. }
+        | HoverShellError::Network(_) => Some(Duration::from_millis(backoff)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FailoverConfig {
+        FailoverConfig {
+            max_retries: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            fallback_chain: Vec::new(),
+        }
+    }
+
+    fn server_error() -> HoverShellError {
+        HoverShellError::ProviderServer {
+            provider: "openai".to_string(),
+            status: 502,
+            message: "Bad gateway".to_string(),
+        }
+    }
+
+    fn rate_limited(retry_after: Option<u64>) -> HoverShellError {
+        HoverShellError::RateLimited {
+            provider: "openai".to_string(),
+            message: "Slow down".to_string(),
+            retry_after,
+        }
+    }
+
+    fn millis(delay: Option<Duration>) -> Option<u128> {
+        delay.map(|delay| delay.as_millis())
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let config = config();
+        let delays: Vec<Option<u128>> = (0..6)
+            .map(|retries| millis(retry_delay(&config, retries, &server_error())))
+            .collect();
+        assert_eq!(delays, vec![Some(100), Some(200), Some(400), Some(800), Some(1000), Some(1000)]);
+
+        // Far past the point where the shift would overflow
+        assert_eq!(millis(retry_delay(&FailoverConfig { max_retries: 100, ..config }, 64, &server_error())), Some(1000));
+    }
+
+    #[test]
+    fn stops_after_max_retries_or_on_permanent_errors() {
+        let config = FailoverConfig { max_retries: 2, ..config() };
+        assert!(retry_delay(&config, 1, &server_error()).is_some());
+        assert!(retry_delay(&config, 2, &server_error()).is_none());
+
+        let network = HoverShellError::Network("connection reset".to_string());
+        assert_eq!(millis(retry_delay(&config, 0, &network)), Some(100));
+
+        let auth = HoverShellError::ProviderAuth {
+            provider: "openai".to_string(),
+            message: "Invalid key".to_string(),
+        };
+        assert!(retry_delay(&config, 0, &auth).is_none());
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let config = config();
+        assert_eq!(millis(retry_delay(&config, 0, &rate_limited(Some(1)))), Some(1000));
+        assert_eq!(millis(retry_delay(&config, 3, &rate_limited(Some(0)))), Some(0));
+        // Asking for more than the longest backoff moves on to the next provider
+        assert_eq!(retry_delay(&config, 0, &rate_limited(Some(2))), None);
+        // Without a Retry-After, rate limits back off like server errors
+        assert_eq!(millis(retry_delay(&config, 2, &rate_limited(None))), Some(400));
+    }
+}
@@ -0,0 +1,304 @@
+use super::{
+    router::estimate_tokens, streaming::StreamChunk, AIProvider, ChatMessage, ProviderInfo,
+    ProviderResponse, ProviderStream, UsageInfo,
+};
+use crate::{
+    config::{Config, ProviderConfig},
+    error::{HoverShellError, Result},
+    utils::expand_home,
+};
+use futures_util::stream;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+// Read from the provider's `config`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct MockSettings {
+    // YAML file with the scripted responses, relative to the config directory
+    fixture: Option<String>,
+    // Wait before answering and between streamed chunks, in milliseconds
+    latency_ms: u64,
+    chunk_delay_ms: u64,
+    capabilities: Vec<String>,
+    context_window: Option<u32>,
+}
+
+impl Default for MockSettings {
+    fn default() -> Self {
+        Self {
+            fixture: None,
+            latency_ms: 0,
+            chunk_delay_ms: 0,
+            capabilities: vec!["text".to_string(), "chat".to_string(), "streaming".to_string()],
+            context_window: None,
+        }
+    }
+}
+
+//   responses:
+//     - match: "(?i)^list files"
+//       response: "ls -la"
+//     - match: "flaky"
+//       error: { kind: server, status: 503 }
+//       times: 2
+//   default: "echo: {prompt}"
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct MockFixture {
+    responses: Vec<MockRule>,
+    // Answer when no rule matches; the prompt is echoed without one
+    default: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MockRule {
+    #[serde(rename = "match")]
+    pattern: String,
+    #[serde(default)]
+    response: String,
+    // How the response is streamed; split after each word without these
+    #[serde(default)]
+    chunks: Vec<String>,
+    usage: Option<MockUsage>,
+    error: Option<MockError>,
+    // The rule only applies to this many matching calls, so later calls
+    // fall through to the next rule
+    times: Option<u32>,
+    latency_ms: Option<u64>,
+    chunk_delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MockUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MockErrorKind {
+    Auth,
+    RateLimited,
+    ContextTooLong,
+    ModelNotFound,
+    Server,
+    Network,
+    Provider,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MockError {
+    kind: MockErrorKind,
+    message: Option<String>,
+    // For `server`, defaults to 500
+    status: Option<u16>,
+    // For `rate_limited`, in seconds
+    retry_after: Option<u64>,
+    // Streams send this many chunks before failing; other calls fail
+    // straight away
+    after_chunks: Option<usize>,
+}
+
+impl MockError {
+    fn to_error(&self, provider_id: &str) -> HoverShellError {
+        let provider = provider_id.to_string();
+        let message = self.message.clone().unwrap_or_else(|| "Mock error".to_string());
+        match self.kind {
+            MockErrorKind::Auth => HoverShellError::ProviderAuth { provider, message },
+            MockErrorKind::RateLimited => HoverShellError::RateLimited {
+                provider,
+                message,
+                retry_after: self.retry_after,
+            },
+            MockErrorKind::ContextTooLong => HoverShellError::ContextTooLong { provider, message },
+            MockErrorKind::ModelNotFound => HoverShellError::ModelNotFound { provider, message },
+            MockErrorKind::Server => HoverShellError::ProviderServer {
+                provider,
+                status: self.status.unwrap_or(500),
+                message,
+            },
+            MockErrorKind::Network => HoverShellError::Network(message),
+            MockErrorKind::Provider => HoverShellError::Provider(format!("{}: {}", provider, message)),
+        }
+    }
+}
+
+// What a call gets back from the fixture
+struct Reply {
+    content: String,
+    chunks: Vec<String>,
+    usage: UsageInfo,
+    error: Option<MockError>,
+    latency: Duration,
+    chunk_delay: Duration,
+}
+
+// Answers from a YAML fixture instead of an API, for tests and offline
+// demos. Without a fixture it echoes the prompt.
+pub struct MockProvider {
+    config: ProviderConfig,
+    settings: MockSettings,
+    rules: Vec<(Regex, MockRule)>,
+    default: Option<String>,
+    // How often each rule has answered, for `times`
+    calls: Vec<AtomicU32>,
+}
+
+impl MockProvider {
+    pub fn new(config: &ProviderConfig) -> Result<Self> {
+        let settings: MockSettings = serde_json::from_value(config.config.clone())
+            .map_err(|e| HoverShellError::Config(format!("Invalid settings for provider {}: {}", config.id, e)))?;
+
+        let fixture = match &settings.fixture {
+            Some(path) => load_fixture(&config.id, path)?,
+            None => MockFixture::default(),
+        };
+
+        let mut rules = Vec::new();
+        for rule in fixture.responses {
+            let regex = Regex::new(&rule.pattern).map_err(|e| {
+                HoverShellError::Config(format!("Invalid pattern {} for provider {}: {}", rule.pattern, config.id, e))
+            })?;
+            rules.push((regex, rule));
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            settings,
+            calls: rules.iter().map(|_| AtomicU32::new(0)).collect(),
+            rules,
+            default: fixture.default,
+        })
+    }
+
+    fn reply(&self, prompt: &str) -> Reply {
+        let rule = self.rules.iter().zip(&self.calls).find_map(|((regex, rule), calls)| {
+            if !regex.is_match(prompt) {
+                return None;
+            }
+            let count = calls.fetch_add(1, Ordering::SeqCst);
+            rule.times.is_none_or(|times| count < times).then_some(rule)
+        });
+
+        let content = match rule {
+            Some(rule) => rule.response.clone(),
+            None => self.default.as_deref().unwrap_or("{prompt}").to_string(),
+        }
+        .replace("{prompt}", prompt);
+
+        let chunks = match rule {
+            Some(rule) if !rule.chunks.is_empty() => rule.chunks.clone(),
+            _ => content.split_inclusive(' ').map(|word| word.to_string()).collect(),
+        };
+
+        let (prompt_tokens, completion_tokens) = match rule.and_then(|rule| rule.usage.as_ref()) {
+            Some(usage) => (usage.prompt_tokens, usage.completion_tokens),
+            None => (estimate_tokens(prompt), estimate_tokens(&content)),
+        };
+
+        Reply {
+            content,
+            chunks,
+            usage: UsageInfo {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+                cost: None,
+            },
+            error: rule.and_then(|rule| rule.error.clone()),
+            latency: Duration::from_millis(rule.and_then(|rule| rule.latency_ms).unwrap_or(self.settings.latency_ms)),
+            chunk_delay: Duration::from_millis(rule.and_then(|rule| rule.chunk_delay_ms).unwrap_or(self.settings.chunk_delay_ms)),
+        }
+    }
+
+    async fn respond(&self, prompt: &str) -> Result<ProviderResponse> {
+        let reply = self.reply(prompt);
+        tokio::time::sleep(reply.latency).await;
+        if let Some(error) = &reply.error {
+            return Err(error.to_error(&self.config.id));
+        }
+
+        Ok(ProviderResponse {
+            content: reply.content,
+            usage: Some(reply.usage),
+            metadata: HashMap::new(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AIProvider for MockProvider {
+    async fn execute(&self, prompt: &str, _context: Option<&str>) -> Result<ProviderResponse> {
+        self.respond(prompt).await
+    }
+
+    async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ProviderResponse> {
+        let prompt = messages.last().map(|m| m.content.as_str()).unwrap_or_default();
+        self.respond(prompt).await
+    }
+
+    async fn stream(&self, prompt: &str, _context: Option<&str>) -> Result<ProviderStream> {
+        let reply = self.reply(prompt);
+        tokio::time::sleep(reply.latency).await;
+
+        // A mid-stream failure still reports the chunks sent before it
+        let mut steps: Vec<Result<StreamChunk>> = Vec::new();
+        match &reply.error {
+            Some(error) => match error.after_chunks {
+                Some(count) => {
+                    steps.extend(reply.chunks.into_iter().take(count).map(|chunk| Ok(StreamChunk::Text(chunk))));
+                    steps.push(Err(error.to_error(&self.config.id)));
+                }
+                None => return Err(error.to_error(&self.config.id)),
+            },
+            None => {
+                steps.extend(reply.chunks.into_iter().map(|chunk| Ok(StreamChunk::Text(chunk))));
+                steps.push(Ok(StreamChunk::Usage(reply.usage)));
+            }
+        }
+
+        let delay = reply.chunk_delay;
+        Ok(Box::pin(stream::unfold(steps.into_iter().enumerate(), move |mut steps| async move {
+            let (index, step) = steps.next()?;
+            if index > 0 && !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            Some((step, steps))
+        })))
+    }
+
+    fn get_info(&self) -> ProviderInfo {
+        ProviderInfo {
+            id: self.config.id.clone(),
+            name: self.config.name.clone(),
+            provider_type: "mock".to_string(),
+            capabilities: self.settings.capabilities.clone(),
+            max_tokens: self.settings.context_window,
+            supports_streaming: self.settings.capabilities.iter().any(|c| c == "streaming"),
+            supports_chat: self.settings.capabilities.iter().any(|c| c == "chat"),
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(vec![self.config.model.clone().unwrap_or_else(|| "mock".to_string())])
+    }
+}
+
+fn load_fixture(provider_id: &str, path: &str) -> Result<MockFixture> {
+    let mut file = PathBuf::from(expand_home(path));
+    if file.is_relative() {
+        file = Config::config_dir().join(file);
+    }
+
+    let content = std::fs::read_to_string(&file).map_err(|e| {
+        HoverShellError::Config(format!("Can't read fixture {} for provider {}: {}", file.display(), provider_id, e))
+    })?;
+    serde_yaml::from_str(&content).map_err(|e| {
+        HoverShellError::Config(format!("Invalid fixture {} for provider {}: {}", file.display(), provider_id, e))
+    })
+}
@@ -0,0 +1,209 @@
+use super::{
+    errors, openai_chat_stream, prompt_with_context, role_and_content, usage, AIProvider, ChatMessage,
+    ProviderInfo, ProviderResponse, ProviderStream,
+};
+use crate::{
+    config::ProviderConfig,
+    error::{HoverShellError, Result},
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Read from the provider's `config`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct CompatibleSettings {
+    // Put in front of /chat/completions and /models, for servers and
+    // gateways that mount the API somewhere other than /v1
+    path_prefix: String,
+    auth_header: String,
+    // Written before the key, e.g. "Bearer"; empty sends the bare key
+    auth_scheme: String,
+    headers: HashMap<String, String>,
+    // Largest prompt the model takes, used for routing
+    context_window: Option<u32>,
+    max_tokens: u32,
+    temperature: f64,
+    // Request timeout in milliseconds
+    timeout: Option<u64>,
+    // Not every server accepts `stream_options` to report token counts
+    stream_usage: bool,
+}
+
+impl Default for CompatibleSettings {
+    fn default() -> Self {
+        Self {
+            path_prefix: "/v1".to_string(),
+            auth_header: "Authorization".to_string(),
+            auth_scheme: "Bearer".to_string(),
+            headers: HashMap::new(),
+            context_window: None,
+            max_tokens: 1000,
+            temperature: 0.7,
+            timeout: None,
+            stream_usage: true,
+        }
+    }
+}
+
+// Any server speaking the OpenAI chat completions API: llama.cpp server,
+// vLLM, LM Studio, LocalAI or an internal gateway in front of them
+pub struct OpenAICompatibleProvider {
+    config: ProviderConfig,
+    settings: CompatibleSettings,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl OpenAICompatibleProvider {
+    pub fn new(config: &ProviderConfig) -> Result<Self> {
+        let base_url = config.base_url.as_deref()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .ok_or_else(|| HoverShellError::Config(format!("Provider {} needs a base_url", config.id)))?;
+
+        let mut settings: CompatibleSettings = serde_json::from_value(config.config.clone())
+            .map_err(|e| HoverShellError::Config(format!("Invalid settings for provider {}: {}", config.id, e)))?;
+        let prefix = settings.path_prefix.trim_matches('/');
+        settings.path_prefix = if prefix.is_empty() { String::new() } else { format!("/{}", prefix) };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &settings.headers {
+            headers.insert(header_name(&config.id, name)?, header_value(&config.id, value)?);
+        }
+        if let Some(api_key) = config.api_key.as_deref().filter(|key| !key.is_empty()) {
+            let credentials = if settings.auth_scheme.is_empty() {
+                api_key.to_string()
+            } else {
+                format!("{} {}", settings.auth_scheme, api_key)
+            };
+            let mut value = header_value(&config.id, &credentials)?;
+            value.set_sensitive(true);
+            headers.insert(header_name(&config.id, &settings.auth_header)?, value);
+        }
+
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(timeout) = settings.timeout {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        let client = builder.build()
+            .map_err(|e| HoverShellError::Config(format!("Failed to create client for provider {}: {}", config.id, e)))?;
+
+        Ok(Self {
+            config: config.clone(),
+            settings,
+            base_url,
+            client,
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{}", self.base_url, self.settings.path_prefix, path)
+    }
+
+    // Servers that host a single model (llama.cpp) don't need one named
+    fn chat_body(&self, messages: serde_json::Value, stream: bool) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "messages": messages,
+            "max_tokens": self.settings.max_tokens,
+            "temperature": self.settings.temperature
+        });
+        if let Some(model) = &self.config.model {
+            body["model"] = serde_json::json!(model);
+        }
+        if stream {
+            body["stream"] = serde_json::json!(true);
+            if self.settings.stream_usage {
+                body["stream_options"] = serde_json::json!({ "include_usage": true });
+            }
+        }
+        body
+    }
+
+    async fn post_chat(&self, body: &serde_json::Value) -> Result<reqwest::Response> {
+        let response = self.client
+            .post(self.url("/chat/completions"))
+            .json(body)
+            .send()
+            .await?;
+        errors::check_response(&self.config.id, response).await
+    }
+
+    async fn chat_messages(&self, messages: serde_json::Value) -> Result<ProviderResponse> {
+        let response = self.post_chat(&self.chat_body(messages, false)).await?;
+        let result: serde_json::Value = response.json().await?;
+
+        let content = result["choices"][0]["message"]["content"].as_str()
+            .unwrap_or("")
+            .to_string();
+
+        Ok(ProviderResponse {
+            content,
+            usage: usage::parse_usage(&result, "/usage/prompt_tokens", "/usage/completion_tokens"),
+            metadata: HashMap::new(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AIProvider for OpenAICompatibleProvider {
+    async fn execute(&self, prompt: &str, context: Option<&str>) -> Result<ProviderResponse> {
+        let messages = serde_json::json!([
+            {
+                "role": "user",
+                "content": prompt_with_context(prompt, context)
+            }
+        ]);
+        self.chat_messages(messages).await
+    }
+
+    async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ProviderResponse> {
+        self.chat_messages(serde_json::json!(role_and_content(&messages))).await
+    }
+
+    async fn stream(&self, prompt: &str, context: Option<&str>) -> Result<ProviderStream> {
+        let messages = serde_json::json!([
+            {
+                "role": "user",
+                "content": prompt_with_context(prompt, context)
+            }
+        ]);
+        let response = self.post_chat(&self.chat_body(messages, true)).await?;
+        Ok(openai_chat_stream(self.config.id.clone(), response))
+    }
+
+    fn get_info(&self) -> ProviderInfo {
+        ProviderInfo {
+            id: self.config.id.clone(),
+            name: self.config.name.clone(),
+            provider_type: "openai-compatible".to_string(),
+            capabilities: vec!["text".to_string(), "chat".to_string(), "streaming".to_string()],
+            max_tokens: self.settings.context_window,
+            supports_streaming: true,
+            supports_chat: true,
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self.client.get(self.url("/models")).send().await?;
+        let response = errors::check_response(&self.config.id, response).await?;
+        let result: serde_json::Value = response.json().await?;
+
+        let models = result["data"].as_array()
+            .ok_or_else(|| HoverShellError::Parse(format!("Unexpected model list from provider {}", self.config.id)))?;
+        Ok(models.iter()
+            .filter_map(|model| model["id"].as_str().map(|id| id.to_string()))
+            .collect())
+    }
+}
+
+fn header_name(provider_id: &str, name: &str) -> Result<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| HoverShellError::Config(format!("Invalid header name {} for provider {}: {}", name, provider_id, e)))
+}
+
+fn header_value(provider_id: &str, value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value)
+        .map_err(|e| HoverShellError::Config(format!("Invalid header value for provider {}: {}", provider_id, e)))
+}
@@ -0,0 +1,299 @@
+use super::AIProvider;
+use crate::{
+    config::{ProviderConfig, RoutingConfig, RoutingRule},
+    error::{HoverShellError, Result},
+    utils::{expand_home, glob_to_regex},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+// Every provider gets one of these depending on where it is hosted
+pub const LOCAL_TAG: &str = "local";
+pub const CLOUD_TAG: &str = "cloud";
+
+const DEFAULT_CAPABILITY: &str = "text";
+
+// What a request needs from a provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteQuery {
+    pub prompt: String,
+    pub context: Option<String>,
+    pub provider_id: Option<String>,
+    pub capability: Option<String>,
+    pub working_directory: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedProvider {
+    pub provider_id: String,
+    pub reason: String,
+}
+
+// Where a request goes and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteDecision {
+    pub provider_id: String,
+    pub reason: String,
+    // Tried in order when the provider fails
+    pub fallbacks: Vec<String>,
+    // The prompt to send, without a routing prefix
+    pub prompt: String,
+    pub estimated_tokens: u32,
+    pub matched_rules: Vec<String>,
+    pub skipped: Vec<SkippedProvider>,
+}
+
+#[derive(PartialEq)]
+enum Candidate {
+    Requested,
+    Rule(String),
+    Default,
+    Fallback,
+    Other,
+}
+
+impl Candidate {
+    fn reason(&self) -> String {
+        match self {
+            Candidate::Requested => "requested".to_string(),
+            Candidate::Rule(name) => format!("selected by rule {}", name),
+            Candidate::Default => "default provider".to_string(),
+            Candidate::Fallback => "first usable fallback".to_string(),
+            Candidate::Other => "only provider allowed".to_string(),
+        }
+    }
+}
+
+// Picks the provider for a request from the routing rules, the provider's
+// limits and capabilities, and the default and fallback providers
+pub struct PromptRouter {
+    rules: Vec<RoutingRule>,
+    tags: HashMap<String, Vec<String>>,
+}
+
+impl PromptRouter {
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            tags: HashMap::new(),
+        }
+    }
+
+    pub fn initialize(&mut self, config: &RoutingConfig) {
+        self.rules = config.rules.clone();
+    }
+
+    // Tags come from `tags` in the provider's `config`, plus "local" or
+    // "cloud" depending on its base URL
+    pub fn register_provider(&mut self, config: &ProviderConfig) {
+        let mut tags: Vec<String> = match config.config.get("tags") {
+            Some(tags) => serde_json::from_value(tags.clone()).unwrap_or_else(|e| {
+                warn!("Ignoring invalid tags for provider {}: {}", config.id, e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        tags.push(if is_local(config) { LOCAL_TAG } else { CLOUD_TAG }.to_string());
+        self.tags.insert(config.id.clone(), tags);
+    }
+
+    pub fn route(
+        &self,
+        query: &RouteQuery,
+        providers: &HashMap<String, Arc<dyn AIProvider>>,
+        default_provider: Option<&str>,
+        fallback_chain: &[String],
+    ) -> Result<RouteDecision> {
+        let capability = query.capability.as_deref().unwrap_or(DEFAULT_CAPABILITY);
+        let estimated_tokens = estimate_tokens(&query.prompt)
+            + query.context.as_deref().map(estimate_tokens).unwrap_or(0);
+        let directory = query.working_directory.as_deref().map(expand_home);
+
+        let matched: Vec<&RoutingRule> = self.rules.iter()
+            .filter(|rule| rule_matches(rule, query, capability, estimated_tokens, directory.as_deref()))
+            .collect();
+
+        let prompt = matched.iter()
+            .find_map(|rule| rule.prefix.as_deref().and_then(|prefix| strip_prefix(&query.prompt, prefix)))
+            .unwrap_or(&query.prompt)
+            .to_string();
+
+        let mut candidates: Vec<(&str, Candidate)> = Vec::new();
+        match query.provider_id.as_deref() {
+            Some(id) => candidates.push((id, Candidate::Requested)),
+            None => {
+                if let Some((rule, id)) = matched.iter().find_map(|rule| rule.provider.as_deref().map(|id| (rule, id))) {
+                    candidates.push((id, Candidate::Rule(rule.name.clone())));
+                }
+                if let Some(id) = default_provider {
+                    candidates.push((id, Candidate::Default));
+                }
+            }
+        }
+        candidates.extend(fallback_chain.iter().map(|id| (id.as_str(), Candidate::Fallback)));
+        // The rest only count when nothing above may be used, so a privacy
+        // rule can still land on an allowed provider
+        let mut others: Vec<&str> = providers.keys().map(|id| id.as_str()).collect();
+        others.sort();
+        candidates.extend(others.into_iter().map(|id| (id, Candidate::Other)));
+
+        let mut chosen: Option<(&str, Candidate)> = None;
+        let mut fallbacks: Vec<String> = Vec::new();
+        let mut skipped: Vec<SkippedProvider> = Vec::new();
+        let mut seen: Vec<&str> = Vec::new();
+        for (id, candidate) in candidates {
+            if seen.contains(&id) || (candidate == Candidate::Other && chosen.is_some()) {
+                continue;
+            }
+            seen.push(id);
+
+            if let Err(reason) = self.check(id, providers, capability, estimated_tokens, &matched) {
+                if candidate == Candidate::Requested {
+                    return Err(HoverShellError::Provider(format!(
+                        "Provider {} can't take this request: {}",
+                        id, reason
+                    )));
+                }
+                skipped.push(SkippedProvider {
+                    provider_id: id.to_string(),
+                    reason,
+                });
+                continue;
+            }
+
+            if chosen.is_none() {
+                chosen = Some((id, candidate));
+            } else if candidate == Candidate::Fallback {
+                fallbacks.push(id.to_string());
+            }
+        }
+
+        let Some((provider_id, candidate)) = chosen else {
+            let reasons: Vec<String> = skipped.iter()
+                .map(|s| format!("{} ({})", s.provider_id, s.reason))
+                .collect();
+            return Err(HoverShellError::Provider(format!(
+                "No provider can take this request: {}",
+                if reasons.is_empty() { "no providers loaded".to_string() } else { reasons.join(", ") }
+            )));
+        };
+
+        Ok(RouteDecision {
+            provider_id: provider_id.to_string(),
+            reason: candidate.reason(),
+            fallbacks,
+            prompt,
+            estimated_tokens,
+            matched_rules: matched.iter().map(|rule| rule.name.clone()).collect(),
+            skipped,
+        })
+    }
+
+    // Why the provider can't take the request, if it can't
+    fn check(
+        &self,
+        provider_id: &str,
+        providers: &HashMap<String, Arc<dyn AIProvider>>,
+        capability: &str,
+        estimated_tokens: u32,
+        matched: &[&RoutingRule],
+    ) -> std::result::Result<(), String> {
+        let Some(provider) = providers.get(provider_id) else {
+            return Err("not loaded (unknown or disabled)".to_string());
+        };
+
+        let info = provider.get_info();
+        if !info.capabilities.iter().any(|c| c == capability) {
+            return Err(format!("doesn't support {}", capability));
+        }
+        if let Some(max_tokens) = info.max_tokens.filter(|max| estimated_tokens > *max) {
+            return Err(format!(
+                "prompt needs about {} tokens, limit is {}",
+                estimated_tokens, max_tokens
+            ));
+        }
+
+        let tags = self.tags.get(provider_id).map(|t| t.as_slice()).unwrap_or_default();
+        for rule in matched {
+            if let Some(tag) = tags.iter().find(|tag| rule.deny_tags.contains(tag)) {
+                return Err(format!("tagged {}, denied by rule {}", tag, rule.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PromptRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Roughly four characters per token for English text and code
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(4) as u32
+}
+
+fn rule_matches(
+    rule: &RoutingRule,
+    query: &RouteQuery,
+    capability: &str,
+    estimated_tokens: u32,
+    directory: Option<&str>,
+) -> bool {
+    if let Some(prefix) = &rule.prefix {
+        if strip_prefix(&query.prompt, prefix).is_none() {
+            return false;
+        }
+    }
+    if let Some(pattern) = &rule.workspace {
+        match directory {
+            Some(directory) if workspace_matches(rule, pattern, directory) => {}
+            _ => return false,
+        }
+    }
+    if rule.capability.as_deref().is_some_and(|c| c != capability) {
+        return false;
+    }
+    if rule.min_prompt_tokens.is_some_and(|min| estimated_tokens < min) {
+        return false;
+    }
+    true
+}
+
+// Matches the directory or one of its parents, so the rule covers a whole
+// project
+fn workspace_matches(rule: &RoutingRule, pattern: &str, directory: &str) -> bool {
+    let regex = match glob_to_regex(expand_home(pattern).trim_end_matches('/')) {
+        Ok(regex) => regex,
+        Err(e) => {
+            warn!("Ignoring routing rule {} with invalid workspace: {}", rule.name, e);
+            return false;
+        }
+    };
+    Path::new(directory)
+        .ancestors()
+        .any(|dir| regex.is_match(&dir.to_string_lossy()))
+}
+
+// The prompt after a leading prefix word, or None if it doesn't start with it
+fn strip_prefix<'a>(prompt: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = prompt.trim_start().strip_prefix(prefix)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim_start())
+}
+
+fn is_local(config: &ProviderConfig) -> bool {
+    match config.base_url.as_deref() {
+        Some(base_url) => reqwest::Url::parse(base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]" | "::1")),
+        // Ollama defaults to the local server and mocks never leave the machine
+        None => matches!(config.provider_type.as_str(), "ollama" | "mock"),
+    }
+}
@@ -0,0 +1,261 @@
+use super::UsageInfo;
+use crate::error::{HoverShellError, Result};
+use futures_util::{stream, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>>> + Send>>;
+pub type ProviderStream = Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>;
+
+// Providers report token counts alongside the text, possibly split over
+// several chunks (e.g. prompt tokens first, completion tokens at the end)
+#[derive(Debug, Clone)]
+pub enum StreamChunk {
+    Text(String),
+    Usage(UsageInfo),
+}
+
+// One server-sent event. Multiple `data:` lines are joined with newlines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+pub fn response_bytes(response: reqwest::Response) -> ByteStream {
+    Box::pin(response.bytes_stream().map(|chunk| {
+        chunk.map(|bytes| bytes.to_vec()).map_err(HoverShellError::from)
+    }))
+}
+
+struct LineState {
+    bytes: ByteStream,
+    buffer: Vec<u8>,
+    lines: VecDeque<String>,
+    done: bool,
+}
+
+// Splits a byte stream into lines without their `\n` or `\r\n`. Lines may
+// span chunk boundaries, including in the middle of a UTF-8 character.
+pub fn lines(bytes: ByteStream) -> impl Stream<Item = Result<String>> + Send {
+    let state = LineState {
+        bytes,
+        buffer: Vec::new(),
+        lines: VecDeque::new(),
+        done: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(line) = state.lines.pop_front() {
+                return Some((Ok(line), state));
+            }
+            if state.done {
+                return None;
+            }
+
+            match state.bytes.next().await {
+                Some(Ok(chunk)) => {
+                    state.buffer.extend_from_slice(&chunk);
+                    while let Some(end) = state.buffer.iter().position(|&b| b == b'\n') {
+                        let mut line: Vec<u8> = state.buffer.drain(..=end).collect();
+                        line.pop();
+                        if line.last() == Some(&b'\r') {
+                            line.pop();
+                        }
+                        state.lines.push_back(String::from_utf8_lossy(&line).to_string());
+                    }
+                }
+                Some(Err(e)) => {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+                None => {
+                    state.done = true;
+                    if !state.buffer.is_empty() {
+                        let rest = std::mem::take(&mut state.buffer);
+                        state.lines.push_back(String::from_utf8_lossy(&rest).to_string());
+                    }
+                }
+            }
+        }
+    })
+}
+
+// Parses a `text/event-stream` body into events
+pub fn sse_events(bytes: ByteStream) -> impl Stream<Item = Result<SseEvent>> + Send {
+    let lines = Box::pin(lines(bytes));
+
+    stream::unfold(Some(lines), |lines| async move {
+        let mut lines = lines?;
+        let mut event = None;
+        let mut data: Vec<String> = Vec::new();
+
+        loop {
+            match lines.next().await {
+                Some(Ok(line)) => {
+                    // A blank line dispatches the event collected so far
+                    if line.is_empty() {
+                        if data.is_empty() {
+                            event = None;
+                            continue;
+                        }
+                        let sse = SseEvent { event, data: data.join("\n") };
+                        return Some((Ok(sse), Some(lines)));
+                    }
+                    if line.starts_with(':') {
+                        continue;
+                    }
+
+                    let (field, value) = line.split_once(':').unwrap_or((line.as_str(), ""));
+                    let value = value.strip_prefix(' ').unwrap_or(value);
+                    match field {
+                        "event" => event = Some(value.to_string()),
+                        "data" => data.push(value.to_string()),
+                        _ => {}
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    // Servers may close the connection without a final blank line
+                    if data.is_empty() {
+                        return None;
+                    }
+                    return Some((Ok(SseEvent { event, data: data.join("\n") }), None));
+                }
+            }
+        }
+    })
+}
+
+// Parses a newline-delimited JSON body, skipping blank lines
+pub fn ndjson(bytes: ByteStream) -> impl Stream<Item = Result<serde_json::Value>> + Send {
+    lines(bytes).filter_map(|line| async move {
+        match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(serde_json::from_str(&line).map_err(|e| {
+                HoverShellError::Parse(format!("Invalid JSON in stream: {}", e))
+            })),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+// What a provider makes of one item of its stream
+pub enum StreamStep {
+    Text(String),
+    Usage(UsageInfo),
+    Skip,
+    Done,
+}
+
+// Turns a stream of parsed items into chunks. The stream ends at the first
+// `Done` or error.
+pub fn text_stream<T, S, F>(items: S, step: F) -> ProviderStream
+where
+    T: Send + 'static,
+    S: Stream<Item = Result<T>> + Send + 'static,
+    F: FnMut(T) -> Result<StreamStep> + Send + 'static,
+{
+    let items = Box::pin(items);
+    Box::pin(stream::unfold(Some((items, step)), |state| async move {
+        let (mut items, mut step) = state?;
+        loop {
+            let item = match items.next().await? {
+                Ok(item) => item,
+                Err(e) => return Some((Err(e), None)),
+            };
+            match step(item) {
+                Ok(StreamStep::Text(text)) if text.is_empty() => continue,
+                Ok(StreamStep::Text(text)) => return Some((Ok(StreamChunk::Text(text)), Some((items, step)))),
+                Ok(StreamStep::Usage(usage)) => return Some((Ok(StreamChunk::Usage(usage)), Some((items, step)))),
+                Ok(StreamStep::Skip) => continue,
+                Ok(StreamStep::Done) => return None,
+                Err(e) => return Some((Err(e), None)),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bytes(chunks: &[&[u8]]) -> ByteStream {
+        let chunks: Vec<Result<Vec<u8>>> = chunks.iter().map(|chunk| Ok(chunk.to_vec())).collect();
+        Box::pin(stream::iter(chunks))
+    }
+
+    async fn events(chunks: &[&[u8]]) -> Vec<SseEvent> {
+        sse_events(bytes(chunks)).map(|event| event.unwrap()).collect().await
+    }
+
+    fn event(name: Option<&str>, data: &str) -> SseEvent {
+        SseEvent {
+            event: name.map(str::to_string),
+            data: data.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn joins_sse_events_split_across_chunks() {
+        let events = events(&[
+            b"event: message_st",
+            b"art\ndata: {\"a\":",
+            b"1}\n",
+            b"\n: keep-alive\n\nda",
+            b"ta: second\n\n",
+        ]).await;
+        assert_eq!(events, vec![
+            event(Some("message_start"), "{\"a\":1}"),
+            event(None, "second"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn handles_crlf_and_multi_line_data() {
+        let events = events(&[
+            b"data: first line\r\ndata:second line\r\n\r",
+            b"\nevent: done\r\ndata: [DONE]\r\n\r\n",
+        ]).await;
+        assert_eq!(events, vec![
+            event(None, "first line\nsecond line"),
+            event(Some("done"), "[DONE]"),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn keeps_final_sse_event_without_blank_line() {
+        let received = events(&[b"data: one\n\ndata: two\ndata: three"]).await;
+        assert_eq!(received, vec![event(None, "one"), event(None, "two\nthree")]);
+
+        // Nothing but comments and blank lines
+        assert!(events(&[b": ping\n\n\n"]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn splits_lines_inside_utf8_characters() {
+        let euro = "€".as_bytes();
+        let lines: Vec<String> = lines(bytes(&[b"price: ", &euro[..1], &euro[1..], b"5\r\nnext"]))
+            .map(|line| line.unwrap())
+            .collect()
+            .await;
+        assert_eq!(lines, vec!["price: €5", "next"]);
+    }
+
+    #[tokio::test]
+    async fn parses_ndjson_split_across_chunks() {
+        let values: Vec<Result<serde_json::Value>> = ndjson(bytes(&[
+            b"{\"response\":\"he",
+            b"llo\"}\r\n\n{\"done\":",
+            b"true}",
+        ])).collect().await;
+        let values: Vec<serde_json::Value> = values.into_iter().map(|value| value.unwrap()).collect();
+        assert_eq!(values, vec![json!({ "response": "hello" }), json!({ "done": true })]);
+
+        let mut invalid = Box::pin(ndjson(bytes(&[b"{\"ok\":1}\nnot json\n"])));
+        assert!(invalid.next().await.unwrap().is_ok());
+        assert!(matches!(invalid.next().await, Some(Err(HoverShellError::Parse(_)))));
+    }
+}
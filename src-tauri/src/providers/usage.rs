@@ -0,0 +1,448 @@
+use super::UsageInfo;
+use crate::{
+    config::{Config, ProviderConfig, UsageConfig},
+    error::{HoverShellError, Result},
+    utils::write_atomic,
+};
+use chrono::{Datelike, Local, NaiveDate};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Price table key used for models without an entry of their own
+const DEFAULT_PRICE_KEY: &str = "default";
+
+// USD per million tokens
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+// Totals for one provider on one local calendar day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub date: NaiveDate,
+    pub provider_id: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageQuery {
+    pub provider_id: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderSpend {
+    pub provider_id: String,
+    pub requests: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    pub budget: Option<f64>,
+}
+
+// Month-to-date spend against the configured budgets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub month: String,
+    pub requests: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    pub budget: Option<f64>,
+    pub remaining: Option<f64>,
+    pub providers: Vec<ProviderSpend>,
+}
+
+struct ProviderPricing {
+    model: Option<String>,
+    prices: HashMap<String, ModelPrice>,
+    monthly_budget: Option<f64>,
+}
+
+// Keeps per-provider, per-day token and cost totals in
+// ~/.hovershell/usage.json and enforces the monthly budgets.
+pub struct UsageTracker {
+    records: Vec<UsageRecord>,
+    pricing: HashMap<String, ProviderPricing>,
+    usage_config: UsageConfig,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            pricing: HashMap::new(),
+            usage_config: UsageConfig::default(),
+        }
+    }
+
+    pub async fn initialize(&mut self, config: &Config) -> Result<()> {
+        self.usage_config = config.usage.clone();
+        self.records = load().await?;
+        info!("Usage tracker loaded {} daily records", self.records.len());
+        Ok(())
+    }
+
+    // Reads `pricing` and `monthly_budget` from the provider's `config`:
+    //
+    //   config:
+    //     monthly_budget: 20
+    //     pricing:
+    //       gpt-4o-mini: { prompt: 0.15, completion: 0.6 }
+    //       default: { prompt: 0.5, completion: 1.5 }
+    pub fn register_provider(&mut self, config: &ProviderConfig) {
+        let prices = match config.config.get("pricing") {
+            Some(pricing) => serde_json::from_value(pricing.clone()).unwrap_or_else(|e| {
+                warn!("Ignoring invalid pricing for provider {}: {}", config.id, e);
+                HashMap::new()
+            }),
+            None => HashMap::new(),
+        };
+
+        self.pricing.insert(config.id.clone(), ProviderPricing {
+            model: config.model.clone(),
+            prices,
+            monthly_budget: config.config.get("monthly_budget").and_then(|b| b.as_f64()),
+        });
+    }
+
+    // Refuses new requests once this month's spend has reached the global
+    // budget or the provider's own one
+    pub fn check_budget(&self, provider_id: &str) -> Result<()> {
+        if !self.usage_config.enabled {
+            return Ok(());
+        }
+
+        if let Some(budget) = self.usage_config.monthly_budget {
+            let spent = self.month_cost(None);
+            if spent >= budget {
+                return Err(HoverShellError::Provider(format!(
+                    "Monthly AI budget of ${:.2} reached (${:.2} spent)",
+                    budget, spent
+                )));
+            }
+        }
+
+        if let Some(budget) = self.pricing.get(provider_id).and_then(|p| p.monthly_budget) {
+            let spent = self.month_cost(Some(provider_id));
+            if spent >= budget {
+                return Err(HoverShellError::Provider(format!(
+                    "Monthly budget of ${:.2} for provider {} reached (${:.2} spent)",
+                    budget, provider_id, spent
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fills in the cost from the provider's price table, if it has one
+    pub fn price(&self, provider_id: &str, usage: &mut UsageInfo) {
+        let Some(pricing) = self.pricing.get(provider_id) else {
+            return;
+        };
+        let price = pricing.model.as_ref()
+            .and_then(|model| pricing.prices.get(model))
+            .or_else(|| pricing.prices.get(DEFAULT_PRICE_KEY));
+
+        if let Some(price) = price {
+            usage.cost = Some(
+                (usage.prompt_tokens as f64 * price.prompt
+                    + usage.completion_tokens as f64 * price.completion)
+                    / 1_000_000.0,
+            );
+        }
+    }
+
+    pub async fn record(&mut self, provider_id: &str, usage: &UsageInfo) -> Result<()> {
+        if !self.usage_config.enabled {
+            return Ok(());
+        }
+
+        let date = Local::now().date_naive();
+        let index = match self.records.iter().position(|r| r.date == date && r.provider_id == provider_id) {
+            Some(index) => index,
+            None => {
+                self.records.push(UsageRecord {
+                    date,
+                    provider_id: provider_id.to_string(),
+                    requests: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    total_tokens: 0,
+                    cost: 0.0,
+                });
+                self.records.len() - 1
+            }
+        };
+
+        let record = &mut self.records[index];
+        record.requests += 1;
+        record.prompt_tokens += usage.prompt_tokens as u64;
+        record.completion_tokens += usage.completion_tokens as u64;
+        record.total_tokens += usage.total_tokens as u64;
+        record.cost += usage.cost.unwrap_or(0.0);
+
+        self.save().await
+    }
+
+    pub fn query(&self, query: &UsageQuery) -> Vec<UsageRecord> {
+        let mut records: Vec<UsageRecord> = self.records.iter()
+            .filter(|r| query.provider_id.as_ref().is_none_or(|id| &r.provider_id == id))
+            .filter(|r| query.from.is_none_or(|from| r.date >= from))
+            .filter(|r| query.to.is_none_or(|to| r.date <= to))
+            .cloned()
+            .collect();
+        records.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.provider_id.cmp(&b.provider_id)));
+        records
+    }
+
+    pub fn summary(&self) -> UsageSummary {
+        let today = Local::now().date_naive();
+        let mut providers: HashMap<String, ProviderSpend> = HashMap::new();
+        for record in self.records.iter().filter(|r| same_month(r.date, today)) {
+            let spend = providers.entry(record.provider_id.clone()).or_insert_with(|| ProviderSpend {
+                provider_id: record.provider_id.clone(),
+                requests: 0,
+                total_tokens: 0,
+                cost: 0.0,
+                budget: self.pricing.get(&record.provider_id).and_then(|p| p.monthly_budget),
+            });
+            spend.requests += record.requests;
+            spend.total_tokens += record.total_tokens;
+            spend.cost += record.cost;
+        }
+
+        let mut providers: Vec<ProviderSpend> = providers.into_values().collect();
+        providers.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+
+        let cost = providers.iter().map(|p| p.cost).sum();
+        let budget = self.usage_config.monthly_budget;
+        UsageSummary {
+            month: today.format("%Y-%m").to_string(),
+            requests: providers.iter().map(|p| p.requests).sum(),
+            total_tokens: providers.iter().map(|p| p.total_tokens).sum(),
+            cost,
+            budget,
+            remaining: budget.map(|b| (b - cost).max(0.0)),
+            providers,
+        }
+    }
+
+    fn month_cost(&self, provider_id: Option<&str>) -> f64 {
+        let today = Local::now().date_naive();
+        self.records.iter()
+            .filter(|r| same_month(r.date, today))
+            .filter(|r| provider_id.is_none_or(|id| r.provider_id == id))
+            .map(|r| r.cost)
+            .sum()
+    }
+
+    async fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.records)?;
+        write_atomic(&usage_file(), content.as_bytes()).await
+    }
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Reads token counts out of a response body. Pointers that don't resolve
+// count as zero; a body with neither count has no usage.
+pub fn parse_usage(body: &serde_json::Value, prompt_pointer: &str, completion_pointer: &str) -> Option<UsageInfo> {
+    let count = |pointer: &str| body.pointer(pointer).and_then(|v| v.as_f64()).map(|v| v as u32);
+    let prompt_tokens = count(prompt_pointer);
+    let completion_tokens = count(completion_pointer);
+    if prompt_tokens.is_none() && completion_tokens.is_none() {
+        return None;
+    }
+
+    let prompt_tokens = prompt_tokens.unwrap_or(0);
+    let completion_tokens = completion_tokens.unwrap_or(0);
+    Some(UsageInfo {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        cost: None,
+    })
+}
+
+pub fn usage_file() -> PathBuf {
+    Config::config_dir().join("usage.json")
+}
+
+fn same_month(date: NaiveDate, today: NaiveDate) -> bool {
+    date.year() == today.year() && date.month() == today.month()
+}
+
+async fn load() -> Result<Vec<UsageRecord>> {
+    load_from(&usage_file()).await
+}
+
+async fn load_from(path: &Path) -> Result<Vec<UsageRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = tokio::fs::read_to_string(path).await?;
+    match serde_json::from_str(&content) {
+        Ok(records) => Ok(records),
+        Err(e) => {
+            // Keep the damaged file for inspection and start counting afresh
+            // rather than refusing to start
+            let aside = path.with_extension("json.bad");
+            warn!("Failed to parse usage file, moving it to {}: {}", aside.display(), e);
+            if let Err(e) = tokio::fs::rename(path, &aside).await {
+                warn!("Failed to move aside usage file: {}", e);
+            }
+            Ok(Vec::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn provider(id: &str, model: &str, config: serde_json::Value) -> ProviderConfig {
+        ProviderConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            provider_type: "openai".to_string(),
+            base_url: None,
+            model: Some(model.to_string()),
+            api_key: None,
+            default: false,
+            enabled: true,
+            config,
+        }
+    }
+
+    fn tracker(monthly_budget: Option<f64>) -> UsageTracker {
+        let mut tracker = UsageTracker::new();
+        tracker.usage_config = UsageConfig { enabled: true, monthly_budget };
+        tracker
+    }
+
+    fn spend(tracker: &mut UsageTracker, provider_id: &str, date: NaiveDate, cost: f64) {
+        tracker.records.push(UsageRecord {
+            date,
+            provider_id: provider_id.to_string(),
+            requests: 1,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            total_tokens: 0,
+            cost,
+        });
+    }
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> UsageInfo {
+        UsageInfo {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            cost: None,
+        }
+    }
+
+    #[test]
+    fn prices_by_model_then_default() {
+        let pricing = json!({
+            "pricing": {
+                "small": { "prompt": 1.0, "completion": 2.0 },
+                "default": { "prompt": 10.0, "completion": 20.0 }
+            }
+        });
+        let mut tracker = tracker(None);
+        tracker.register_provider(&provider("a", "small", pricing.clone()));
+        tracker.register_provider(&provider("b", "large", pricing));
+
+        let mut info = usage(1_000_000, 500_000);
+        tracker.price("a", &mut info);
+        assert_eq!(info.cost, Some(2.0));
+
+        let mut info = usage(1_000_000, 500_000);
+        tracker.price("b", &mut info);
+        assert_eq!(info.cost, Some(20.0));
+
+        let mut info = usage(1_000_000, 500_000);
+        tracker.price("unknown", &mut info);
+        assert_eq!(info.cost, None);
+    }
+
+    #[test]
+    fn global_budget_blocks_once_reached() {
+        let today = Local::now().date_naive();
+        let mut tracker = tracker(Some(5.0));
+        spend(&mut tracker, "a", today, 4.0);
+        assert!(tracker.check_budget("a").is_ok());
+
+        spend(&mut tracker, "b", today, 1.0);
+        assert!(tracker.check_budget("a").is_err());
+        assert!(tracker.check_budget("b").is_err());
+    }
+
+    #[test]
+    fn provider_budget_blocks_only_that_provider() {
+        let today = Local::now().date_naive();
+        let mut tracker = tracker(None);
+        tracker.register_provider(&provider("a", "small", json!({ "monthly_budget": 2.0 })));
+        tracker.register_provider(&provider("b", "small", json!({})));
+        spend(&mut tracker, "a", today, 2.0);
+        spend(&mut tracker, "b", today, 100.0);
+
+        assert!(tracker.check_budget("a").is_err());
+        assert!(tracker.check_budget("b").is_ok());
+    }
+
+    #[test]
+    fn disabled_tracking_ignores_budgets() {
+        let today = Local::now().date_naive();
+        let mut tracker = tracker(Some(1.0));
+        tracker.usage_config.enabled = false;
+        spend(&mut tracker, "a", today, 10.0);
+
+        assert!(tracker.check_budget("a").is_ok());
+    }
+
+    #[test]
+    fn previous_month_does_not_count() {
+        let today = Local::now().date_naive();
+        let last_month = today.with_day(1).unwrap().pred_opt().unwrap();
+        let mut tracker = tracker(Some(5.0));
+        tracker.register_provider(&provider("a", "small", json!({ "monthly_budget": 1.0 })));
+        spend(&mut tracker, "a", last_month, 50.0);
+
+        assert!(tracker.check_budget("a").is_ok());
+        let summary = tracker.summary();
+        assert_eq!(summary.cost, 0.0);
+        assert_eq!(summary.remaining, Some(5.0));
+    }
+
+    #[tokio::test]
+    async fn malformed_file_is_moved_aside() {
+        let path = std::env::temp_dir().join(format!("hovershell-usage-{}.json", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, "{ not json").await.unwrap();
+
+        let records = load_from(&path).await.unwrap();
+        assert!(records.is_empty());
+        assert!(!path.exists());
+
+        let aside = path.with_extension("json.bad");
+        assert_eq!(tokio::fs::read_to_string(&aside).await.unwrap(), "{ not json");
+        let _ = tokio::fs::remove_file(&aside).await;
+    }
+}
@@ -1,14 +1,53 @@
 use crate::{
-    config::Config,
+    config::{Config, TerminalConfig, WorkspaceRule},
     error::{HoverShellError, Result},
 };
-use log::{error, info};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::process::{Command, Stdio};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::{mpsc, watch, RwLock};
+
+pub mod emulator;
+pub mod layout;
+pub mod persistence;
+pub mod profile;
+pub mod pty;
+pub mod recording;
+pub mod scrollback;
+pub mod search;
+pub mod shell_integration;
+
+use emulator::{Emulator, ScreenSnapshot, TerminalEvent};
+use layout::{FocusDirection, PaneLayout, SplitDirection};
+use persistence::{SavedSession, SavedSessions};
+use profile::ResolvedProfile;
+use pty::{ProcessExit, Pty, PtyOptions, PtySize};
+use recording::{CastEvent, CastHeader, Recorder, Redactor};
+use scrollback::OutputRange;
+use search::{SearchDirection, SearchMatch, SearchOptions, SearchResult};
+use shell_integration::{CommandBlock, ShellIntegration, ShellLaunch};
+
+const READ_CHUNK_SIZE: usize = 4096;
+const OUTPUT_CHANNEL_CAPACITY: usize = 64;
+const MAX_EVENT_BYTES: usize = 64 * 1024;
+const EMIT_INTERVAL: Duration = Duration::from_millis(16);
+// Shortest gap between /proc lookups of a session's directory while output
+// streams in without OSC 7
+const CWD_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+// How long a shell gets to exit after SIGHUP/SIGTERM before it is killed
+const TERMINATE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+const KILL_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub const TERMINAL_OUTPUT_EVENT: &str = "terminal-output";
+pub const COMMAND_FINISHED_EVENT: &str = "command-finished";
+pub const CWD_CHANGED_EVENT: &str = "cwd-changed";
+pub const SESSION_EXITED_EVENT: &str = "session-exited";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TerminalSession {
@@ -19,103 +58,421 @@ pub struct TerminalSession {
     pub is_active: bool,
     pub output: String,
     pub process_id: Option<u32>,
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalOutputEvent {
+    pub session_id: String,
+    pub sequence: u64,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandFinishedEvent {
+    pub session_id: String,
+    pub block: CommandBlock,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CwdChangedEvent {
+    pub session_id: String,
+    pub working_directory: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionExitedEvent {
+    pub session_id: String,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub auto_closed: bool,
+}
+
+// Sent by a session's wait thread once its shell has been reaped
+#[derive(Debug, Clone)]
+pub struct SessionExit {
+    pub session_id: String,
+    pub pid: u32,
+    pub exit: ProcessExit,
+}
+
+// Screen state and shell integration markers for one session. Both are fed
+// from the same output stream so block line indices match the transcript.
+struct SessionScreen {
+    emulator: Emulator,
+    integration: ShellIntegration,
+    working_directory: Option<String>,
+    recorder: Option<Recorder>,
+}
+
+impl SessionScreen {
+    fn new(cols: u16, rows: u16, scrollback_lines: usize) -> Self {
+        Self {
+            emulator: Emulator::new(cols, rows, scrollback_lines),
+            integration: ShellIntegration::new(),
+            working_directory: None,
+            recorder: None,
+        }
+    }
+
+    fn finish_recording(&mut self) -> Option<PathBuf> {
+        match self.recorder.take()?.finish() {
+            Ok(path) => Some(path),
+            Err(e) => {
+                error!("Failed to finish recording: {}", e);
+                None
+            }
+        }
+    }
+}
+
+type Screens = Arc<RwLock<HashMap<String, SessionScreen>>>;
+
+struct ShellProcess {
+    pty: Pty,
+    output_task: tokio::task::JoinHandle<()>,
+    exited: watch::Receiver<Option<ProcessExit>>,
+}
+
+impl ShellProcess {
+    // Hangs up the shell and its jobs, then kills whatever is still running
+    // after the grace period. The wait thread reaps the shell either way.
+    async fn terminate(mut self) -> Option<ProcessExit> {
+        let pid = self.pty.pid();
+        if self.exited.borrow().is_none() {
+            info!("Terminating process: {}", pid);
+            self.pty.signal_group(libc::SIGHUP);
+            self.pty.signal_group(libc::SIGTERM);
+            // Stopped jobs only see the signals once they are continued
+            self.pty.signal_group(libc::SIGCONT);
+
+            if !wait_for_exit(&mut self.exited, TERMINATE_GRACE_PERIOD).await {
+                warn!("Process {} did not exit after SIGTERM, sending SIGKILL", pid);
+                self.pty.signal_group(libc::SIGKILL);
+                if !wait_for_exit(&mut self.exited, KILL_TIMEOUT).await {
+                    error!("Process {} did not exit after SIGKILL", pid);
+                }
+            }
+        }
+
+        self.output_task.abort();
+        let exit = *self.exited.borrow();
+        exit
+    }
 }
 
 pub struct TerminalManager {
     sessions: HashMap<String, TerminalSession>,
     active_session: Option<String>,
-    output_buffer: HashMap<String, Vec<String>>,
+    screens: Screens,
+    processes: HashMap<String, ShellProcess>,
+    // Shells of closed sessions that are still being terminated
+    terminating: Vec<tokio::task::JoinHandle<Option<ProcessExit>>>,
+    playbacks: HashMap<String, tokio::task::JoinHandle<()>>,
+    layouts: Vec<PaneLayout>,
+    app_handle: Option<AppHandle>,
+    terminal_config: TerminalConfig,
+    workspace_rules: Vec<WorkspaceRule>,
+    // Masks secrets in the scrollback and history written to disk
+    redactor: Redactor,
+    exit_tx: mpsc::UnboundedSender<SessionExit>,
+    exit_rx: Option<mpsc::UnboundedReceiver<SessionExit>>,
+    command_tx: mpsc::UnboundedSender<CommandFinishedEvent>,
+    command_rx: Option<mpsc::UnboundedReceiver<CommandFinishedEvent>>,
 }
 
 impl TerminalManager {
     pub async fn new() -> Result<Self> {
         info!("Initializing terminal manager");
         
+        let (exit_tx, exit_rx) = mpsc::unbounded_channel();
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
         Ok(Self {
             sessions: HashMap::new(),
             active_session: None,
-            output_buffer: HashMap::new(),
+            screens: Arc::new(RwLock::new(HashMap::new())),
+            processes: HashMap::new(),
+            terminating: Vec::new(),
+            playbacks: HashMap::new(),
+            layouts: Vec::new(),
+            app_handle: None,
+            terminal_config: TerminalConfig::default(),
+            workspace_rules: Vec::new(),
+            redactor: Redactor::default(),
+            exit_tx,
+            exit_rx: Some(exit_rx),
+            command_tx,
+            command_rx: Some(command_rx),
         })
     }
 
     pub async fn shutdown(&mut self) -> Result<()> {
         info!("Shutting down terminal manager");
         
-        // Terminate all active sessions
-        for (id, session) in self.sessions.iter() {
-            if let Some(pid) = session.process_id {
-                if let Err(e) = self.terminate_process(pid).await {
-                    error!("Failed to terminate process {} for session {}: {}", pid, id, e);
-                }
-            }
+        // Terminate all active sessions in parallel so shutdown waits for
+        // at most one grace period
+        let terminations = self.processes.drain().map(|(_, process)| process.terminate());
+        futures_util::future::join_all(terminations).await;
+        futures_util::future::join_all(self.terminating.drain(..)).await;
+        
+        for (_, playback) in self.playbacks.drain() {
+            playback.abort();
         }
         
         self.sessions.clear();
         self.active_session = None;
-        self.output_buffer.clear();
+        self.layouts.clear();
+        for (_, mut screen) in self.screens.write().await.drain() {
+            screen.finish_recording();
+        }
         
         Ok(())
     }
 
-    pub async fn initialize(&mut self, config: &Config) -> Result<()> {
+    pub async fn initialize(&mut self, config: &Config, app_handle: &AppHandle) -> Result<()> {
+        self.app_handle = Some(app_handle.clone());
+        self.terminal_config = config.terminal.clone();
+        self.workspace_rules = config.workspace_rules.clone();
+        self.redactor = Redactor::from_config(config);
+        
+        if config.terminal.restore_sessions {
+            match persistence::load().await {
+                Ok(Some(saved)) => {
+                    let restored = self.restore_sessions(saved).await;
+                    if restored > 0 {
+                        info!("Terminal manager initialized with {} restored sessions", restored);
+                        return Ok(());
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to load saved sessions: {}", e),
+            }
+        }
+        
         // Create initial session
-        let session_id = uuid::Uuid::new_v4().to_string();
-        let session = TerminalSession {
-            id: session_id.clone(),
-            title: "Terminal".to_string(),
-            working_directory: std::env::current_dir()
-                .unwrap_or_else(|_| std::path::PathBuf::from("."))
-                .to_string_lossy()
-                .to_string(),
-            shell: config.terminal.shell.clone(),
-            is_active: true,
-            output: String::new(),
-            process_id: None,
-        };
-
-        self.sessions.insert(session_id.clone(), session);
-        self.active_session = Some(session_id);
+        let profile = self.resolve_profile(None, None);
+        let session_id = self.create_session(Some("Terminal".to_string()), profile).await?;
+        self.set_active_session(&session_id).await?;
         
         info!("Terminal manager initialized with default session");
         Ok(())
     }
 
-    pub async fn create_session(&mut self, title: Option<String>, working_directory: Option<String>) -> Result<String> {
+    // Recreates saved sessions with their scrollback and history and starts
+    // a fresh shell in each. Returns how many sessions were restored.
+    async fn restore_sessions(&mut self, saved: SavedSessions) -> usize {
+        let mut restored = 0;
+        let mut restored_order = Vec::new();
+        
+        for saved_session in saved.sessions {
+            // The directory may have been removed since the last run
+            let working_directory = if std::path::Path::new(&saved_session.working_directory).is_dir() {
+                saved_session.working_directory
+            } else {
+                dirs::home_dir()
+                    .unwrap_or_else(|| std::path::PathBuf::from("/"))
+                    .to_string_lossy()
+                    .to_string()
+            };
+            
+            let session = TerminalSession {
+                id: saved_session.id.clone(),
+                title: saved_session.title,
+                working_directory,
+                shell: profile::validate_shell(&saved_session.shell),
+                is_active: false,
+                output: String::new(),
+                process_id: None,
+                environment: saved_session.environment,
+                args: saved_session.args,
+                profile: saved_session.profile,
+            };
+            
+            let size = PtySize::default();
+            let mut screen = SessionScreen::new(size.cols, size.rows, self.terminal_config.scrollback_lines);
+            for line in &saved_session.scrollback {
+                screen.emulator.scrollback_mut().push_line(line);
+            }
+            screen.integration.seed_history(saved_session.command_history);
+            
+            self.screens.write().await.insert(session.id.clone(), screen);
+            self.sessions.insert(session.id.clone(), session);
+            
+            if let Err(e) = self.start_shell_process(&saved_session.id).await {
+                error!("Failed to restore session {}: {}", saved_session.id, e);
+                self.sessions.remove(&saved_session.id);
+                self.screens.write().await.remove(&saved_session.id);
+                continue;
+            }
+            
+            if saved_session.is_active || self.active_session.is_none() {
+                self.active_session = Some(saved_session.id.clone());
+            }
+            restored_order.push(saved_session.id);
+            restored += 1;
+        }
+        
+        // Keep saved pane layouts for sessions that came back; any session
+        // not placed in a layout gets a tab of its own
+        for mut layout in saved.layouts {
+            if layout.retain_sessions(|id| self.sessions.contains_key(id)) {
+                self.layouts.push(layout);
+            }
+        }
+        for session_id in restored_order {
+            if !self.layouts.iter().any(|layout| layout.contains(&session_id)) {
+                self.layouts.push(PaneLayout::new(&session_id));
+            }
+        }
+        
+        if let Some(active_id) = self.active_session.clone() {
+            for session in self.sessions.values_mut() {
+                session.is_active = session.id == active_id;
+            }
+        }
+        
+        restored
+    }
+
+    // Writes session metadata, scrollback and command history so the next
+    // launch can restore them
+    pub async fn save_sessions(&self) -> Result<()> {
+        let screens = self.screens.read().await;
+        // Playbacks are read-only views of a recording, not shells to restore
+        let sessions: Vec<SavedSession> = self.sessions.values()
+            .filter(|session| !self.playbacks.contains_key(&session.id))
+            .map(|session| {
+                let session = with_live_cwd(session, &screens);
+                let screen = screens.get(&session.id);
+            
+                SavedSession {
+                    scrollback: screen
+                        .map(|screen| {
+                            screen.emulator.transcript_range(None, None)
+                                .lines
+                                .into_iter()
+                                .map(|line| self.redactor.redact(&line.text))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    command_history: screen
+                        .map(|screen| {
+                            screen.integration.history()
+                                .iter()
+                                .map(|command| self.redactor.redact(command))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    is_active: self.active_session.as_deref() == Some(session.id.as_str()),
+                    id: session.id,
+                    title: session.title,
+                    working_directory: session.working_directory,
+                    shell: session.shell,
+                    environment: session.environment,
+                    args: session.args,
+                    profile: session.profile,
+                }
+            })
+            .collect();
+        let layouts = self.layouts.iter()
+            .cloned()
+            .filter_map(|mut layout| {
+                layout.retain_sessions(|id| !self.playbacks.contains_key(id)).then_some(layout)
+            })
+            .collect();
+        
+        let count = sessions.len();
+        persistence::save(&SavedSessions::new(sessions, layouts)).await?;
+        info!("Saved {} terminal sessions", count);
+        Ok(())
+    }
+
+    // Profile for a new session from the terminal config, the named profile
+    // and the workspace rules matching the working directory
+    pub fn resolve_profile(&self, working_directory: Option<&str>, profile_name: Option<&str>) -> ResolvedProfile {
+        profile::resolve(&self.terminal_config, &self.workspace_rules, working_directory, profile_name)
+    }
+
+    pub async fn create_session(&mut self, title: Option<String>, profile: ResolvedProfile) -> Result<String> {
+        let session_id = self.spawn_session(title, profile).await?;
+        self.layouts.push(PaneLayout::new(&session_id));
+        
+        Ok(session_id)
+    }
+
+    // Creates a session and starts its shell without giving it a tab
+    async fn spawn_session(&mut self, title: Option<String>, profile: ResolvedProfile) -> Result<String> {
+        if !std::path::Path::new(&profile.working_directory).is_dir() {
+            return Err(HoverShellError::Terminal(format!(
+                "Working directory does not exist: {}",
+                profile.working_directory
+            )));
+        }
+        
         let session_id = uuid::Uuid::new_v4().to_string();
         let title = title.unwrap_or_else(|| format!("Terminal {}", self.sessions.len() + 1));
-        let working_directory = working_directory.unwrap_or_else(|| {
-            std::env::current_dir()
-                .unwrap_or_else(|_| std::path::PathBuf::from("."))
-                .to_string_lossy()
-                .to_string()
-        });
+        let args = profile.shell_args();
 
         let session = TerminalSession {
             id: session_id.clone(),
             title,
-            working_directory,
-            shell: "/bin/zsh".to_string(), // TODO: Get from config
+            working_directory: profile.working_directory,
+            shell: profile.shell,
             is_active: false,
             output: String::new(),
             process_id: None,
+            environment: profile.environment,
+            args,
+            profile: profile.name,
         };
 
         self.sessions.insert(session_id.clone(), session);
+        if let Err(e) = self.start_shell_process(&session_id).await {
+            self.sessions.remove(&session_id);
+            self.screens.write().await.remove(&session_id);
+            return Err(e);
+        }
         info!("Created new terminal session: {}", session_id);
         
         Ok(session_id)
     }
 
     pub async fn close_session(&mut self, session_id: &str) -> Result<()> {
-        if let Some(session) = self.sessions.remove(session_id) {
-            if let Some(pid) = session.process_id {
-                self.terminate_process(pid).await?;
+        if self.sessions.remove(session_id).is_some() {
+            // Terminating can take the whole grace period, which callers
+            // holding the manager lock shouldn't wait out
+            if let Some(process) = self.processes.remove(session_id) {
+                self.terminating.retain(|task| !task.is_finished());
+                self.terminating.push(tokio::spawn(process.terminate()));
+            }
+            if let Some(playback) = self.playbacks.remove(session_id) {
+                playback.abort();
+            }
+            if let Some(mut screen) = self.screens.write().await.remove(session_id) {
+                screen.finish_recording();
             }
             
-            self.output_buffer.remove(session_id);
+            // Closing a pane hands its space to the sibling pane, and focus
+            // moves within the same tab when possible
+            let mut next_focus = None;
+            if let Some(index) = self.layouts.iter().position(|layout| layout.contains(session_id)) {
+                if self.layouts[index].remove(session_id) {
+                    next_focus = Some(self.layouts[index].focused_session.clone());
+                } else {
+                    self.layouts.remove(index);
+                }
+            }
             
-            if self.active_session.as_ref() == Some(session_id) {
-                self.active_session = self.sessions.keys().next().cloned();
+            if self.active_session.as_deref() == Some(session_id) {
+                self.active_session = next_focus.or_else(|| self.sessions.keys().next().cloned());
+                if let Some(session) = self.active_session.as_ref().and_then(|id| self.sessions.get_mut(id)) {
+                    session.is_active = true;
+                }
             }
             
             info!("Closed terminal session: {}", session_id);
@@ -138,6 +495,9 @@ impl TerminalManager {
                 session.is_active = true;
                 self.active_session = Some(session_id.to_string());
             }
+            if let Some(layout) = self.layouts.iter_mut().find(|layout| layout.contains(session_id)) {
+                layout.focused_session = session_id.to_string();
+            }
             
             info!("Set active session: {}", session_id);
         } else {
@@ -148,10 +508,10 @@ impl TerminalManager {
     }
 
     pub async fn send_input(&mut self, session_id: &str, input: &str) -> Result<()> {
-        if let Some(session) = self.sessions.get(session_id) {
-            // Execute the command and update output buffer
+        if self.sessions.contains_key(session_id) {
+            // Forward the line to the session's shell
             self.execute_command(session_id, input).await?;
-            info!("Sent input to session {}: {}", session_id, input);
+            info!("Sent input to session {}", session_id);
         } else {
             return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
         }
@@ -159,95 +519,200 @@ impl TerminalManager {
         Ok(())
     }
 
-    pub async fn get_output(&self, session_id: &str) -> Result<String> {
-        if let Some(lines) = self.output_buffer.get(session_id) {
-            Ok(lines.join("\n"))
-        } else {
-            Ok(String::new())
+    // Keystrokes and pastes go to the shell exactly as typed; the shell
+    // does its own line editing and echo
+    pub async fn write_input(&mut self, session_id: &str, data: &[u8]) -> Result<()> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
         }
+
+        if self.playbacks.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session is a recording playback: {}", session_id)));
+        }
+
+        if !self.processes.contains_key(session_id) {
+            self.start_shell_process(session_id).await?;
+        }
+
+        if let Some(process) = self.processes.get_mut(session_id) {
+            process.pty.write_all(data)?;
+        }
+
+        debug!("Wrote {} bytes to session {}", data.len(), session_id);
+        Ok(())
+    }
+
+    pub async fn get_output(&self, session_id: &str, from: Option<u64>, to: Option<u64>) -> Result<OutputRange> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
+        }
+        
+        let screens = self.screens.read().await;
+        Ok(match screens.get(session_id) {
+            Some(screen) => screen.emulator.transcript_range(from, to),
+            None => OutputRange {
+                first_index: 0,
+                next_index: 0,
+                lines: Vec::new(),
+            },
+        })
+    }
+
+    pub async fn get_snapshot(&self, session_id: &str) -> Result<ScreenSnapshot> {
+        self.screens.read().await
+            .get(session_id)
+            .map(|screen| screen.emulator.snapshot())
+            .ok_or_else(|| HoverShellError::Terminal(format!("No screen for session: {}", session_id)))
     }
 
     pub async fn get_state(&self) -> Vec<crate::commands::TerminalState> {
+        let screens = self.screens.read().await;
         self.sessions.values().map(|session| {
             crate::commands::TerminalState {
                 id: session.id.clone(),
                 title: session.title.clone(),
-                working_directory: session.working_directory.clone(),
+                working_directory: screens.get(&session.id)
+                    .and_then(|screen| screen.working_directory.clone())
+                    .unwrap_or_else(|| session.working_directory.clone()),
                 is_active: session.is_active,
-                output: self.output_buffer.get(&session.id)
-                    .map(|lines| lines.join("\n"))
+                output: screens.get(&session.id)
+                    .map(|screen| screen.emulator.transcript_text())
                     .unwrap_or_default(),
             }
         }).collect()
     }
 
-    pub async fn get_session(&self, session_id: &str) -> Option<&TerminalSession> {
-        self.sessions.get(session_id)
+    pub async fn get_command_blocks(&self, session_id: &str) -> Result<Vec<CommandBlock>> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
+        }
+        
+        Ok(self.screens.read().await
+            .get(session_id)
+            .map(|screen| screen.integration.blocks())
+            .unwrap_or_default())
     }
 
-    pub async fn get_active_session(&self) -> Option<&TerminalSession> {
-        self.active_session.as_ref().and_then(|id| self.sessions.get(id))
+    pub async fn get_command_output(&self, session_id: &str, block_id: u64) -> Result<String> {
+        let screens = self.screens.read().await;
+        let screen = screens.get(session_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("No screen for session: {}", session_id)))?;
+        let block = screen.integration.get_block(block_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("Command block not found: {}", block_id)))?;
+        
+        Ok(shell_integration::block_output(&screen.emulator, block))
     }
 
-    pub async fn get_session_list(&self) -> Vec<&TerminalSession> {
-        self.sessions.values().collect()
+    // The last non-blank line a command printed
+    pub async fn last_output_line(&self, session_id: &str, block_id: u64) -> Option<String> {
+        let output = self.get_command_output(session_id, block_id).await.ok()?;
+        output.lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string)
     }
 
-    pub async fn execute_command(&mut self, session_id: &str, command: &str) -> Result<String> {
-        if let Some(session) = self.sessions.get(session_id) {
-            use std::process::{Command, Stdio};
-            use tokio::io::{AsyncBufReadExt, BufReader};
-            
-            // Start shell process
-            let mut child = Command::new(&session.shell)
-                .current_dir(&session.working_directory)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .map_err(|e| HoverShellError::Terminal(format!("Failed to start shell: {}", e)))?;
-            
-            // Send command to stdin
-            if let Some(stdin) = child.stdin.as_mut() {
-                use std::io::Write;
-                stdin.write_all(command.as_bytes())
-                    .map_err(|e| HoverShellError::Terminal(format!("Failed to write to stdin: {}", e)))?;
-                stdin.write_all(b"\n")
-                    .map_err(|e| HoverShellError::Terminal(format!("Failed to write newline: {}", e)))?;
-            }
-            
-            // Wait for command to complete
-            let output = child.wait_with_output()
-                .map_err(|e| HoverShellError::Terminal(format!("Failed to wait for command: {}", e)))?;
-            
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            
-            // Add to output buffer
-            let output_lines = self.output_buffer.entry(session_id.to_string()).or_insert_with(Vec::new);
-            output_lines.push(format!("$ {}", command));
-            
-            if !stdout.is_empty() {
-                for line in stdout.lines() {
-                    output_lines.push(line.to_string());
-                }
-            }
-            
-            if !stderr.is_empty() {
-                for line in stderr.lines() {
-                    output_lines.push(format!("error: {}", line));
-                }
-            }
-            
-            info!("Executed command in session {}: {}", session_id, command);
-            Ok(stdout.to_string())
-        } else {
-            Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)))
+    pub async fn search_scrollback(
+        &self,
+        session_id: &str,
+        pattern: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchResult> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
+        }
+        
+        let regex = search::build_regex(pattern, options)?;
+        let screens = self.screens.read().await;
+        Ok(search_screen(session_id, screens.get(session_id), pattern, &regex, options))
+    }
+
+    pub async fn search_all_sessions(&self, pattern: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        let regex = search::build_regex(pattern, options)?;
+        let screens = self.screens.read().await;
+        
+        Ok(self.sessions.keys()
+            .map(|session_id| search_screen(session_id, screens.get(session_id), pattern, &regex, options))
+            .filter(|result| !result.matches.is_empty())
+            .collect())
+    }
+
+    // Finds the next or previous match relative to a position, wrapping
+    // around at either end of the scrollback
+    pub async fn find_match(
+        &self,
+        session_id: &str,
+        pattern: &str,
+        options: &SearchOptions,
+        line: u64,
+        column: usize,
+        direction: SearchDirection,
+    ) -> Result<Option<SearchMatch>> {
+        let options = SearchOptions {
+            max_results: usize::MAX,
+            ..options.clone()
+        };
+        let result = self.search_scrollback(session_id, pattern, &options).await?;
+        Ok(search::step_match(&result.matches, line, column, direction))
+    }
+
+    pub async fn get_command_history(&self, session_id: &str) -> Result<Vec<String>> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
         }
+        
+        Ok(self.screens.read().await
+            .get(session_id)
+            .map(|screen| screen.integration.history())
+            .unwrap_or_default())
+    }
+
+    pub async fn get_session(&self, session_id: &str) -> Option<TerminalSession> {
+        let screens = self.screens.read().await;
+        self.sessions.get(session_id).map(|session| with_live_cwd(session, &screens))
+    }
+
+    pub async fn get_active_session(&self) -> Option<TerminalSession> {
+        let screens = self.screens.read().await;
+        self.active_session.as_ref()
+            .and_then(|id| self.sessions.get(id))
+            .map(|session| with_live_cwd(session, &screens))
+    }
+
+    pub async fn get_session_list(&self) -> Vec<TerminalSession> {
+        let screens = self.screens.read().await;
+        self.sessions.values().map(|session| with_live_cwd(session, &screens)).collect()
+    }
+
+    pub async fn execute_command(&mut self, session_id: &str, command: &str) -> Result<()> {
+        if !self.sessions.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
+        }
+        
+        if self.playbacks.contains_key(session_id) {
+            return Err(HoverShellError::Terminal(format!("Session is a recording playback: {}", session_id)));
+        }
+        
+        // Restart the shell if it has not been started or was stopped
+        if !self.processes.contains_key(session_id) {
+            self.start_shell_process(session_id).await?;
+        }
+        
+        if let Some(process) = self.processes.get_mut(session_id) {
+            let mut line = command.as_bytes().to_vec();
+            line.push(b'\n');
+            process.pty.write_all(&line)?;
+        }
+        
+        info!("Executed command in session {}", session_id);
+        Ok(())
     }
 
     pub async fn clear_output(&mut self, session_id: &str) -> Result<()> {
-        self.output_buffer.remove(session_id);
+        if let Some(screen) = self.screens.write().await.get_mut(session_id) {
+            screen.emulator.clear_history();
+        }
         
         if let Some(session) = self.sessions.get_mut(session_id) {
             session.output.clear();
@@ -258,56 +723,631 @@ impl TerminalManager {
     }
 
     pub async fn resize_terminal(&mut self, session_id: &str, width: u16, height: u16) -> Result<()> {
-        if let Some(session) = self.sessions.get(session_id) {
-            if let Some(pid) = session.process_id {
-                // TODO: Implement terminal resize
-                // This would involve sending SIGWINCH to the process
-                info!("Resizing terminal {} to {}x{}", session_id, width, height);
-            }
-        } else {
+        if !self.sessions.contains_key(session_id) {
             return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
         }
         
+        if width == 0 || height == 0 {
+            return Err(HoverShellError::Terminal(format!("Invalid terminal size: {}x{}", width, height)));
+        }
+        
+        if let Some(process) = self.processes.get_mut(session_id) {
+            let size = PtySize { rows: height, cols: width };
+            let current = process.pty.size();
+            if current.rows != size.rows || current.cols != size.cols {
+                process.pty.resize(size)?;
+                info!("Resized terminal {} to {}x{}", session_id, width, height);
+            }
+        }
+        
+        // The screen keeps the size even without a shell, so a restarted
+        // shell starts at the size the panel last reported
+        if let Some(screen) = self.screens.write().await.get_mut(session_id) {
+            if screen.emulator.size() != (width as usize, height as usize) {
+                screen.emulator.resize(width, height);
+                if let Some(recorder) = screen.recorder.as_mut() {
+                    if let Err(e) = recorder.write_resize(width, height) {
+                        error!("Failed to record resize for session {}: {}", session_id, e);
+                    }
+                }
+            }
+        }
+        
         Ok(())
     }
 
-    async fn terminate_process(&self, pid: u32) -> Result<()> {
-        // TODO: Implement process termination
-        // This would involve sending SIGTERM or SIGKILL to the process
-        info!("Terminating process: {}", pid);
+    pub async fn get_layouts(&self) -> Vec<PaneLayout> {
+        self.layouts.clone()
+    }
+
+    // Splits the pane showing `session_id` and starts a new session in the
+    // new pane, with the same profile and directory
+    pub async fn split_pane(&mut self, session_id: &str, direction: SplitDirection) -> Result<PaneLayout> {
+        let index = self.layout_index(session_id)?;
+        let source = self.get_session(session_id).await
+            .ok_or_else(|| HoverShellError::Terminal(format!("Session not found: {}", session_id)))?;
+        let profile = self.resolve_profile(Some(&source.working_directory), source.profile.as_deref());
+        
+        let new_session_id = self.spawn_session(None, profile).await?;
+        self.layouts[index].split(session_id, &new_session_id, direction);
+        self.set_active_session(&new_session_id).await?;
+        
+        info!("Split pane {} {:?} into {}", session_id, direction, new_session_id);
+        Ok(self.layouts[index].clone())
+    }
+
+    // Closes the pane's session. Returns the tab's remaining layout, if any.
+    pub async fn close_pane(&mut self, session_id: &str) -> Result<Option<PaneLayout>> {
+        let layout_id = self.layouts[self.layout_index(session_id)?].id.clone();
+        self.close_session(session_id).await?;
+        
+        Ok(self.layouts.iter().find(|layout| layout.id == layout_id).cloned())
+    }
+
+    // Moves focus to the neighboring pane in a direction, returning the
+    // newly focused session
+    pub async fn focus_pane(&mut self, session_id: &str, direction: FocusDirection) -> Result<Option<String>> {
+        let index = self.layout_index(session_id)?;
+        let neighbor = self.layouts[index].neighbor(session_id, direction);
+        
+        if let Some(neighbor) = &neighbor {
+            self.set_active_session(neighbor).await?;
+        }
+        Ok(neighbor)
+    }
+
+    pub async fn resize_split(&mut self, layout_id: &str, split_id: &str, ratio: f32) -> Result<PaneLayout> {
+        let layout = self.layouts.iter_mut()
+            .find(|layout| layout.id == layout_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("Layout not found: {}", layout_id)))?;
+        
+        if !ratio.is_finite() || !layout.set_ratio(split_id, ratio) {
+            return Err(HoverShellError::Terminal(format!("Cannot resize split {} to {}", split_id, ratio)));
+        }
+        Ok(layout.clone())
+    }
+
+    fn layout_index(&self, session_id: &str) -> Result<usize> {
+        self.layouts.iter()
+            .position(|layout| layout.contains(session_id))
+            .ok_or_else(|| HoverShellError::Terminal(format!("No pane for session: {}", session_id)))
+    }
+
+    pub async fn start_recording(
+        &mut self,
+        session_id: &str,
+        path: Option<PathBuf>,
+        redactor: Redactor,
+    ) -> Result<PathBuf> {
+        let session = self.sessions.get(session_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("Session not found: {}", session_id)))?;
+        
+        let mut screens = self.screens.write().await;
+        let screen = screens.get_mut(session_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("No screen for session: {}", session_id)))?;
+        if let Some(recorder) = &screen.recorder {
+            return Err(HoverShellError::Terminal(format!(
+                "Session {} is already recording to {}",
+                session_id,
+                recorder.path().display()
+            )));
+        }
+        
+        let (cols, rows) = screen.emulator.size();
+        let header = CastHeader {
+            version: 2,
+            width: cols as u16,
+            height: rows as u16,
+            timestamp: Some(chrono::Utc::now().timestamp()),
+            idle_time_limit: None,
+            title: Some(session.title.clone()),
+            env: HashMap::from([
+                ("SHELL".to_string(), session.shell.clone()),
+                ("TERM".to_string(), "xterm-256color".to_string()),
+            ]),
+        };
+        
+        let path = path.unwrap_or_else(|| recording::default_recording_path(session_id));
+        let recorder = Recorder::create(&path, &header, redactor)?;
+        screen.recorder = Some(recorder);
+        
+        info!("Recording session {} to {}", session_id, path.display());
+        Ok(path)
+    }
+
+    pub async fn stop_recording(&mut self, session_id: &str) -> Result<PathBuf> {
+        let mut screens = self.screens.write().await;
+        let recorder = screens.get_mut(session_id)
+            .and_then(|screen| screen.recorder.take())
+            .ok_or_else(|| HoverShellError::Terminal(format!("Session is not recording: {}", session_id)))?;
+        
+        let path = recorder.finish()?;
+        info!("Stopped recording session {} to {}", session_id, path.display());
+        Ok(path)
+    }
+
+    // Opens a read-only session that replays a recording through its own
+    // emulator, emitting output events as a live shell would
+    pub async fn play_recording(&mut self, path: &Path, speed: f64) -> Result<String> {
+        if speed.is_nan() || speed < recording::MIN_PLAYBACK_SPEED {
+            return Err(HoverShellError::Terminal(format!(
+                "Invalid playback speed: {} (minimum {})",
+                speed,
+                recording::MIN_PLAYBACK_SPEED
+            )));
+        }
+        
+        let (header, events) = recording::read_cast(path)?;
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let title = header.title.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Recording".to_string())
+        });
+        
+        let session = TerminalSession {
+            id: session_id.clone(),
+            title: format!("Playback: {}", title),
+            working_directory: path.parent()
+                .map(|parent| parent.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            shell: header.env.get("SHELL").cloned().unwrap_or_default(),
+            is_active: false,
+            output: String::new(),
+            process_id: None,
+            environment: HashMap::new(),
+            args: Vec::new(),
+            profile: None,
+        };
+        
+        self.screens.write().await.insert(
+            session_id.clone(),
+            SessionScreen::new(header.width.max(1), header.height.max(1), self.terminal_config.scrollback_lines),
+        );
+        self.sessions.insert(session_id.clone(), session);
+        self.layouts.push(PaneLayout::new(&session_id));
+        
+        let playback = tokio::spawn(play_cast(
+            session_id.clone(),
+            header,
+            events,
+            speed,
+            self.screens.clone(),
+            self.app_handle.clone(),
+        ));
+        self.playbacks.insert(session_id.clone(), playback);
+        
+        info!("Playing {} in session {} at {}x", path.display(), session_id, speed);
+        Ok(session_id)
+    }
+
+    pub async fn stop_playback(&mut self, session_id: &str) -> Result<()> {
+        // The session stays a playback, so it still takes no input and
+        // isn't saved
+        let playback = self.playbacks.get(session_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("No playback for session: {}", session_id)))?;
+        playback.abort();
+        
+        info!("Stopped playback in session: {}", session_id);
+        Ok(())
+    }
+
+    // Exits are delivered through this channel so the caller can hand them
+    // back to `handle_session_exit` without holding the manager lock while
+    // waiting. Returns `None` after the first call.
+    pub fn take_exit_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<SessionExit>> {
+        self.exit_rx.take()
+    }
+
+    // Every command that finishes in any session, for consumers such as the
+    // command history. Returns `None` after the first call.
+    pub fn take_command_receiver(&mut self) -> Option<mpsc::UnboundedReceiver<CommandFinishedEvent>> {
+        self.command_rx.take()
+    }
+
+    // Called when a shell exits on its own. Exits of processes that were
+    // terminated through the manager are ignored since their session has
+    // already been closed or restarted.
+    pub async fn handle_session_exit(&mut self, exit: SessionExit) -> Result<()> {
+        let Some(session) = self.sessions.get_mut(&exit.session_id) else {
+            return Ok(());
+        };
+        if session.process_id != Some(exit.pid) {
+            return Ok(());
+        }
+        
+        session.process_id = None;
+        // The output task is left running rather than aborted so it still
+        // forwards whatever the shell printed last
+        self.processes.remove(&exit.session_id);
+        info!(
+            "Shell process {} for session {} exited with code {:?}, signal {:?}",
+            exit.pid, exit.session_id, exit.exit.code, exit.exit.signal
+        );
+        
+        let auto_close = self.terminal_config.auto_close;
+        if auto_close {
+            self.close_session(&exit.session_id).await?;
+        }
+        
+        if let Some(app_handle) = &self.app_handle {
+            let event = SessionExitedEvent {
+                session_id: exit.session_id.clone(),
+                exit_code: exit.exit.code,
+                signal: exit.exit.signal,
+                auto_closed: auto_close,
+            };
+            if let Err(e) = app_handle.emit_all(SESSION_EXITED_EVENT, event) {
+                error!("Failed to emit session exit: {}", e);
+            }
+        }
+        
         Ok(())
     }
 
     pub async fn start_shell_process(&mut self, session_id: &str) -> Result<()> {
+        let session = self.sessions.get(session_id)
+            .ok_or_else(|| HoverShellError::Terminal(format!("Session not found: {}", session_id)))?;
+        
+        if self.processes.contains_key(session_id) {
+            return Ok(());
+        }
+        
+        info!("Starting shell process for session: {}", session_id);
+        
+        // Integration is best effort; the shell still starts without markers
+        let launch = shell_integration::prepare_launch(&session.shell, &session.args).await.unwrap_or_else(|e| {
+            warn!("Shell integration unavailable for {}: {}", session.shell, e);
+            ShellLaunch {
+                args: session.args.clone(),
+                environment: HashMap::new(),
+            }
+        });
+        let mut environment = launch.environment;
+        environment.extend(session.environment.clone());
+        let args = launch.args;
+        let size = self.screens.read().await
+            .get(session_id)
+            .map(|screen| {
+                let (cols, rows) = screen.emulator.size();
+                PtySize { rows: rows as u16, cols: cols as u16 }
+            })
+            .unwrap_or_default();
+        let pty = Pty::spawn(PtyOptions {
+            shell: &session.shell,
+            args: &args,
+            working_directory: &session.working_directory,
+            environment: &environment,
+            size,
+        })?;
+        let pid = pty.pid();
+        
+        let size = pty.size();
+        self.screens.write().await
+            .entry(session_id.to_string())
+            .or_insert_with(|| SessionScreen::new(size.cols, size.rows, self.terminal_config.scrollback_lines));
+        
+        // Output is read on a dedicated thread and handed to an async task
+        // that appends it to the session's buffer and forwards it to the UI.
+        // The bounded channel stops the reader when the UI falls behind.
+        let (tx, rx) = mpsc::channel(OUTPUT_CHANNEL_CAPACITY);
+        spawn_pty_reader(session_id, pty.try_clone_reader()?, tx)?;
+        let output_task = tokio::spawn(pump_output(
+            session_id.to_string(),
+            pid,
+            rx,
+            self.screens.clone(),
+            self.app_handle.clone(),
+            self.command_tx.clone(),
+        ));
+        let exited = spawn_exit_waiter(session_id, pid, self.exit_tx.clone())?;
+        
+        self.processes.insert(session_id.to_string(), ShellProcess { pty, output_task, exited });
         if let Some(session) = self.sessions.get_mut(session_id) {
-            // TODO: Implement shell process startup
-            // This would involve:
-            // 1. Starting the shell process with proper environment
-            // 2. Setting up stdin/stdout/stderr pipes
-            // 3. Storing the process ID
-            // 4. Starting background task to read output
-            
-            info!("Starting shell process for session: {}", session_id);
-            // session.process_id = Some(process_id);
-        } else {
-            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
+            session.process_id = Some(pid);
         }
         
+        info!("Shell process {} started for session: {}", pid, session_id);
         Ok(())
     }
 
     pub async fn stop_shell_process(&mut self, session_id: &str) -> Result<()> {
-        if let Some(session) = self.sessions.get_mut(session_id) {
-            if let Some(pid) = session.process_id {
-                self.terminate_process(pid).await?;
-                session.process_id = None;
+        match self.sessions.get_mut(session_id) {
+            Some(session) => session.process_id = None,
+            None => return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id))),
+        }
+        
+        if let Some(process) = self.processes.remove(session_id) {
+            process.terminate().await;
+        }
+        
+        // A restarted shell continues in the directory the old one was in
+        let live_cwd = self.screens.read().await
+            .get(session_id)
+            .and_then(|screen| screen.working_directory.clone());
+        if let (Some(session), Some(path)) = (self.sessions.get_mut(session_id), live_cwd) {
+            session.working_directory = path;
+        }
+        
+        info!("Stopped shell process for session: {}", session_id);
+        Ok(())
+    }
+}
+
+fn search_screen(
+    session_id: &str,
+    screen: Option<&SessionScreen>,
+    pattern: &str,
+    regex: &regex::Regex,
+    options: &SearchOptions,
+) -> SearchResult {
+    match screen {
+        // An empty pattern matches nothing rather than everything, so
+        // incremental search can start from a blank input
+        Some(screen) if !pattern.is_empty() => {
+            let range = screen.emulator.transcript_range(options.from_line, options.to_line);
+            search::search_lines(session_id, range.lines.iter(), regex, options.max_results)
+        }
+        _ => SearchResult {
+            session_id: session_id.to_string(),
+            matches: Vec::new(),
+            truncated: false,
+        },
+    }
+}
+
+// Copy of the session with the working directory last reported by its shell
+fn with_live_cwd(session: &TerminalSession, screens: &HashMap<String, SessionScreen>) -> TerminalSession {
+    let mut session = session.clone();
+    if let Some(path) = screens.get(&session.id).and_then(|screen| screen.working_directory.clone()) {
+        session.working_directory = path;
+    }
+    session
+}
+
+// Reaps the shell on a dedicated thread. This is the only place that waits
+// on the pid, so the exit status is published for `terminate` and the
+// manager rather than collected twice.
+fn spawn_exit_waiter(
+    session_id: &str,
+    pid: u32,
+    exit_tx: mpsc::UnboundedSender<SessionExit>,
+) -> Result<watch::Receiver<Option<ProcessExit>>> {
+    let (tx, rx) = watch::channel(None);
+    let session_id = session_id.to_string();
+    std::thread::Builder::new()
+        .name(format!("pty-wait-{}", session_id))
+        .spawn(move || {
+            let exit = pty::wait_for_exit(pid);
+            let _ = tx.send(Some(exit));
+            let _ = exit_tx.send(SessionExit { session_id, pid, exit });
+        })
+        .map_err(|e| HoverShellError::Terminal(format!("Failed to spawn wait thread: {}", e)))?;
+    Ok(rx)
+}
+
+async fn wait_for_exit(exited: &mut watch::Receiver<Option<ProcessExit>>, timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, exited.wait_for(|exit| exit.is_some()))
+        .await
+        .map(|result| result.is_ok())
+        .unwrap_or(false)
+}
+
+fn spawn_pty_reader(session_id: &str, mut reader: File, tx: mpsc::Sender<Vec<u8>>) -> Result<()> {
+    std::thread::Builder::new()
+        .name(format!("pty-reader-{}", session_id))
+        .spawn(move || {
+            let mut buf = [0u8; READ_CHUNK_SIZE];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if tx.blocking_send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    // The master reports EIO once the shell has exited
+                    Err(_) => break,
+                }
+            }
+        })
+        .map_err(|e| HoverShellError::Terminal(format!("Failed to spawn pty reader: {}", e)))?;
+    
+    Ok(())
+}
+
+async fn pump_output(
+    session_id: String,
+    shell_pid: u32,
+    mut rx: mpsc::Receiver<Vec<u8>>,
+    screens: Screens,
+    app_handle: Option<AppHandle>,
+    commands: mpsc::UnboundedSender<CommandFinishedEvent>,
+) {
+    let mut carry = Vec::new();
+    let mut sequence: u64 = 0;
+    let mut last_emit: Option<Instant> = None;
+    let mut last_cwd_probe: Option<Instant> = None;
+    
+    while let Some(mut chunk) = rx.recv().await {
+        // Throttle events so bursts of output are coalesced into fewer,
+        // larger chunks instead of flooding the webview
+        if let Some(last_emit) = last_emit {
+            let elapsed = last_emit.elapsed();
+            if elapsed < EMIT_INTERVAL {
+                tokio::time::sleep(EMIT_INTERVAL - elapsed).await;
+            }
+        }
+        
+        while chunk.len() < MAX_EVENT_BYTES {
+            match rx.try_recv() {
+                Ok(more) => chunk.extend_from_slice(&more),
+                Err(_) => break,
+            }
+        }
+        
+        let data = decode_utf8(&mut carry, &chunk);
+        let mut finished = Vec::new();
+        let mut cwd_changed = None;
+        if let Some(screen) = screens.write().await.get_mut(&session_id) {
+            if let Some(recorder) = screen.recorder.as_mut() {
+                if let Err(e) = recorder.write_output(&data) {
+                    error!("Recording failed for session {}: {}", session_id, e);
+                    screen.recorder = None;
+                }
             }
             
-            info!("Stopped shell process for session: {}", session_id);
-        } else {
-            return Err(HoverShellError::Terminal(format!("Session not found: {}", session_id)));
+            for event in screen.emulator.process(&chunk) {
+                if let TerminalEvent::Osc { params, line, col } = event {
+                    finished.extend(screen.integration.handle_osc(&params, line, col, &screen.emulator));
+                }
+            }
+            
+            // Shells without integration never send OSC 7, so ask the OS
+            // when a command finishes, and otherwise at most once a second
+            let probe_due = !finished.is_empty()
+                || !matches!(last_cwd_probe, Some(probed) if probed.elapsed() < CWD_PROBE_INTERVAL);
+            let current = match screen.integration.working_directory() {
+                Some(path) => Some(path.to_string()),
+                None if probe_due => {
+                    last_cwd_probe = Some(Instant::now());
+                    foreground_working_directory(shell_pid)
+                }
+                None => None,
+            };
+            if current.is_some() && current != screen.working_directory {
+                screen.working_directory = current.clone();
+                cwd_changed = current;
+            }
         }
         
-        Ok(())
+        for block in &finished {
+            let _ = commands.send(CommandFinishedEvent {
+                session_id: session_id.clone(),
+                block: block.clone(),
+            });
+        }
+        
+        if let Some(app_handle) = &app_handle {
+            sequence += 1;
+            let event = TerminalOutputEvent {
+                session_id: session_id.clone(),
+                sequence,
+                data,
+            };
+            if let Err(e) = app_handle.emit_all(TERMINAL_OUTPUT_EVENT, event) {
+                error!("Failed to emit output for session {}: {}", session_id, e);
+            }
+            
+            for block in finished {
+                let event = CommandFinishedEvent {
+                    session_id: session_id.clone(),
+                    block,
+                };
+                if let Err(e) = app_handle.emit_all(COMMAND_FINISHED_EVENT, event) {
+                    error!("Failed to emit command block for session {}: {}", session_id, e);
+                }
+            }
+            
+            if let Some(working_directory) = cwd_changed {
+                let event = CwdChangedEvent {
+                    session_id: session_id.clone(),
+                    working_directory,
+                };
+                if let Err(e) = app_handle.emit_all(CWD_CHANGED_EVENT, event) {
+                    error!("Failed to emit cwd change for session {}: {}", session_id, e);
+                }
+            }
+        }
+        last_emit = Some(Instant::now());
+    }
+    
+    warn!("Shell output closed for session: {}", session_id);
+}
+
+async fn play_cast(
+    session_id: String,
+    header: CastHeader,
+    events: Vec<CastEvent>,
+    speed: f64,
+    screens: Screens,
+    app_handle: Option<AppHandle>,
+) {
+    let mut previous = 0.0;
+    let mut sequence: u64 = 0;
+    
+    for event in events {
+        tokio::time::sleep(recording::playback_delay(previous, event.time, speed, header.idle_time_limit)).await;
+        previous = event.time;
+        
+        match event.kind.as_str() {
+            "o" => {
+                if let Some(screen) = screens.write().await.get_mut(&session_id) {
+                    screen.emulator.process(event.data.as_bytes());
+                }
+                if let Some(app_handle) = &app_handle {
+                    sequence += 1;
+                    let output = TerminalOutputEvent {
+                        session_id: session_id.clone(),
+                        sequence,
+                        data: event.data,
+                    };
+                    if let Err(e) = app_handle.emit_all(TERMINAL_OUTPUT_EVENT, output) {
+                        error!("Failed to emit playback output for session {}: {}", session_id, e);
+                    }
+                }
+            }
+            "r" => {
+                let size = event.data.split_once('x')
+                    .and_then(|(cols, rows)| Some((cols.parse::<u16>().ok()?, rows.parse::<u16>().ok()?)))
+                    .filter(|(cols, rows)| *cols > 0 && *rows > 0);
+                if let (Some((cols, rows)), Some(screen)) = (size, screens.write().await.get_mut(&session_id)) {
+                    screen.emulator.resize(cols, rows);
+                }
+            }
+            // Input and marker events do not change the screen
+            _ => {}
+        }
     }
-}
\ No newline at end of file
+    
+    info!("Playback finished for session: {}", session_id);
+}
+
+// Working directory of the terminal's foreground process group, read from
+// /proc. Field 8 of /proc/<pid>/stat is the foreground group of the
+// process's controlling terminal, whose leader has the same pid.
+#[cfg(target_os = "linux")]
+fn foreground_working_directory(shell_pid: u32) -> Option<String> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", shell_pid)).ok()?;
+    // The command name may contain spaces, so parse after its closing paren
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let pid = fields.get(5)
+        .and_then(|tpgid| tpgid.parse::<i32>().ok())
+        .filter(|tpgid| *tpgid > 0)
+        .map(|tpgid| tpgid as u32)
+        .unwrap_or(shell_pid);
+    
+    std::fs::read_link(format!("/proc/{}/cwd", pid))
+        .or_else(|_| std::fs::read_link(format!("/proc/{}/cwd", shell_pid)))
+        .ok()
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn foreground_working_directory(_shell_pid: u32) -> Option<String> {
+    None
+}
+
+// Decodes a chunk as UTF-8, holding back a trailing incomplete sequence
+// until the rest of it arrives with the next chunk.
+fn decode_utf8(carry: &mut Vec<u8>, chunk: &[u8]) -> String {
+    carry.extend_from_slice(chunk);
+    let split = match std::str::from_utf8(carry) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => carry.len(),
+    };
+    let text = String::from_utf8_lossy(&carry[..split]).to_string();
+    carry.drain(..split);
+    text
+}
@@ -0,0 +1,1096 @@
+use super::scrollback::{OutputRange, Scrollback, ScrollbackLine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthChar;
+
+const MAX_PARAMS: usize = 32;
+const MAX_OSC_LEN: usize = 64 * 1024;
+const TAB_WIDTH: usize = 8;
+// Fills the second cell of a double-width character; never part of the text
+const WIDE_SPACER: char = '\0';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "type", content = "value")]
+pub enum Color {
+    #[default]
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CellStyle {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub style: CellStyle,
+}
+
+impl Cell {
+    fn blank(style: CellStyle) -> Self {
+        // Erased cells keep only the background (back color erase)
+        Self {
+            ch: ' ',
+            style: CellStyle { bg: style.bg, ..CellStyle::default() },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    cells: Vec<Cell>,
+    timestamp: DateTime<Utc>,
+}
+
+impl Row {
+    fn new(cols: usize, style: CellStyle) -> Self {
+        Self {
+            cells: vec![Cell::blank(style); cols],
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let text: String = self.cells.iter().map(|cell| cell.ch).filter(|&ch| ch != WIDE_SPACER).collect();
+        text.trim_end().to_string()
+    }
+
+    fn is_blank(&self) -> bool {
+        self.cells.iter().all(|cell| cell.ch == ' ')
+    }
+
+    fn runs(&self) -> Vec<StyledRun> {
+        let mut runs: Vec<StyledRun> = Vec::new();
+        let end = self
+            .cells
+            .iter()
+            .rposition(|cell| cell.ch != ' ' || cell.style != CellStyle::default())
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+
+        for cell in self.cells[..end].iter().filter(|cell| cell.ch != WIDE_SPACER) {
+            match runs.last_mut() {
+                Some(run) if run.style == cell.style => run.text.push(cell.ch),
+                _ => runs.push(StyledRun {
+                    text: cell.ch.to_string(),
+                    style: cell.style,
+                }),
+            }
+        }
+        runs
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    row: usize,
+    col: usize,
+    style: CellStyle,
+    wrap_pending: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyledRun {
+    pub text: String,
+    pub style: CellStyle,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreenSnapshot {
+    pub cols: usize,
+    pub rows: usize,
+    pub cursor_row: usize,
+    pub cursor_col: usize,
+    pub cursor_visible: bool,
+    pub alternate_screen: bool,
+    pub title: String,
+    pub lines: Vec<Vec<StyledRun>>,
+}
+
+// Events the emulator cannot act on by itself and hands back to its owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalEvent {
+    Bell,
+    TitleChanged(String),
+    // Any OSC sequence other than the window title, split on ';', with the
+    // transcript line and column of the cursor when it was received
+    Osc { params: Vec<String>, line: u64, col: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    Csi,
+    CsiIgnore,
+    Osc,
+    OscEscape,
+    // DCS, SOS, PM and APC strings are consumed and discarded
+    Ignored,
+    IgnoredEscape,
+}
+
+// VT100/xterm emulator keeping a cell grid for the visible screen and the
+// session's scrollback for lines that scroll off the primary screen.
+pub struct Emulator {
+    cols: usize,
+    rows: usize,
+    grid: Vec<Row>,
+    saved_primary: Option<Vec<Row>>,
+    cursor: Cursor,
+    saved_cursor: Option<Cursor>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    autowrap: bool,
+    cursor_visible: bool,
+    title: String,
+    scrollback: Scrollback,
+
+    state: State,
+    params: Vec<u16>,
+    current_param: Option<u32>,
+    private_marker: Option<u8>,
+    intermediates: Vec<u8>,
+    osc: Vec<u8>,
+    utf8: Vec<u8>,
+    events: Vec<TerminalEvent>,
+}
+
+impl Emulator {
+    pub fn new(cols: u16, rows: u16, scrollback_lines: usize) -> Self {
+        let cols = (cols as usize).max(1);
+        let rows = (rows as usize).max(1);
+
+        Self {
+            cols,
+            rows,
+            grid: (0..rows).map(|_| Row::new(cols, CellStyle::default())).collect(),
+            saved_primary: None,
+            cursor: Cursor::default(),
+            saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            autowrap: true,
+            cursor_visible: true,
+            title: String::new(),
+            scrollback: Scrollback::new(scrollback_lines),
+            state: State::Ground,
+            params: Vec::new(),
+            current_param: None,
+            private_marker: None,
+            intermediates: Vec::new(),
+            osc: Vec::new(),
+            utf8: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn process(&mut self, data: &[u8]) -> Vec<TerminalEvent> {
+        for &byte in data {
+            self.advance(byte);
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.saved_primary.is_some()
+    }
+
+    pub fn scrollback(&self) -> &Scrollback {
+        &self.scrollback
+    }
+
+    pub fn scrollback_mut(&mut self) -> &mut Scrollback {
+        &mut self.scrollback
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        let cols = (cols as usize).max(1);
+        let rows = (rows as usize).max(1);
+        if cols == self.cols && rows == self.rows {
+            return;
+        }
+
+        let alternate = self.is_alternate_screen();
+        let style = self.cursor.style;
+
+        // Shrinking drops rows below the cursor first, then moves rows above
+        // it into history so the cursor line stays visible. Growing adds
+        // blank rows at the bottom.
+        while self.grid.len() > rows {
+            if self.grid.len() - 1 > self.cursor.row {
+                self.grid.pop();
+            } else {
+                let row = self.grid.remove(0);
+                if !alternate {
+                    self.push_history(row);
+                }
+                self.cursor.row -= 1;
+            }
+        }
+        while self.grid.len() < rows {
+            self.grid.push(Row::new(cols, style));
+        }
+        for row in self.grid.iter_mut() {
+            row.cells.resize(cols, Cell::blank(CellStyle::default()));
+        }
+
+        if let Some(primary) = self.saved_primary.as_mut() {
+            primary.truncate(rows);
+            while primary.len() < rows {
+                primary.push(Row::new(cols, CellStyle::default()));
+            }
+            for row in primary.iter_mut() {
+                row.cells.resize(cols, Cell::blank(CellStyle::default()));
+            }
+        }
+
+        self.cols = cols;
+        self.rows = rows;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.col = self.cursor.col.min(cols - 1);
+        self.cursor.wrap_pending = false;
+    }
+
+    pub fn snapshot(&self) -> ScreenSnapshot {
+        ScreenSnapshot {
+            cols: self.cols,
+            rows: self.rows,
+            cursor_row: self.cursor.row,
+            cursor_col: self.cursor.col,
+            cursor_visible: self.cursor_visible,
+            alternate_screen: self.is_alternate_screen(),
+            title: self.title.clone(),
+            lines: self.grid.iter().map(|row| row.runs()).collect(),
+        }
+    }
+
+    pub fn screen_text(&self) -> Vec<String> {
+        self.grid.iter().map(|row| row.text()).collect()
+    }
+
+    // The primary screen's rows up to the last one in use. While a full-screen
+    // program runs on the alternate screen the saved primary rows are used,
+    // so the transcript is not polluted by TUI redraws.
+    fn transcript_rows(&self) -> &[Row] {
+        let rows = self.saved_primary.as_deref().unwrap_or(&self.grid);
+        let cursor_row = if self.saved_primary.is_some() { 0 } else { self.cursor.row };
+        let last_used = rows.iter().rposition(|row| !row.is_blank()).unwrap_or(0);
+        &rows[..=last_used.max(cursor_row).min(rows.len() - 1)]
+    }
+
+    // Absolute-indexed transcript: history lines followed by the rows of the
+    // primary screen, which continue the history's numbering.
+    pub fn transcript_range(&self, from: Option<u64>, to: Option<u64>) -> OutputRange {
+        let first_index = self.scrollback.first_index();
+        let screen_start = self.scrollback.next_index();
+        let rows = self.transcript_rows();
+        let next_index = screen_start + rows.len() as u64;
+
+        let from = from.unwrap_or(first_index).clamp(first_index, next_index);
+        let to = to.unwrap_or(next_index).clamp(from, next_index);
+
+        let mut lines = self.scrollback.range(Some(from), Some(to.min(screen_start))).lines;
+        for index in from.max(screen_start)..to {
+            let row = &rows[(index - screen_start) as usize];
+            lines.push(ScrollbackLine {
+                index,
+                text: row.text(),
+                timestamp: row.timestamp,
+            });
+        }
+
+        OutputRange {
+            first_index,
+            next_index,
+            lines,
+        }
+    }
+
+    // Transcript index of the cursor's line on the primary screen
+    pub fn cursor_line(&self) -> u64 {
+        let row = if self.is_alternate_screen() { 0 } else { self.cursor.row };
+        self.scrollback.next_index() + row as u64
+    }
+
+    pub fn line_text(&self, index: u64) -> Option<String> {
+        self.transcript_range(Some(index), Some(index + 1))
+            .lines
+            .pop()
+            .map(|line| line.text)
+    }
+
+    pub fn transcript_text(&self) -> String {
+        self.transcript_range(None, None)
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn clear_history(&mut self) {
+        self.scrollback.clear();
+    }
+
+    fn advance(&mut self, byte: u8) {
+        match self.state {
+            State::Osc => return self.osc_byte(byte),
+            State::OscEscape => {
+                if byte == b'\\' {
+                    self.dispatch_osc();
+                    self.state = State::Ground;
+                } else {
+                    self.dispatch_osc();
+                    self.state = State::Escape;
+                    self.advance(byte);
+                }
+                return;
+            }
+            State::Ignored => {
+                match byte {
+                    0x1b => self.state = State::IgnoredEscape,
+                    0x07 | 0x18 | 0x1a => self.state = State::Ground,
+                    _ => {}
+                }
+                return;
+            }
+            State::IgnoredEscape => {
+                self.state = if byte == b'\\' { State::Ground } else { State::Ignored };
+                return;
+            }
+            _ => {}
+        }
+
+        // C0 controls are executed in every other state, even mid-sequence
+        match byte {
+            0x1b => {
+                self.utf8.clear();
+                self.enter_escape();
+                return;
+            }
+            0x18 | 0x1a => {
+                self.state = State::Ground;
+                return;
+            }
+            0x00..=0x1f => {
+                self.execute(byte);
+                return;
+            }
+            _ => {}
+        }
+
+        match self.state {
+            State::Ground => self.ground_byte(byte),
+            State::Escape => self.escape_byte(byte),
+            State::EscapeIntermediate => {
+                // Charset designations and similar; the final byte ends them
+                if (0x30..=0x7e).contains(&byte) {
+                    self.state = State::Ground;
+                }
+            }
+            State::Csi => self.csi_byte(byte),
+            State::CsiIgnore => {
+                if (0x40..=0x7e).contains(&byte) {
+                    self.state = State::Ground;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn enter_escape(&mut self) {
+        self.state = State::Escape;
+        self.params.clear();
+        self.current_param = None;
+        self.private_marker = None;
+        self.intermediates.clear();
+    }
+
+    fn ground_byte(&mut self, byte: u8) {
+        if byte < 0x80 {
+            self.utf8.clear();
+            if byte != 0x7f {
+                self.print(byte as char);
+            }
+            return;
+        }
+
+        if byte & 0xc0 != 0x80 {
+            // A new lead byte abandons any unfinished sequence
+            if !self.utf8.is_empty() {
+                self.utf8.clear();
+                self.print(char::REPLACEMENT_CHARACTER);
+            }
+        } else if self.utf8.is_empty() {
+            self.print(char::REPLACEMENT_CHARACTER);
+            return;
+        }
+
+        self.utf8.push(byte);
+        let expected = match self.utf8[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+
+        if self.utf8.len() >= expected {
+            let c = std::str::from_utf8(&self.utf8)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.utf8.clear();
+            self.print(c);
+        }
+    }
+
+    fn escape_byte(&mut self, byte: u8) {
+        self.state = State::Ground;
+        match byte {
+            b'[' => self.state = State::Csi,
+            b']' => {
+                self.osc.clear();
+                self.state = State::Osc;
+            }
+            b'P' | b'X' | b'^' | b'_' => self.state = State::Ignored,
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => self.reset(),
+            0x20..=0x2f => {
+                self.intermediates.push(byte);
+                self.state = State::EscapeIntermediate;
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_byte(&mut self, byte: u8) {
+        match byte {
+            b'0'..=b'9' => {
+                let digit = (byte - b'0') as u32;
+                let value = self.current_param.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                self.current_param = Some(value.min(u16::MAX as u32));
+            }
+            b';' | b':' => {
+                self.finish_param();
+            }
+            b'<'..=b'?' => {
+                if self.params.is_empty() && self.current_param.is_none() && self.private_marker.is_none() {
+                    self.private_marker = Some(byte);
+                } else {
+                    self.state = State::CsiIgnore;
+                }
+            }
+            0x20..=0x2f => self.intermediates.push(byte),
+            0x40..=0x7e => {
+                if self.current_param.is_some() || !self.params.is_empty() {
+                    self.finish_param();
+                }
+                self.state = State::Ground;
+                self.dispatch_csi(byte);
+            }
+            _ => self.state = State::CsiIgnore,
+        }
+    }
+
+    fn finish_param(&mut self) {
+        if self.params.len() < MAX_PARAMS {
+            self.params.push(self.current_param.unwrap_or(0) as u16);
+        }
+        self.current_param = None;
+    }
+
+    fn osc_byte(&mut self, byte: u8) {
+        match byte {
+            0x07 => {
+                self.dispatch_osc();
+                self.state = State::Ground;
+            }
+            0x1b => self.state = State::OscEscape,
+            0x18 | 0x1a => self.state = State::Ground,
+            _ => {
+                if self.osc.len() < MAX_OSC_LEN {
+                    self.osc.push(byte);
+                }
+            }
+        }
+    }
+
+    fn dispatch_osc(&mut self) {
+        let text = String::from_utf8_lossy(&self.osc).to_string();
+        self.osc.clear();
+
+        let parts: Vec<String> = text.split(';').map(|part| part.to_string()).collect();
+        match parts[0].as_str() {
+            "0" | "2" => {
+                self.title = parts[1..].join(";");
+                self.events.push(TerminalEvent::TitleChanged(self.title.clone()));
+            }
+            "1" => {}
+            _ => self.events.push(TerminalEvent::Osc {
+                params: parts,
+                line: self.cursor_line(),
+                col: self.cursor.col,
+            }),
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            0x07 => self.events.push(TerminalEvent::Bell),
+            0x08 => {
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+                self.cursor.wrap_pending = false;
+            }
+            0x09 => {
+                let next = (self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor.col = next.min(self.cols - 1);
+                self.cursor.wrap_pending = false;
+            }
+            0x0a..=0x0c => self.linefeed(),
+            0x0d => {
+                self.cursor.col = 0;
+                self.cursor.wrap_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params.get(index) {
+            Some(&0) | None => default,
+            Some(&value) => value,
+        }
+    }
+
+    fn dispatch_csi(&mut self, action: u8) {
+        if let Some(marker) = self.private_marker {
+            if marker == b'?' && (action == b'h' || action == b'l') {
+                let enable = action == b'h';
+                for mode in self.params.clone() {
+                    self.set_private_mode(mode, enable);
+                }
+            }
+            return;
+        }
+        if !self.intermediates.is_empty() {
+            return;
+        }
+
+        let n = self.param(0, 1) as usize;
+        match action {
+            b'A' => self.move_cursor_to(self.cursor.row.saturating_sub(n), self.cursor.col),
+            b'B' | b'e' => self.move_cursor_to(self.cursor.row + n, self.cursor.col),
+            b'C' | b'a' => self.move_cursor_to(self.cursor.row, self.cursor.col + n),
+            b'D' => self.move_cursor_to(self.cursor.row, self.cursor.col.saturating_sub(n)),
+            b'E' => self.move_cursor_to(self.cursor.row + n, 0),
+            b'F' => self.move_cursor_to(self.cursor.row.saturating_sub(n), 0),
+            b'G' | b'`' => self.move_cursor_to(self.cursor.row, n - 1),
+            b'd' => self.move_cursor_to(n - 1, self.cursor.col),
+            b'H' | b'f' => {
+                let row = self.param(0, 1) as usize - 1;
+                let col = self.param(1, 1) as usize - 1;
+                self.move_cursor_to(row, col);
+            }
+            b'J' => self.erase_display(self.params.first().copied().unwrap_or(0)),
+            b'K' => self.erase_line(self.params.first().copied().unwrap_or(0)),
+            b'L' => self.insert_lines(n),
+            b'M' => self.delete_lines(n),
+            b'@' => self.insert_chars(n),
+            b'P' => self.delete_chars(n),
+            b'X' => self.erase_chars(n),
+            b'S' => self.scroll_up(n),
+            b'T' => self.scroll_down(n),
+            b'm' => self.select_graphic_rendition(),
+            b'r' => {
+                let top = self.param(0, 1) as usize - 1;
+                let bottom = (self.param(1, self.rows as u16) as usize).min(self.rows) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_cursor_to(0, 0);
+                }
+            }
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enable: bool) {
+        match mode {
+            7 => self.autowrap = enable,
+            25 => self.cursor_visible = enable,
+            47 | 1047 => self.set_alternate_screen(enable),
+            1049 => {
+                if enable {
+                    self.save_cursor();
+                    self.set_alternate_screen(true);
+                } else {
+                    self.set_alternate_screen(false);
+                    self.restore_cursor();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_alternate_screen(&mut self, enable: bool) {
+        if enable && self.saved_primary.is_none() {
+            let blank = (0..self.rows).map(|_| Row::new(self.cols, CellStyle::default())).collect();
+            self.saved_primary = Some(std::mem::replace(&mut self.grid, blank));
+        } else if !enable {
+            if let Some(primary) = self.saved_primary.take() {
+                self.grid = primary;
+            }
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+    }
+
+    fn select_graphic_rendition(&mut self) {
+        if self.params.is_empty() {
+            self.cursor.style = CellStyle::default();
+            return;
+        }
+
+        let params = self.params.clone();
+        let style = &mut self.cursor.style;
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => *style = CellStyle::default(),
+                1 => style.bold = true,
+                2 => style.dim = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                5 | 6 => style.blink = true,
+                7 => style.inverse = true,
+                8 => style.hidden = true,
+                9 => style.strikethrough = true,
+                21 | 22 => {
+                    style.bold = false;
+                    style.dim = false;
+                }
+                23 => style.italic = false,
+                24 => style.underline = false,
+                25 => style.blink = false,
+                27 => style.inverse = false,
+                28 => style.hidden = false,
+                29 => style.strikethrough = false,
+                code @ 30..=37 => style.fg = Color::Indexed((code - 30) as u8),
+                39 => style.fg = Color::Default,
+                code @ 40..=47 => style.bg = Color::Indexed((code - 40) as u8),
+                49 => style.bg = Color::Default,
+                code @ 90..=97 => style.fg = Color::Indexed((code - 90 + 8) as u8),
+                code @ 100..=107 => style.bg = Color::Indexed((code - 100 + 8) as u8),
+                code @ (38 | 48) => {
+                    let (color, consumed) = parse_extended_color(&params[i + 1..]);
+                    if let Some(color) = color {
+                        if code == 38 {
+                            style.fg = color;
+                        } else {
+                            style.bg = color;
+                        }
+                    }
+                    i += consumed;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        // East Asian wide characters and most emoji take two cells
+        let width = if c.width() == Some(2) && self.cols >= 2 { 2 } else { 1 };
+
+        if self.cursor.wrap_pending {
+            self.cursor.col = 0;
+            self.cursor.wrap_pending = false;
+            self.linefeed();
+        }
+
+        // A wide character that doesn't fit wraps whole, leaving the last
+        // cell blank
+        if self.cursor.col + width > self.cols {
+            if self.autowrap {
+                let blank = Cell::blank(self.cursor.style);
+                self.clear_wide_at(self.cursor.col);
+                self.grid[self.cursor.row].cells[self.cursor.col] = blank;
+                self.cursor.col = 0;
+                self.linefeed();
+            } else {
+                self.cursor.col = self.cols - width;
+            }
+        }
+
+        let col = self.cursor.col;
+        for offset in 0..width {
+            self.clear_wide_at(col + offset);
+        }
+        let style = self.cursor.style;
+        let row = &mut self.grid[self.cursor.row];
+        row.cells[col] = Cell { ch: c, style };
+        if width == 2 {
+            row.cells[col + 1] = Cell { ch: WIDE_SPACER, style };
+        }
+        row.timestamp = Utc::now();
+
+        if col + width < self.cols {
+            self.cursor.col = col + width;
+        } else {
+            self.cursor.col = self.cols - 1;
+            self.cursor.wrap_pending = self.autowrap;
+        }
+    }
+
+    // Overwriting either half of a wide character blanks the other half
+    fn clear_wide_at(&mut self, col: usize) {
+        let blank = Cell::blank(CellStyle::default());
+        let cells = &mut self.grid[self.cursor.row].cells;
+        if cells[col].ch == WIDE_SPACER && col > 0 {
+            cells[col - 1] = blank;
+            cells[col] = blank;
+        } else if cells.get(col + 1).is_some_and(|next| next.ch == WIDE_SPACER) {
+            cells[col + 1] = blank;
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.cursor.wrap_pending = false;
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.cursor.wrap_pending = false;
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.scroll_bottom - self.scroll_top + 1);
+        // Only full-screen scrolls of the primary screen feed the history
+        let keep_history = self.scroll_top == 0 && !self.is_alternate_screen();
+
+        for _ in 0..n {
+            let row = self.grid.remove(self.scroll_top);
+            if keep_history {
+                self.push_history(row);
+            }
+            self.grid.insert(self.scroll_bottom, Row::new(self.cols, self.cursor.style));
+        }
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.scroll_bottom - self.scroll_top + 1);
+        for _ in 0..n {
+            self.grid.remove(self.scroll_bottom);
+            self.grid.insert(self.scroll_top, Row::new(self.cols, self.cursor.style));
+        }
+    }
+
+    fn push_history(&mut self, row: Row) {
+        self.scrollback.push_line_at(&row.text(), row.timestamp);
+    }
+
+    fn move_cursor_to(&mut self, row: usize, col: usize) {
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.col = col.min(self.cols - 1);
+        self.cursor.wrap_pending = false;
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        let blank = Cell::blank(self.cursor.style);
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        match mode {
+            0 => {
+                self.grid[row].cells[col..].fill(blank);
+                for r in &mut self.grid[row + 1..] {
+                    r.cells.fill(blank);
+                }
+            }
+            1 => {
+                self.grid[row].cells[..=col].fill(blank);
+                for r in &mut self.grid[..row] {
+                    r.cells.fill(blank);
+                }
+            }
+            2 | 3 => {
+                for r in self.grid.iter_mut() {
+                    r.cells.fill(blank);
+                }
+                if mode == 3 {
+                    self.scrollback.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode: u16) {
+        let blank = Cell::blank(self.cursor.style);
+        let col = self.cursor.col;
+        let cells = &mut self.grid[self.cursor.row].cells;
+        match mode {
+            0 => cells[col..].fill(blank),
+            1 => cells[..=col].fill(blank),
+            2 => cells.fill(blank),
+            _ => {}
+        }
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if self.cursor.row < self.scroll_top || self.cursor.row > self.scroll_bottom {
+            return;
+        }
+        let n = n.min(self.scroll_bottom - self.cursor.row + 1);
+        for _ in 0..n {
+            self.grid.remove(self.scroll_bottom);
+            self.grid.insert(self.cursor.row, Row::new(self.cols, self.cursor.style));
+        }
+        self.cursor.col = 0;
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if self.cursor.row < self.scroll_top || self.cursor.row > self.scroll_bottom {
+            return;
+        }
+        let n = n.min(self.scroll_bottom - self.cursor.row + 1);
+        for _ in 0..n {
+            self.grid.remove(self.cursor.row);
+            self.grid.insert(self.scroll_bottom, Row::new(self.cols, self.cursor.style));
+        }
+        self.cursor.col = 0;
+    }
+
+    fn insert_chars(&mut self, n: usize) {
+        let blank = Cell::blank(self.cursor.style);
+        let col = self.cursor.col;
+        let cells = &mut self.grid[self.cursor.row].cells;
+        let n = n.min(cells.len() - col);
+        cells[col..].rotate_right(n);
+        cells[col..col + n].fill(blank);
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let blank = Cell::blank(self.cursor.style);
+        let col = self.cursor.col;
+        let cells = &mut self.grid[self.cursor.row].cells;
+        let n = n.min(cells.len() - col);
+        cells[col..].rotate_left(n);
+        let len = cells.len();
+        cells[len - n..].fill(blank);
+    }
+
+    fn erase_chars(&mut self, n: usize) {
+        let blank = Cell::blank(self.cursor.style);
+        let col = self.cursor.col;
+        let cells = &mut self.grid[self.cursor.row].cells;
+        let end = (col + n).min(cells.len());
+        cells[col..end].fill(blank);
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.cursor);
+    }
+
+    fn restore_cursor(&mut self) {
+        if let Some(cursor) = self.saved_cursor {
+            self.cursor = cursor;
+            self.move_cursor_to(cursor.row, cursor.col);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.saved_primary = None;
+        self.grid = (0..self.rows).map(|_| Row::new(self.cols, CellStyle::default())).collect();
+        self.cursor = Cursor::default();
+        self.saved_cursor = None;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.autowrap = true;
+        self.cursor_visible = true;
+    }
+}
+
+// Parses the arguments following SGR 38/48: `5;n` for the 256-color palette
+// or `2;r;g;b` for truecolor. Returns the color and how many params it used.
+fn parse_extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    match params.first() {
+        Some(5) if params.len() >= 2 => (Some(Color::Indexed(params[1].min(255) as u8)), 2),
+        Some(2) if params.len() >= 4 => (
+            Some(Color::Rgb(
+                params[1].min(255) as u8,
+                params[2].min(255) as u8,
+                params[3].min(255) as u8,
+            )),
+            4,
+        ),
+        _ => (None, params.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emulator(cols: u16, rows: u16, input: &str) -> Emulator {
+        let mut emulator = Emulator::new(cols, rows, 100);
+        emulator.process(input.as_bytes());
+        emulator
+    }
+
+    fn cursor(emulator: &Emulator) -> (usize, usize) {
+        (emulator.cursor.row, emulator.cursor.col)
+    }
+
+    #[test]
+    fn moves_cursor_within_bounds() {
+        let mut emulator = emulator(10, 5, "\x1b[3;4H");
+        assert_eq!(cursor(&emulator), (2, 3));
+
+        emulator.process(b"\x1b[2A\x1b[5C");
+        assert_eq!(cursor(&emulator), (0, 8));
+
+        // Moves past the edges stop at the last row and column
+        emulator.process(b"\x1b[20B\x1b[20C");
+        assert_eq!(cursor(&emulator), (4, 9));
+        emulator.process(b"\x1b[99;99H\x1b[20D");
+        assert_eq!(cursor(&emulator), (4, 0));
+
+        emulator.process(b"ab\rc\x1b[G");
+        assert_eq!(emulator.screen_text()[4], "cb");
+        assert_eq!(cursor(&emulator), (4, 0));
+    }
+
+    #[test]
+    fn wraps_at_last_column() {
+        let mut emulator = emulator(4, 3, "abcd");
+        // The cursor waits on the last column until the next character
+        assert_eq!(cursor(&emulator), (0, 3));
+        emulator.process(b"e");
+        assert_eq!(emulator.screen_text(), vec!["abcd", "e", ""]);
+
+        emulator.process(b"\x1b[?7l\x1b[3;1Hwxyz!");
+        assert_eq!(emulator.screen_text()[2], "wxy!");
+    }
+
+    #[test]
+    fn scrolls_only_inside_region() {
+        let mut emulator = emulator(5, 5, "1\r\n2\r\n3\r\n4\r\n5");
+        emulator.process(b"\x1b[2;4r");
+        assert_eq!(cursor(&emulator), (0, 0));
+
+        // A linefeed on the bottom margin scrolls rows 2-4 and leaves the
+        // rows outside the region alone
+        emulator.process(b"\x1b[4;1H\nx");
+        assert_eq!(emulator.screen_text(), vec!["1", "3", "4", "x", "5"]);
+        // Lines scrolled out of a partial region don't reach the history
+        assert_eq!(emulator.scrollback().next_index(), 0);
+
+        // A reverse index on the top margin scrolls the region down
+        emulator.process(b"\x1b[2;1H\x1bMy");
+        assert_eq!(emulator.screen_text(), vec!["1", "y", "3", "4", "5"]);
+
+        // Outside the region the cursor moves freely and stops at the edge
+        emulator.process(b"\x1b[5;1H\n\nz");
+        assert_eq!(emulator.screen_text(), vec!["1", "y", "3", "4", "z"]);
+    }
+
+    #[test]
+    fn ignores_invalid_scroll_region() {
+        let mut emulator = emulator(5, 3, "\x1b[3;2r");
+        assert_eq!((emulator.scroll_top, emulator.scroll_bottom), (0, 2));
+
+        emulator.process(b"\x1b[1;99r");
+        assert_eq!((emulator.scroll_top, emulator.scroll_bottom), (0, 2));
+    }
+
+    #[test]
+    fn alternate_screen_keeps_primary() {
+        let mut emulator = emulator(10, 3, "$ vim\r\n");
+        assert_eq!(cursor(&emulator), (1, 0));
+
+        emulator.process(b"\x1b[?1049h\x1b[2;3Hediting");
+        assert!(emulator.is_alternate_screen());
+        assert_eq!(emulator.screen_text(), vec!["", "  editing", ""]);
+        // The transcript shows the primary screen, not the full-screen app
+        assert_eq!(emulator.transcript_text(), "$ vim");
+
+        emulator.process(b"\x1b[?1049l");
+        assert!(!emulator.is_alternate_screen());
+        assert_eq!(emulator.screen_text(), vec!["$ vim", "", ""]);
+        assert_eq!(cursor(&emulator), (1, 0));
+
+        // Leaving twice is harmless
+        emulator.process(b"\x1b[?1049l");
+        assert_eq!(emulator.screen_text()[0], "$ vim");
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let mut emulator = emulator(10, 3, "日本語 ok");
+        assert_eq!(emulator.screen_text()[0], "日本語 ok");
+        assert_eq!(cursor(&emulator), (0, 9));
+
+        emulator.process("\r\n🦀x".as_bytes());
+        assert_eq!(emulator.screen_text()[1], "🦀x");
+        assert_eq!(cursor(&emulator), (1, 3));
+        assert_eq!(emulator.snapshot().lines[1][0].text, "🦀x");
+    }
+
+    #[test]
+    fn wide_character_wraps_whole() {
+        let emulator = emulator(5, 3, "abcd漢字");
+        assert_eq!(emulator.screen_text(), vec!["abcd", "漢字", ""]);
+        assert_eq!(cursor(&emulator), (1, 4));
+    }
+
+    #[test]
+    fn overwriting_half_a_wide_character_blanks_it() {
+        let mut emulator = emulator(6, 2, "漢字");
+        emulator.process(b"\x1b[1;2Hx");
+        assert_eq!(emulator.screen_text()[0], " x字");
+
+        emulator.process(b"\x1b[1;3Hy");
+        assert_eq!(emulator.screen_text()[0], " xy");
+    }
+}
@@ -0,0 +1,394 @@
+use serde::{Deserialize, Serialize};
+
+const MIN_RATIO: f32 = 0.1;
+const MAX_RATIO: f32 = 0.9;
+
+// `Horizontal` places the two children side by side, `Vertical` stacks
+// them top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LayoutNode {
+    Pane {
+        session_id: String,
+    },
+    Split {
+        id: String,
+        direction: SplitDirection,
+        // Share of the space given to `first`
+        ratio: f32,
+        first: Box<LayoutNode>,
+        second: Box<LayoutNode>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PaneRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LayoutNode {
+    fn contains(&self, session_id: &str) -> bool {
+        match self {
+            LayoutNode::Pane { session_id: id } => id == session_id,
+            LayoutNode::Split { first, second, .. } => {
+                first.contains(session_id) || second.contains(session_id)
+            }
+        }
+    }
+
+    fn collect_sessions(&self, sessions: &mut Vec<String>) {
+        match self {
+            LayoutNode::Pane { session_id } => sessions.push(session_id.clone()),
+            LayoutNode::Split { first, second, .. } => {
+                first.collect_sessions(sessions);
+                second.collect_sessions(sessions);
+            }
+        }
+    }
+
+    fn split(&mut self, session_id: &str, new_session_id: &str, direction: SplitDirection) -> bool {
+        match self {
+            LayoutNode::Pane { session_id: id } if id == session_id => {
+                let existing = LayoutNode::Pane { session_id: id.clone() };
+                *self = LayoutNode::Split {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    direction,
+                    ratio: 0.5,
+                    first: Box::new(existing),
+                    second: Box::new(LayoutNode::Pane {
+                        session_id: new_session_id.to_string(),
+                    }),
+                };
+                true
+            }
+            LayoutNode::Pane { .. } => false,
+            LayoutNode::Split { first, second, .. } => {
+                first.split(session_id, new_session_id, direction)
+                    || second.split(session_id, new_session_id, direction)
+            }
+        }
+    }
+
+    // Returns the node that replaces this one once the pane is gone, or
+    // `None` if this node was the pane itself
+    fn remove(self, session_id: &str) -> Option<LayoutNode> {
+        match self {
+            LayoutNode::Pane { session_id: ref id } if id == session_id => None,
+            LayoutNode::Pane { .. } => Some(self),
+            LayoutNode::Split { id, direction, ratio, first, second } => {
+                match (first.remove(session_id), second.remove(session_id)) {
+                    (Some(first), Some(second)) => Some(LayoutNode::Split {
+                        id,
+                        direction,
+                        ratio,
+                        first: Box::new(first),
+                        second: Box::new(second),
+                    }),
+                    // The sibling takes over the split's space
+                    (Some(remaining), None) | (None, Some(remaining)) => Some(remaining),
+                    (None, None) => None,
+                }
+            }
+        }
+    }
+
+    fn set_ratio(&mut self, split_id: &str, new_ratio: f32) -> bool {
+        match self {
+            LayoutNode::Pane { .. } => false,
+            LayoutNode::Split { id, ratio, first, second, .. } => {
+                if id == split_id {
+                    *ratio = new_ratio.clamp(MIN_RATIO, MAX_RATIO);
+                    true
+                } else {
+                    first.set_ratio(split_id, new_ratio) || second.set_ratio(split_id, new_ratio)
+                }
+            }
+        }
+    }
+
+    fn collect_rects(&self, rect: PaneRect, rects: &mut Vec<(String, PaneRect)>) {
+        match self {
+            LayoutNode::Pane { session_id } => rects.push((session_id.clone(), rect)),
+            LayoutNode::Split { direction, ratio, first, second, .. } => {
+                let (first_rect, second_rect) = match direction {
+                    SplitDirection::Horizontal => {
+                        let width = rect.width * ratio;
+                        (
+                            PaneRect { width, ..rect },
+                            PaneRect { x: rect.x + width, width: rect.width - width, ..rect },
+                        )
+                    }
+                    SplitDirection::Vertical => {
+                        let height = rect.height * ratio;
+                        (
+                            PaneRect { height, ..rect },
+                            PaneRect { y: rect.y + height, height: rect.height - height, ..rect },
+                        )
+                    }
+                };
+                first.collect_rects(first_rect, rects);
+                second.collect_rects(second_rect, rects);
+            }
+        }
+    }
+}
+
+// The panes of one tab. Each pane shows exactly one terminal session, so
+// panes are addressed by their session id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaneLayout {
+    pub id: String,
+    pub root: LayoutNode,
+    pub focused_session: String,
+}
+
+impl PaneLayout {
+    pub fn new(session_id: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            root: LayoutNode::Pane {
+                session_id: session_id.to_string(),
+            },
+            focused_session: session_id.to_string(),
+        }
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.root.contains(session_id)
+    }
+
+    pub fn sessions(&self) -> Vec<String> {
+        let mut sessions = Vec::new();
+        self.root.collect_sessions(&mut sessions);
+        sessions
+    }
+
+    pub fn split(&mut self, session_id: &str, new_session_id: &str, direction: SplitDirection) -> bool {
+        if !self.root.split(session_id, new_session_id, direction) {
+            return false;
+        }
+        self.focused_session = new_session_id.to_string();
+        true
+    }
+
+    // Removes a pane. Returns false when it was the last one and the layout
+    // should be dropped.
+    pub fn remove(&mut self, session_id: &str) -> bool {
+        let root = std::mem::replace(
+            &mut self.root,
+            LayoutNode::Pane { session_id: String::new() },
+        );
+        match root.remove(session_id) {
+            Some(root) => {
+                self.root = root;
+                if self.focused_session == session_id {
+                    self.focused_session = self.sessions().into_iter().next().unwrap_or_default();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_ratio(&mut self, split_id: &str, ratio: f32) -> bool {
+        self.root.set_ratio(split_id, ratio)
+    }
+
+    // Pane positions within a unit square
+    pub fn rects(&self) -> Vec<(String, PaneRect)> {
+        let mut rects = Vec::new();
+        let full = PaneRect { x: 0.0, y: 0.0, width: 1.0, height: 1.0 };
+        self.root.collect_rects(full, &mut rects);
+        rects
+    }
+
+    // The closest pane in a direction that overlaps the given pane on the
+    // other axis
+    pub fn neighbor(&self, session_id: &str, direction: FocusDirection) -> Option<String> {
+        const EPSILON: f32 = 1e-4;
+
+        let rects = self.rects();
+        let (_, from) = rects.iter().find(|(id, _)| id == session_id)?;
+        let overlaps = |a0: f32, a1: f32, b0: f32, b1: f32| a0 < b1 - EPSILON && b0 < a1 - EPSILON;
+
+        rects.iter()
+            .filter(|(id, _)| id != session_id)
+            .filter_map(|(id, rect)| {
+                let distance = match direction {
+                    FocusDirection::Left => from.x - (rect.x + rect.width),
+                    FocusDirection::Right => rect.x - (from.x + from.width),
+                    FocusDirection::Up => from.y - (rect.y + rect.height),
+                    FocusDirection::Down => rect.y - (from.y + from.height),
+                };
+                let aligned = match direction {
+                    FocusDirection::Left | FocusDirection::Right => {
+                        overlaps(from.y, from.y + from.height, rect.y, rect.y + rect.height)
+                    }
+                    FocusDirection::Up | FocusDirection::Down => {
+                        overlaps(from.x, from.x + from.width, rect.x, rect.x + rect.width)
+                    }
+                };
+                (aligned && distance > -EPSILON).then_some((id, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(id, _)| id.clone())
+    }
+
+    // Drops panes whose sessions no longer exist. Returns false if none
+    // are left.
+    pub fn retain_sessions(&mut self, exists: impl Fn(&str) -> bool) -> bool {
+        for session_id in self.sessions() {
+            if !exists(&session_id) && !self.remove(&session_id) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "a" on the left, "b" above "c" on the right
+    fn l_shaped() -> PaneLayout {
+        let mut layout = PaneLayout::new("a");
+        assert!(layout.split("a", "b", SplitDirection::Horizontal));
+        assert!(layout.split("b", "c", SplitDirection::Vertical));
+        layout
+    }
+
+    fn split_id(node: &LayoutNode) -> String {
+        match node {
+            LayoutNode::Split { id, .. } => id.clone(),
+            LayoutNode::Pane { .. } => panic!("not a split"),
+        }
+    }
+
+    fn ratio(node: &LayoutNode) -> f32 {
+        match node {
+            LayoutNode::Split { ratio, .. } => *ratio,
+            LayoutNode::Pane { .. } => panic!("not a split"),
+        }
+    }
+
+    #[test]
+    fn splits_and_removes_back_to_one_pane() {
+        let mut layout = PaneLayout::new("a");
+        assert!(!layout.split("missing", "b", SplitDirection::Horizontal));
+        assert!(layout.split("a", "b", SplitDirection::Horizontal));
+        assert_eq!(layout.sessions(), vec!["a", "b"]);
+        assert_eq!(layout.focused_session, "b");
+
+        // The sibling takes the removed pane's place and the focus
+        assert!(layout.remove("b"));
+        assert!(matches!(&layout.root, LayoutNode::Pane { session_id } if session_id == "a"));
+        assert_eq!(layout.focused_session, "a");
+
+        assert!(!layout.remove("a"));
+    }
+
+    #[test]
+    fn promotes_subtree_when_pane_is_removed() {
+        let mut layout = l_shaped();
+        layout.focused_session = "a".to_string();
+
+        assert!(layout.remove("a"));
+        assert_eq!(layout.sessions(), vec!["b", "c"]);
+        assert_eq!(layout.focused_session, "b");
+        assert!(matches!(&layout.root, LayoutNode::Split { direction: SplitDirection::Vertical, .. }));
+
+        // Removing an unfocused pane keeps the focus where it was
+        assert!(layout.remove("c"));
+        assert_eq!(layout.sessions(), vec!["b"]);
+        assert_eq!(layout.focused_session, "b");
+    }
+
+    #[test]
+    fn finds_neighbors_in_l_shaped_layout() {
+        let layout = l_shaped();
+
+        assert_eq!(layout.neighbor("a", FocusDirection::Right).as_deref(), Some("b"));
+        assert_eq!(layout.neighbor("b", FocusDirection::Left).as_deref(), Some("a"));
+        assert_eq!(layout.neighbor("c", FocusDirection::Left).as_deref(), Some("a"));
+        assert_eq!(layout.neighbor("b", FocusDirection::Down).as_deref(), Some("c"));
+        assert_eq!(layout.neighbor("c", FocusDirection::Up).as_deref(), Some("b"));
+
+        // Nothing lies past the edges of the layout
+        assert_eq!(layout.neighbor("a", FocusDirection::Up), None);
+        assert_eq!(layout.neighbor("a", FocusDirection::Down), None);
+        assert_eq!(layout.neighbor("b", FocusDirection::Right), None);
+        assert_eq!(layout.neighbor("missing", FocusDirection::Left), None);
+    }
+
+    #[test]
+    fn prefers_the_closest_neighbor() {
+        let mut layout = PaneLayout::new("a");
+        layout.split("a", "b", SplitDirection::Horizontal);
+        layout.split("b", "c", SplitDirection::Horizontal);
+
+        assert_eq!(layout.neighbor("a", FocusDirection::Right).as_deref(), Some("b"));
+        assert_eq!(layout.neighbor("c", FocusDirection::Left).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn clamps_split_ratios() {
+        let mut layout = l_shaped();
+        let outer = split_id(&layout.root);
+
+        assert!(layout.set_ratio(&outer, 1.5));
+        assert_eq!(ratio(&layout.root), MAX_RATIO);
+        assert!(layout.set_ratio(&outer, -1.0));
+        assert_eq!(ratio(&layout.root), MIN_RATIO);
+        assert!(layout.set_ratio(&outer, 0.25));
+        assert_eq!(ratio(&layout.root), 0.25);
+        assert!(!layout.set_ratio("missing", 0.5));
+
+        let rects = layout.rects();
+        assert_eq!(rects[0], ("a".to_string(), PaneRect { x: 0.0, y: 0.0, width: 0.25, height: 1.0 }));
+        assert_eq!(rects[1], ("b".to_string(), PaneRect { x: 0.25, y: 0.0, width: 0.75, height: 0.5 }));
+    }
+
+    #[test]
+    fn retains_existing_sessions() {
+        let mut layout = l_shaped();
+        assert!(layout.retain_sessions(|id| id != "b"));
+        assert_eq!(layout.sessions(), vec!["a", "c"]);
+        assert_eq!(layout.focused_session, "c");
+
+        assert!(!layout.retain_sessions(|_| false));
+    }
+
+    #[test]
+    fn round_trips_through_serde() {
+        let layout = l_shaped();
+        let json = serde_json::to_value(&layout.root).unwrap();
+        assert_eq!(json["type"], "split");
+        assert_eq!(json["direction"], "horizontal");
+        assert_eq!(json["first"]["type"], "pane");
+        assert_eq!(json["second"]["second"]["session_id"], "c");
+
+        let root: LayoutNode = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&root).unwrap(), json);
+        assert_eq!(split_id(&root), split_id(&layout.root));
+    }
+}
@@ -0,0 +1,81 @@
+use super::layout::PaneLayout;
+use crate::{
+    config::Config,
+    error::{HoverShellError, Result},
+    utils::write_atomic,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const SESSIONS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub id: String,
+    pub title: String,
+    pub working_directory: String,
+    pub shell: String,
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    #[serde(default)]
+    pub scrollback: Vec<String>,
+    #[serde(default)]
+    pub command_history: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSessions {
+    pub version: u32,
+    pub saved_at: DateTime<Utc>,
+    pub sessions: Vec<SavedSession>,
+    #[serde(default)]
+    pub layouts: Vec<PaneLayout>,
+}
+
+impl SavedSessions {
+    pub fn new(sessions: Vec<SavedSession>, layouts: Vec<PaneLayout>) -> Self {
+        Self {
+            version: SESSIONS_VERSION,
+            saved_at: Utc::now(),
+            sessions,
+            layouts,
+        }
+    }
+}
+
+pub fn sessions_file() -> PathBuf {
+    Config::config_dir().join("sessions.json")
+}
+
+pub async fn save(saved: &SavedSessions) -> Result<()> {
+    let content = serde_json::to_string(saved)
+        .map_err(|e| HoverShellError::Serialization(format!("Failed to serialize sessions: {}", e)))?;
+    write_atomic(&sessions_file(), content.as_bytes()).await
+}
+
+pub async fn load() -> Result<Option<SavedSessions>> {
+    let path = sessions_file();
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = tokio::fs::read_to_string(&path).await?;
+    let saved: SavedSessions = serde_json::from_str(&content)
+        .map_err(|e| HoverShellError::Parse(format!("Failed to parse saved sessions: {}", e)))?;
+
+    if saved.version != SESSIONS_VERSION {
+        return Err(HoverShellError::Parse(format!(
+            "Unsupported saved sessions version: {}",
+            saved.version
+        )));
+    }
+
+    Ok(Some(saved))
+}
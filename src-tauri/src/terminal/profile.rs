@@ -0,0 +1,333 @@
+use crate::{
+    config::{TerminalConfig, TerminalProfile, WorkspaceRule},
+    utils::{expand_home, glob_to_regex},
+};
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const FALLBACK_SHELL: &str = "/bin/sh";
+
+// Everything needed to start a session's shell, after merging the terminal
+// config, the selected profile and any matching workspace rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedProfile {
+    pub name: Option<String>,
+    pub shell: String,
+    pub args: Vec<String>,
+    pub login_shell: bool,
+    pub environment: HashMap<String, String>,
+    pub working_directory: String,
+}
+
+impl ResolvedProfile {
+    // Arguments to pass to the shell, with the login flag first so it is
+    // seen before any profile arguments
+    pub fn shell_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.login_shell {
+            args.push("-l".to_string());
+        }
+        args.extend(self.args.iter().cloned());
+        args
+    }
+}
+
+// Resolves the profile for a new session. An explicitly requested profile
+// wins over one selected by a workspace rule for the working directory.
+pub fn resolve(
+    config: &TerminalConfig,
+    workspace_rules: &[WorkspaceRule],
+    working_directory: Option<&str>,
+    profile_name: Option<&str>,
+) -> ResolvedProfile {
+    let base_directory = working_directory
+        .map(str::to_string)
+        .or_else(|| config.working_directory.clone())
+        .map(|path| expand_home(&path))
+        .unwrap_or_else(current_directory);
+
+    let name = profile_name
+        .map(str::to_string)
+        .or_else(|| matching_rule(workspace_rules, Path::new(&base_directory)).map(|rule| rule.profile.clone()));
+    let profile = match &name {
+        Some(name) => config.profiles.get(name).cloned().unwrap_or_else(|| {
+            warn!("Terminal profile not found: {}", name);
+            TerminalProfile::default()
+        }),
+        None => TerminalProfile::default(),
+    };
+
+    let mut environment = config.environment.clone();
+    environment.extend(profile.environment);
+
+    // An explicit directory beats the profile's default directory
+    let working_directory = match (working_directory, profile.working_directory) {
+        (None, Some(path)) => expand_home(&path),
+        _ => base_directory,
+    };
+
+    let requested_shell = profile.shell.unwrap_or_else(|| config.shell.clone());
+
+    ResolvedProfile {
+        name,
+        shell: validate_shell(&requested_shell),
+        args: profile.args.unwrap_or_else(|| config.shell_args.clone()),
+        login_shell: profile.login_shell.unwrap_or(config.login_shell),
+        environment,
+        working_directory,
+    }
+}
+
+// Returns `shell` if it can be executed, otherwise the user's login shell
+// from $SHELL, otherwise /bin/sh
+pub fn validate_shell(shell: &str) -> String {
+    if let Some(path) = find_executable(shell) {
+        return path;
+    }
+
+    let fallback = std::env::var("SHELL")
+        .ok()
+        .and_then(|shell| find_executable(&shell))
+        .unwrap_or_else(|| FALLBACK_SHELL.to_string());
+    warn!("Shell {} not found, falling back to {}", shell, fallback);
+    fallback
+}
+
+fn find_executable(shell: &str) -> Option<String> {
+    let shell = expand_home(shell.trim());
+    if shell.is_empty() {
+        return None;
+    }
+
+    if shell.contains('/') {
+        return is_executable(Path::new(&shell)).then_some(shell);
+    }
+
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(&shell))
+            .find(|candidate| is_executable(candidate))
+            .map(|path| path.to_string_lossy().to_string())
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+// The first auto-switching rule whose pattern matches a file in the
+// directory or one of its parents, so subdirectories of a project match
+fn matching_rule<'a>(rules: &'a [WorkspaceRule], directory: &Path) -> Option<&'a WorkspaceRule> {
+    let patterns: Vec<(&WorkspaceRule, Regex)> = rules
+        .iter()
+        .filter(|rule| rule.auto_switch)
+        .filter_map(|rule| match glob_to_regex(&rule.pattern) {
+            Ok(regex) => Some((rule, regex)),
+            Err(e) => {
+                warn!("Ignoring workspace rule {} with invalid pattern: {}", rule.name, e);
+                None
+            }
+        })
+        .collect();
+    if patterns.is_empty() {
+        return None;
+    }
+
+    for dir in directory.ancestors() {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+
+        for (rule, regex) in &patterns {
+            if names.iter().any(|name| regex.is_match(name)) {
+                return Some(rule);
+            }
+        }
+    }
+
+    None
+}
+
+fn current_directory() -> String {
+    std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Tests that read or change $SHELL run one at a time
+    static SHELL_ENV: Mutex<()> = Mutex::new(());
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hovershell-profile-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn executable(dir: &Path, name: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn env(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn config(profiles: Vec<(&str, TerminalProfile)>) -> TerminalConfig {
+        TerminalConfig {
+            shell: FALLBACK_SHELL.to_string(),
+            shell_args: vec!["-i".to_string()],
+            login_shell: false,
+            environment: env(&[("A", "base"), ("B", "base")]),
+            profiles: profiles.into_iter().map(|(name, profile)| (name.to_string(), profile)).collect(),
+            ..TerminalConfig::default()
+        }
+    }
+
+    fn rule(name: &str, pattern: &str, profile: &str, auto_switch: bool) -> WorkspaceRule {
+        WorkspaceRule {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            profile: profile.to_string(),
+            auto_switch,
+        }
+    }
+
+    #[test]
+    fn profile_overrides_base_config() {
+        let dir = temp_dir();
+        let shell = executable(&dir, "work-shell");
+        let config = config(vec![("work", TerminalProfile {
+            shell: Some(shell.clone()),
+            args: Some(vec!["--norc".to_string()]),
+            login_shell: Some(true),
+            environment: env(&[("B", "profile"), ("C", "profile")]),
+            working_directory: None,
+        })]);
+
+        let resolved = resolve(&config, &[], Some("/explicit"), Some("work"));
+        assert_eq!(resolved.name.as_deref(), Some("work"));
+        assert_eq!(resolved.shell, shell);
+        assert_eq!(resolved.shell_args(), vec!["-l", "--norc"]);
+        // Profile variables are layered over the base environment
+        assert_eq!(resolved.environment, env(&[("A", "base"), ("B", "profile"), ("C", "profile")]));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn missing_profile_uses_base_config() {
+        let resolved = resolve(&config(vec![]), &[], Some("/explicit"), Some("absent"));
+        assert_eq!(resolved.name.as_deref(), Some("absent"));
+        assert_eq!(resolved.shell, FALLBACK_SHELL);
+        assert_eq!(resolved.shell_args(), vec!["-i"]);
+        assert_eq!(resolved.environment, env(&[("A", "base"), ("B", "base")]));
+    }
+
+    #[test]
+    fn explicit_directory_beats_profile_default() {
+        let config = config(vec![("work", TerminalProfile {
+            working_directory: Some("/profile-dir".to_string()),
+            ..TerminalProfile::default()
+        })]);
+
+        let explicit = resolve(&config, &[], Some("/explicit"), Some("work"));
+        assert_eq!(explicit.working_directory, "/explicit");
+
+        let default = resolve(&config, &[], None, Some("work"));
+        assert_eq!(default.working_directory, "/profile-dir");
+    }
+
+    #[test]
+    fn finds_executables() {
+        let dir = temp_dir();
+        let shell = executable(&dir, "shell");
+        let plain = dir.join("plain");
+        std::fs::write(&plain, "").unwrap();
+
+        assert_eq!(find_executable(&shell), Some(shell.clone()));
+        assert_eq!(find_executable(&format!("  {}  ", shell)), Some(shell));
+        assert_eq!(find_executable(&plain.to_string_lossy()), None);
+        assert_eq!(find_executable(&dir.to_string_lossy()), None);
+        assert_eq!(find_executable(""), None);
+        assert!(find_executable("sh").is_some_and(|path| path.ends_with("/sh")));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn missing_shell_falls_back_to_login_shell_then_sh() {
+        let _guard = SHELL_ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let saved = std::env::var_os("SHELL");
+        let dir = temp_dir();
+        let login_shell = executable(&dir, "login-shell");
+        let missing = dir.join("missing").to_string_lossy().to_string();
+
+        std::env::set_var("SHELL", &login_shell);
+        assert_eq!(validate_shell(&missing), login_shell);
+
+        std::env::set_var("SHELL", &missing);
+        assert_eq!(validate_shell(&missing), FALLBACK_SHELL);
+
+        std::env::remove_var("SHELL");
+        assert_eq!(validate_shell(&missing), FALLBACK_SHELL);
+
+        match saved {
+            Some(shell) => std::env::set_var("SHELL", shell),
+            None => std::env::remove_var("SHELL"),
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn workspace_rule_matches_from_subdirectory() {
+        let _guard = SHELL_ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let project = temp_dir();
+        std::fs::write(project.join("Cargo.toml"), "").unwrap();
+        let nested = project.join("src").join("deep");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let rules = vec![
+            rule("manual", "Cargo.toml", "manual", false),
+            rule("node", "package.json", "node", true),
+            rule("rust", "Cargo.*", "rust", true),
+        ];
+        assert_eq!(matching_rule(&rules, &nested).map(|r| r.name.as_str()), Some("rust"));
+        assert!(matching_rule(&rules[..2], &nested).is_none());
+
+        let missing = project.join("missing-shell").to_string_lossy().to_string();
+        let config = config(vec![("rust", TerminalProfile {
+            shell: Some(missing.clone()),
+            ..TerminalProfile::default()
+        })]);
+        let nested = nested.to_string_lossy().to_string();
+        let resolved = resolve(&config, &rules, Some(&nested), None);
+        assert_eq!(resolved.name.as_deref(), Some("rust"));
+        assert_eq!(resolved.working_directory, nested);
+        assert_ne!(resolved.shell, missing);
+        assert!(find_executable(&resolved.shell).is_some());
+
+        // An explicit profile wins over the rule
+        let explicit = resolve(&config, &rules, Some(&nested), Some("other"));
+        assert_eq!(explicit.name.as_deref(), Some("other"));
+
+        let _ = std::fs::remove_dir_all(project);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::error::{HoverShellError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+
+#[derive(Debug, Clone, Copy)]
+pub struct PtySize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for PtySize {
+    fn default() -> Self {
+        Self { rows: 24, cols: 80 }
+    }
+}
+
+// How a shell ended: its exit code, or the signal that killed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+}
+
+pub struct PtyOptions<'a> {
+    pub shell: &'a str,
+    pub args: &'a [String],
+    pub working_directory: &'a str,
+    pub environment: &'a HashMap<String, String>,
+    pub size: PtySize,
+}
+
+// A shell process attached to the slave side of a pseudo-terminal. The
+// master side is kept here for writing input; readers get their own handle
+// through `try_clone_reader` so output can be pumped on a separate thread.
+pub struct Pty {
+    master: File,
+    child: Child,
+    size: PtySize,
+}
+
+impl Pty {
+    pub fn spawn(options: PtyOptions<'_>) -> Result<Self> {
+        let mut master_fd: libc::c_int = -1;
+        let mut slave_fd: libc::c_int = -1;
+        let mut winsize = libc::winsize {
+            ws_row: options.size.rows,
+            ws_col: options.size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        let ret = unsafe {
+            libc::openpty(
+                &mut master_fd,
+                &mut slave_fd,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &mut winsize,
+            )
+        };
+        if ret != 0 {
+            return Err(HoverShellError::Terminal(format!(
+                "Failed to allocate pseudo-terminal: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+
+        let master = unsafe { File::from_raw_fd(master_fd) };
+        let slave = unsafe { File::from_raw_fd(slave_fd) };
+
+        // openpty can't open them close-on-exec, so mark both before anything
+        // else gets a chance to spawn: neither the shell nor other children
+        // (jobs, sidecars) may inherit the master, and the slave only reaches
+        // the shell as its stdio
+        set_cloexec(master_fd)?;
+        set_cloexec(slave_fd)?;
+
+        let stdin = slave.try_clone()?;
+        let stdout = slave.try_clone()?;
+
+        let mut command = Command::new(options.shell);
+        command
+            .args(options.args)
+            .current_dir(options.working_directory)
+            .envs(options.environment)
+            .env("TERM", "xterm-256color")
+            .env("COLORTERM", "truecolor")
+            .env("TERM_PROGRAM", "HoverShell")
+            .stdin(Stdio::from(stdin))
+            .stdout(Stdio::from(stdout))
+            .stderr(Stdio::from(slave));
+
+        // Start a new session and make the pty slave its controlling terminal
+        // so job control, signals and interactive programs behave normally.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let child = command
+            .spawn()
+            .map_err(|e| HoverShellError::Terminal(format!("Failed to start shell: {}", e)))?;
+
+        Ok(Self { master, child, size: options.size })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    pub fn size(&self) -> PtySize {
+        self.size
+    }
+
+    pub fn resize(&mut self, size: PtySize) -> Result<()> {
+        let winsize = libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let fd = self.master.as_raw_fd();
+
+        if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ as _, &winsize) } == -1 {
+            return Err(HoverShellError::Terminal(format!(
+                "Failed to resize pty: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        self.size = size;
+
+        // Signal the foreground process group so full-screen programs redraw
+        let pgrp = unsafe { libc::tcgetpgrp(fd) };
+        if pgrp > 0 {
+            unsafe {
+                libc::killpg(pgrp, libc::SIGWINCH);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Sends a signal to every process group in the shell's session: the
+    // shell's own group, the foreground job and any background jobs
+    pub fn signal_group(&self, signal: libc::c_int) {
+        let pgid = self.pid() as libc::pid_t;
+        let mut groups = vec![pgid];
+
+        let foreground = unsafe { libc::tcgetpgrp(self.master.as_raw_fd()) };
+        if foreground > 0 {
+            groups.push(foreground);
+        }
+        // The shell leads its own session, so the session id is its pid
+        groups.extend(session_process_groups(pgid));
+        groups.sort_unstable();
+        groups.dedup();
+
+        for group in groups {
+            unsafe {
+                libc::killpg(group, signal);
+            }
+        }
+    }
+
+    pub fn try_clone_reader(&self) -> Result<File> {
+        self.master
+            .try_clone()
+            .map_err(|e| HoverShellError::Terminal(format!("Failed to clone pty reader: {}", e)))
+    }
+
+    pub fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        self.master
+            .write_all(data)
+            .and_then(|_| self.master.flush())
+            .map_err(|e| HoverShellError::Terminal(format!("Failed to write to pty: {}", e)))
+    }
+}
+
+fn set_cloexec(fd: libc::c_int) -> Result<()> {
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        return Err(HoverShellError::Terminal(format!(
+            "Failed to set close-on-exec on pty: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn session_process_groups(session_id: libc::pid_t) -> Vec<libc::pid_t> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            // The command name may contain spaces, so parse after its closing paren
+            let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+            let pgrp: libc::pid_t = fields.get(2)?.parse().ok()?;
+            let session: libc::pid_t = fields.get(3)?.parse().ok()?;
+            (session == session_id).then_some(pgrp)
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn session_process_groups(_session_id: libc::pid_t) -> Vec<libc::pid_t> {
+    Vec::new()
+}
+
+// Blocks until the process exits and reaps it
+pub fn wait_for_exit(pid: u32) -> ProcessExit {
+    let mut status: libc::c_int = 0;
+    loop {
+        let ret = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, 0) };
+        if ret != -1 {
+            break;
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return ProcessExit { code: None, signal: None };
+        }
+    }
+
+    if libc::WIFEXITED(status) {
+        ProcessExit { code: Some(libc::WEXITSTATUS(status)), signal: None }
+    } else if libc::WIFSIGNALED(status) {
+        ProcessExit { code: None, signal: Some(libc::WTERMSIG(status)) }
+    } else {
+        ProcessExit { code: None, signal: None }
+    }
+}
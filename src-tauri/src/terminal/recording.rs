@@ -0,0 +1,308 @@
+use crate::{
+    config::Config,
+    error::{HoverShellError, Result},
+};
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const REDACTED: &str = "[REDACTED]";
+
+// Partial lines are held back so a secret split across reads is still
+// matched, but not forever: a prompt or progress bar has no newline.
+const MAX_PENDING_BYTES: usize = 4096;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_time_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CastEvent {
+    pub time: f64,
+    pub kind: String,
+    pub data: String,
+}
+
+// Masks secrets in recorded output using the configured patterns and the
+// API keys of configured providers
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    literals: Vec<String>,
+}
+
+impl Redactor {
+    pub fn from_config(config: &Config) -> Self {
+        let patterns = config.security.redaction_patterns.iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!("Ignoring invalid redaction pattern {}: {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+
+        let literals = config.providers.iter()
+            .filter_map(|provider| provider.api_key.clone())
+            .filter(|key| !key.is_empty())
+            .collect();
+
+        Self { patterns, literals }
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = text.to_string();
+        for literal in &self.literals {
+            redacted = redacted.replace(literal.as_str(), REDACTED);
+        }
+        for pattern in &self.patterns {
+            redacted = pattern.replace_all(&redacted, REDACTED).into_owned();
+        }
+        redacted
+    }
+
+    // Where an overlong partial line can be cut: far enough back that a
+    // secret cut off at the end is matched once the rest arrives, and never
+    // inside a secret
+    fn flush_point(&self, text: &str) -> usize {
+        let keep = self.literals.iter().map(String::len).max().unwrap_or(0);
+        let mut split = text.len().saturating_sub(keep);
+        while !text.is_char_boundary(split) {
+            split -= 1;
+        }
+
+        loop {
+            let straddling = self.literals.iter()
+                .flat_map(|literal| text.match_indices(literal.as_str()))
+                .find(|(start, literal)| *start < split && split < start + literal.len());
+            match straddling {
+                Some((start, _)) => split = start,
+                None => return split,
+            }
+        }
+    }
+}
+
+// Writes one session's output as an asciicast v2 file
+pub struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+    redactor: Redactor,
+    pending: String,
+    pending_time: f64,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, header: &CastHeader, redactor: Redactor) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = serde_json::to_string(header)
+            .map_err(|e| HoverShellError::Serialization(format!("Failed to serialize cast header: {}", e)))?;
+        writeln!(writer, "{}", header)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            started: Instant::now(),
+            redactor,
+            pending: String::new(),
+            pending_time: 0.0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_output(&mut self, data: &str) -> Result<()> {
+        if self.pending.is_empty() {
+            self.pending_time = self.elapsed();
+        }
+        self.pending.push_str(data);
+
+        // Only complete lines are redacted and written
+        let split = match self.pending.rfind('\n') {
+            Some(pos) => pos + 1,
+            None if self.pending.len() > MAX_PENDING_BYTES => self.redactor.flush_point(&self.pending),
+            None => return Ok(()),
+        };
+        if split == 0 {
+            return Ok(());
+        }
+        let rest = self.pending.split_off(split);
+        let ready = std::mem::replace(&mut self.pending, rest);
+        let time = self.pending_time;
+        self.pending_time = self.elapsed();
+
+        self.write_event(time, "o", &self.redactor.redact(&ready))
+    }
+
+    pub fn write_resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        self.flush_pending()?;
+        let time = self.elapsed();
+        self.write_event(time, "r", &format!("{}x{}", cols, rows))
+    }
+
+    pub fn finish(mut self) -> Result<PathBuf> {
+        self.flush_pending()?;
+        self.writer.flush()?;
+        Ok(self.path)
+    }
+
+    fn flush_pending(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let redacted = self.redactor.redact(&pending);
+        self.write_event(self.pending_time, "o", &redacted)
+    }
+
+    fn write_event(&mut self, time: f64, kind: &str, data: &str) -> Result<()> {
+        let event = serde_json::json!([(time * 1_000_000.0).round() / 1_000_000.0, kind, data]);
+        writeln!(self.writer, "{}", event)?;
+        Ok(())
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+}
+
+pub fn read_cast(path: &Path) -> Result<(CastHeader, Vec<CastEvent>)> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut lines = reader.lines();
+
+    let header_line = lines.next()
+        .ok_or_else(|| HoverShellError::Parse(format!("Empty recording: {}", path.display())))??;
+    let header: CastHeader = serde_json::from_str(&header_line)
+        .map_err(|e| HoverShellError::Parse(format!("Invalid asciicast header: {}", e)))?;
+    if header.version != 2 {
+        return Err(HoverShellError::Parse(format!("Unsupported asciicast version: {}", header.version)));
+    }
+
+    let mut events = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(&line)
+            .map_err(|e| HoverShellError::Parse(format!("Invalid asciicast event: {}", e)))?;
+        events.push(CastEvent { time, kind, data });
+    }
+
+    Ok((header, events))
+}
+
+// Slowest playback allowed; anything slower is indistinguishable from a hang
+pub const MIN_PLAYBACK_SPEED: f64 = 0.1;
+
+// How long to wait before an event during playback
+pub fn playback_delay(previous: f64, next: f64, speed: f64, idle_time_limit: Option<f64>) -> Duration {
+    let mut gap = (next - previous).max(0.0);
+    if let Some(limit) = idle_time_limit {
+        gap = gap.min(limit);
+    }
+    // A tiny speed on a long gap can exceed what a Duration holds
+    Duration::try_from_secs_f64(gap / speed).unwrap_or(Duration::MAX)
+}
+
+pub fn default_recording_path(session_id: &str) -> PathBuf {
+    let short_id: String = session_id.chars().take(8).collect();
+    Config::config_dir()
+        .join("recordings")
+        .join(format!("{}-{}.cast", short_id, chrono::Local::now().format("%Y%m%d-%H%M%S")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "sk-test-0123456789";
+
+    fn redactor() -> Redactor {
+        Redactor {
+            patterns: vec![Regex::new(r"password=\S+").unwrap()],
+            literals: vec![KEY.to_string()],
+        }
+    }
+
+    // Records `writes` and returns the output events as written to disk
+    fn record(writes: &[&str]) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("hovershell-recording-{}.cast", uuid::Uuid::new_v4()));
+        let header = CastHeader {
+            version: 2,
+            width: 80,
+            height: 24,
+            timestamp: None,
+            idle_time_limit: None,
+            title: None,
+            env: HashMap::new(),
+        };
+
+        let mut recorder = Recorder::create(&path, &header, redactor()).unwrap();
+        for data in writes {
+            recorder.write_output(data).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let (_, events) = read_cast(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        events.into_iter().map(|event| event.data).collect()
+    }
+
+    #[test]
+    fn redacts_literals_and_patterns() {
+        let redactor = redactor();
+        assert_eq!(
+            redactor.redact(&format!("export KEY={} && login password=hunter2 now", KEY)),
+            "export KEY=[REDACTED] && login [REDACTED] now",
+        );
+        assert_eq!(redactor.redact("nothing to hide"), "nothing to hide");
+        assert_eq!(Redactor::default().redact(KEY), KEY);
+    }
+
+    #[test]
+    fn redacts_secret_split_across_lines_of_output() {
+        let events = record(&[&KEY[..5], &format!("{}\n", &KEY[5..])]);
+        assert_eq!(events, vec!["[REDACTED]\n"]);
+    }
+
+    #[test]
+    fn redacts_secret_split_by_overlong_line() {
+        // The first write goes over the pending limit with the key cut off
+        let filler = "x".repeat(MAX_PENDING_BYTES);
+        let events = record(&[&format!("{}{}", filler, &KEY[..5]), &format!("{} done\n", &KEY[5..])]);
+        let output = events.concat();
+        assert!(events.len() > 1);
+        assert!(!output.contains(&KEY[..5]));
+        assert_eq!(output, format!("{}[REDACTED] done\n", filler));
+
+        // A whole key across the cut is not split either
+        let events = record(&[&format!("{}{}yyyyy", filler, KEY)]);
+        assert_eq!(events[0], filler);
+        assert_eq!(events.concat(), format!("{}[REDACTED]yyyyy", filler));
+    }
+}
@@ -0,0 +1,179 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrollbackLine {
+    pub index: u64,
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputRange {
+    pub first_index: u64,
+    pub next_index: u64,
+    pub lines: Vec<ScrollbackLine>,
+}
+
+// Fixed-capacity line buffer. Lines keep an absolute index that survives
+// trimming, so callers can page through history with stable positions.
+#[derive(Debug, Clone)]
+pub struct Scrollback {
+    lines: VecDeque<ScrollbackLine>,
+    capacity: usize,
+    first_index: u64,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity: capacity.max(1),
+            first_index: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.trim();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn first_index(&self) -> u64 {
+        self.first_index
+    }
+
+    pub fn next_index(&self) -> u64 {
+        self.first_index + self.lines.len() as u64
+    }
+
+    pub fn push_line(&mut self, text: &str) {
+        self.push_line_at(text, Utc::now());
+    }
+
+    pub fn push_line_at(&mut self, text: &str, timestamp: DateTime<Utc>) {
+        self.lines.push_back(ScrollbackLine {
+            index: self.next_index(),
+            text: text.to_string(),
+            timestamp,
+        });
+        self.trim();
+    }
+
+    // Returns lines in the absolute range [from, to), clamped to what is
+    // still retained. Omitted bounds default to the start and end.
+    pub fn range(&self, from: Option<u64>, to: Option<u64>) -> OutputRange {
+        let next_index = self.next_index();
+        let from = from.unwrap_or(self.first_index).clamp(self.first_index, next_index);
+        let to = to.unwrap_or(next_index).clamp(from, next_index);
+
+        let start = (from - self.first_index) as usize;
+        let end = (to - self.first_index) as usize;
+
+        OutputRange {
+            first_index: self.first_index,
+            next_index,
+            lines: self.lines.range(start..end).cloned().collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScrollbackLine> {
+        self.lines.iter()
+    }
+
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn clear(&mut self) {
+        self.first_index = self.next_index();
+        self.lines.clear();
+    }
+
+    fn trim(&mut self) {
+        while self.lines.len() > self.capacity {
+            self.lines.pop_front();
+            self.first_index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrollback(capacity: usize, lines: u64) -> Scrollback {
+        let mut scrollback = Scrollback::new(capacity);
+        for i in 0..lines {
+            scrollback.push_line(&format!("line {}", i));
+        }
+        scrollback
+    }
+
+    fn indices(range: &OutputRange) -> Vec<u64> {
+        range.lines.iter().map(|line| line.index).collect()
+    }
+
+    #[test]
+    fn trims_oldest_lines_at_capacity() {
+        let mut scrollback = scrollback(3, 5);
+        assert_eq!(scrollback.len(), 3);
+        assert_eq!((scrollback.first_index(), scrollback.next_index()), (2, 5));
+        assert_eq!(scrollback.text(), "line 2\nline 3\nline 4");
+
+        scrollback.set_capacity(1);
+        assert_eq!(scrollback.text(), "line 4");
+        assert_eq!(scrollback.first_index(), 4);
+
+        // A capacity of zero still keeps the last line
+        scrollback.set_capacity(0);
+        assert_eq!(scrollback.capacity(), 1);
+        assert_eq!(scrollback.len(), 1);
+    }
+
+    #[test]
+    fn clamps_ranges_to_retained_lines() {
+        let scrollback = scrollback(5, 8);
+
+        let all = scrollback.range(None, None);
+        assert_eq!((all.first_index, all.next_index), (3, 8));
+        assert_eq!(indices(&all), vec![3, 4, 5, 6, 7]);
+
+        assert_eq!(indices(&scrollback.range(Some(0), Some(5))), vec![3, 4]);
+        assert_eq!(indices(&scrollback.range(Some(6), Some(100))), vec![6, 7]);
+        assert_eq!(indices(&scrollback.range(Some(5), Some(5))), Vec::<u64>::new());
+        // An inverted range is empty rather than an error
+        assert_eq!(indices(&scrollback.range(Some(7), Some(4))), Vec::<u64>::new());
+        assert_eq!(indices(&scrollback.range(Some(50), None)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn clear_keeps_line_indices_increasing() {
+        let mut scrollback = scrollback(10, 4);
+        scrollback.clear();
+        assert!(scrollback.is_empty());
+        assert_eq!((scrollback.first_index(), scrollback.next_index()), (4, 4));
+
+        scrollback.push_line("after");
+        let range = scrollback.range(None, None);
+        assert_eq!(indices(&range), vec![4]);
+        assert_eq!(range.lines[0].text, "after");
+        assert!(scrollback.range(Some(0), Some(4)).lines.is_empty());
+    }
+}
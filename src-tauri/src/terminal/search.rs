@@ -0,0 +1,235 @@
+use super::scrollback::ScrollbackLine;
+use crate::error::{HoverShellError, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub max_results: usize,
+    // Restricts the search to lines [from_line, to_line)
+    pub from_line: Option<u64>,
+    pub to_line: Option<u64>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            max_results: DEFAULT_MAX_RESULTS,
+            from_line: None,
+            to_line: None,
+        }
+    }
+}
+
+// A match on one line. Columns are character offsets into the line text,
+// matching cell positions for narrow characters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub session_id: String,
+    pub line: u64,
+    pub start_column: usize,
+    pub end_column: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub session_id: String,
+    pub matches: Vec<SearchMatch>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchDirection {
+    Next,
+    Previous,
+}
+
+pub fn build_regex(pattern: &str, options: &SearchOptions) -> Result<Regex> {
+    let mut regex_pattern = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+
+    if options.whole_word {
+        regex_pattern = format!(r"\b(?:{})\b", regex_pattern);
+    }
+    if !options.case_sensitive {
+        regex_pattern = format!("(?i){}", regex_pattern);
+    }
+
+    Regex::new(&regex_pattern)
+        .map_err(|e| HoverShellError::Terminal(format!("Invalid search pattern: {}", e)))
+}
+
+pub fn search_lines<'a>(
+    session_id: &str,
+    lines: impl Iterator<Item = &'a ScrollbackLine>,
+    regex: &Regex,
+    max_results: usize,
+) -> SearchResult {
+    let mut matches = Vec::new();
+    let mut truncated = false;
+
+    'lines: for line in lines {
+        for found in regex.find_iter(&line.text) {
+            // Zero-width matches (e.g. `^`) have nothing to highlight
+            if found.start() == found.end() {
+                continue;
+            }
+            if matches.len() >= max_results {
+                truncated = true;
+                break 'lines;
+            }
+
+            let start_column = line.text[..found.start()].chars().count();
+            matches.push(SearchMatch {
+                session_id: session_id.to_string(),
+                line: line.index,
+                start_column,
+                end_column: start_column + found.as_str().chars().count(),
+                text: line.text.clone(),
+            });
+        }
+    }
+
+    SearchResult {
+        session_id: session_id.to_string(),
+        matches,
+        truncated,
+    }
+}
+
+// Picks the match after (or before) a position, wrapping around the ends
+// of the buffer. `matches` must be in transcript order.
+pub fn step_match(
+    matches: &[SearchMatch],
+    line: u64,
+    column: usize,
+    direction: SearchDirection,
+) -> Option<SearchMatch> {
+    let position = (line, column);
+    let found = match direction {
+        SearchDirection::Next => matches
+            .iter()
+            .find(|m| (m.line, m.start_column) > position)
+            .or_else(|| matches.first()),
+        SearchDirection::Previous => matches
+            .iter()
+            .rev()
+            .find(|m| (m.line, m.start_column) < position)
+            .or_else(|| matches.last()),
+    };
+    found.cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn lines(texts: &[&str]) -> Vec<ScrollbackLine> {
+        texts.iter()
+            .enumerate()
+            .map(|(index, text)| ScrollbackLine {
+                index: index as u64,
+                text: text.to_string(),
+                timestamp: Utc::now(),
+            })
+            .collect()
+    }
+
+    fn options(regex: bool, case_sensitive: bool, whole_word: bool) -> SearchOptions {
+        SearchOptions {
+            regex,
+            case_sensitive,
+            whole_word,
+            ..Default::default()
+        }
+    }
+
+    fn found(line: u64, start_column: usize) -> SearchMatch {
+        SearchMatch {
+            session_id: "session".to_string(),
+            line,
+            start_column,
+            end_column: start_column + 1,
+            text: String::new(),
+        }
+    }
+
+    fn position(found: Option<SearchMatch>) -> Option<(u64, usize)> {
+        found.map(|m| (m.line, m.start_column))
+    }
+
+    #[test]
+    fn builds_regex_from_options() {
+        let regex = build_regex("Error", &options(false, false, false)).unwrap();
+        assert!(regex.is_match("an error occurred"));
+        assert!(regex.is_match("ERRORS"));
+
+        let regex = build_regex("Error", &options(false, true, false)).unwrap();
+        assert!(!regex.is_match("an error occurred"));
+        assert!(regex.is_match("Error: failed"));
+
+        let regex = build_regex("err", &options(false, false, true)).unwrap();
+        assert!(regex.is_match("stderr err"));
+        assert!(!regex.is_match("stderr error"));
+
+        // Without regex, metacharacters are matched literally
+        let regex = build_regex("a.b(c)*", &options(false, true, false)).unwrap();
+        assert!(regex.is_match("x a.b(c)* y"));
+        assert!(!regex.is_match("axbccc"));
+
+        let regex = build_regex(r"err(or)?|warn", &options(true, false, true)).unwrap();
+        assert!(regex.is_match("WARN: disk"));
+        assert!(regex.is_match("error"));
+        assert!(!regex.is_match("warning"));
+
+        assert!(build_regex("(unclosed", &options(true, false, false)).is_err());
+        assert!(build_regex("(unclosed", &options(false, false, false)).is_ok());
+    }
+
+    #[test]
+    fn reports_columns_in_characters() {
+        let lines = lines(&["héllo wörld", "日本語 error here", "plain error"]);
+        let regex = build_regex("wörld|error", &options(true, false, false)).unwrap();
+        let result = search_lines("session", lines.iter(), &regex, 10);
+
+        let columns: Vec<(u64, usize, usize)> = result.matches.iter()
+            .map(|m| (m.line, m.start_column, m.end_column))
+            .collect();
+        assert_eq!(columns, vec![(0, 6, 11), (1, 4, 9), (2, 6, 11)]);
+        assert!(!result.truncated);
+
+        let result = search_lines("session", lines.iter(), &regex, 2);
+        assert_eq!(result.matches.len(), 2);
+        assert!(result.truncated);
+    }
+
+    #[test]
+    fn steps_through_matches_and_wraps() {
+        let matches = vec![found(1, 0), found(1, 5), found(4, 2)];
+
+        assert_eq!(position(step_match(&matches, 1, 0, SearchDirection::Next)), Some((1, 5)));
+        assert_eq!(position(step_match(&matches, 1, 5, SearchDirection::Next)), Some((4, 2)));
+        assert_eq!(position(step_match(&matches, 4, 2, SearchDirection::Next)), Some((1, 0)));
+
+        assert_eq!(position(step_match(&matches, 4, 2, SearchDirection::Previous)), Some((1, 5)));
+        assert_eq!(position(step_match(&matches, 1, 5, SearchDirection::Previous)), Some((1, 0)));
+        assert_eq!(position(step_match(&matches, 1, 0, SearchDirection::Previous)), Some((4, 2)));
+
+        assert_eq!(step_match(&[], 0, 0, SearchDirection::Next).map(|m| m.line), None);
+    }
+}
@@ -0,0 +1,385 @@
+use super::emulator::Emulator;
+use crate::{config::Config, error::Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+const MAX_BLOCKS: usize = 1000;
+const MAX_HISTORY: usize = 500;
+
+const ZSH_SCRIPT: &str = include_str!("../../resources/shell-integration/hovershell.zsh");
+const BASH_SCRIPT: &str = include_str!("../../resources/shell-integration/hovershell.bash");
+const FISH_SCRIPT: &str = include_str!("../../resources/shell-integration/hovershell.fish");
+
+// Set for login bash sessions, which the bash script turns into sourcing
+// the login files
+pub const BASH_LOGIN_VAR: &str = "HOVERSHELL_BASH_LOGIN";
+
+// Startup files zsh reads from $ZDOTDIR before the user's environment is
+// fully set up. Each wrapper sources the user's own copy; .zshrc also loads
+// the integration and restores ZDOTDIR so .zlogin comes from the user.
+const ZSH_WRAPPERS: &[&str] = &[".zshenv", ".zprofile", ".zshrc"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandBlock {
+    pub id: u64,
+    // As the shell reported it, including a leading space that keeps the
+    // command out of history
+    pub command: String,
+    pub prompt_line: u64,
+    pub output_start: Option<u64>,
+    pub output_end: Option<u64>,
+    pub exit_code: Option<i32>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<u64>,
+    pub working_directory: Option<String>,
+}
+
+impl CommandBlock {
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.is_some()
+    }
+}
+
+// Arguments and environment that load the integration script for a shell
+#[derive(Debug, Clone, Default)]
+pub struct ShellLaunch {
+    pub args: Vec<String>,
+    pub environment: HashMap<String, String>,
+}
+
+// Tracks OSC 133 prompt/command markers and OSC 7 directory reports for one
+// session and turns them into command blocks.
+#[derive(Debug, Default)]
+pub struct ShellIntegration {
+    blocks: VecDeque<CommandBlock>,
+    current: Option<CommandBlock>,
+    input_start: Option<(u64, usize)>,
+    next_id: u64,
+    working_directory: Option<String>,
+    history: VecDeque<String>,
+}
+
+impl ShellIntegration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn working_directory(&self) -> Option<&str> {
+        self.working_directory.as_deref()
+    }
+
+    pub fn blocks(&self) -> Vec<CommandBlock> {
+        self.blocks.iter().cloned().chain(self.current.clone()).collect()
+    }
+
+    pub fn get_block(&self, block_id: u64) -> Option<&CommandBlock> {
+        self.blocks
+            .iter()
+            .chain(self.current.iter())
+            .find(|block| block.id == block_id)
+    }
+
+    pub fn last_finished(&self) -> Option<&CommandBlock> {
+        self.blocks.back()
+    }
+
+    // Commands run in this session, oldest first
+    pub fn history(&self) -> Vec<String> {
+        self.history.iter().cloned().collect()
+    }
+
+    pub fn seed_history(&mut self, commands: Vec<String>) {
+        for command in commands {
+            self.push_history(command);
+        }
+    }
+
+    fn push_history(&mut self, command: String) {
+        let command = command.trim().to_string();
+        if command.is_empty() || self.history.back() == Some(&command) {
+            return;
+        }
+        self.history.push_back(command);
+        while self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+
+    // Handles an OSC sequence the emulator passed through. Returns the block
+    // that was just finished, if any.
+    pub fn handle_osc(
+        &mut self,
+        params: &[String],
+        line: u64,
+        col: usize,
+        screen: &Emulator,
+    ) -> Option<CommandBlock> {
+        match params.first().map(String::as_str) {
+            Some("7") => {
+                if let Some(path) = params.get(1).and_then(|url| parse_file_url(url)) {
+                    self.working_directory = Some(path);
+                }
+                None
+            }
+            Some("133") => self.handle_marker(params, line, col, screen),
+            _ => None,
+        }
+    }
+
+    fn handle_marker(
+        &mut self,
+        params: &[String],
+        line: u64,
+        col: usize,
+        screen: &Emulator,
+    ) -> Option<CommandBlock> {
+        match params.get(1).map(String::as_str) {
+            // Prompt start: a new block begins. A command still marked as
+            // running never reported its status (e.g. the shell was killed).
+            Some("A") => {
+                let finished = self
+                    .current
+                    .take()
+                    .filter(|block| block.started_at.is_some())
+                    .map(|mut block| {
+                        block.output_end = Some(line);
+                        self.finish(block)
+                    });
+
+                self.input_start = None;
+                self.current = Some(self.new_block(line));
+                finished
+            }
+            // Prompt end: command input starts at the cursor
+            Some("B") => {
+                self.input_start = Some((line, col));
+                None
+            }
+            // Command submitted: output starts here
+            Some("C") => {
+                let command = params[2..]
+                    .iter()
+                    .find_map(|param| param.strip_prefix("cmdline_url=").map(percent_decode))
+                    .or_else(|| {
+                        params[2..]
+                            .iter()
+                            .find_map(|param| param.strip_prefix("cmdline=").map(str::to_string))
+                    })
+                    .or_else(|| self.typed_command(screen))
+                    .unwrap_or_default();
+
+                // Shells without a prompt marker still get a block
+                if self.current.is_none() {
+                    self.current = Some(self.new_block(line));
+                }
+                let block = self.current.as_mut()?;
+                block.command = command;
+                block.output_start = Some(line);
+                block.started_at = Some(Utc::now());
+                None
+            }
+            // Command finished with an optional exit status
+            Some("D") => {
+                let mut block = self.current.take().filter(|block| block.started_at.is_some())?;
+                block.exit_code = params.get(2).and_then(|code| code.parse().ok());
+                block.output_end = Some(line);
+                Some(self.finish(block))
+            }
+            _ => None,
+        }
+    }
+
+    fn new_block(&mut self, line: u64) -> CommandBlock {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        CommandBlock {
+            id,
+            command: String::new(),
+            prompt_line: line,
+            output_start: None,
+            output_end: None,
+            exit_code: None,
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            working_directory: self.working_directory.clone(),
+        }
+    }
+
+    fn finish(&mut self, mut block: CommandBlock) -> CommandBlock {
+        let finished_at = Utc::now();
+        block.duration_ms = block
+            .started_at
+            .map(|started_at| (finished_at - started_at).num_milliseconds().max(0) as u64);
+        block.finished_at = Some(finished_at);
+
+        self.push_history(block.command.clone());
+        self.blocks.push_back(block.clone());
+        while self.blocks.len() > MAX_BLOCKS {
+            self.blocks.pop_front();
+        }
+        block
+    }
+
+    // Falls back to what the user typed after the prompt when the shell did
+    // not report the command line itself
+    fn typed_command(&self, screen: &Emulator) -> Option<String> {
+        let (line, col) = self.input_start?;
+        let text = screen.line_text(line)?;
+        Some(text.chars().skip(col).collect())
+    }
+}
+
+pub fn block_output(screen: &Emulator, block: &CommandBlock) -> String {
+    let (Some(start), end) = (block.output_start, block.output_end) else {
+        return String::new();
+    };
+    let end = end.unwrap_or_else(|| screen.cursor_line() + 1);
+
+    screen
+        .transcript_range(Some(start), Some(end))
+        .lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Writes the integration scripts under the config directory and returns how
+// to start `shell` with the session's `args` so it loads them. Unknown
+// shells run unmodified.
+pub async fn prepare_launch(shell: &str, args: &[String]) -> Result<ShellLaunch> {
+    let dir = Config::config_dir().join("shell-integration");
+    let name = Path::new(shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    let mut launch = ShellLaunch {
+        args: args.to_vec(),
+        environment: HashMap::new(),
+    };
+    match name {
+        "zsh" => {
+            let zdotdir = dir.join("zsh");
+            tokio::fs::create_dir_all(&zdotdir).await?;
+            let script = write_script(&dir, "hovershell.zsh", ZSH_SCRIPT).await?;
+
+            for wrapper in ZSH_WRAPPERS {
+                let mut content = format!(
+                    "[ -f \"$HOVERSHELL_USER_ZDOTDIR/{0}\" ] && ZDOTDIR=\"$HOVERSHELL_USER_ZDOTDIR\" . \"$HOVERSHELL_USER_ZDOTDIR/{0}\"\n",
+                    wrapper
+                );
+                if *wrapper == ".zshrc" {
+                    content.push_str(&format!(
+                        "ZDOTDIR=\"$HOVERSHELL_USER_ZDOTDIR\"\n. \"{}\"\n",
+                        script.display()
+                    ));
+                }
+                tokio::fs::write(zdotdir.join(wrapper), content).await?;
+            }
+
+            let user_zdotdir = std::env::var("ZDOTDIR").unwrap_or_else(|_| {
+                dirs::home_dir()
+                    .unwrap_or_else(|| PathBuf::from("."))
+                    .to_string_lossy()
+                    .to_string()
+            });
+            launch.environment.insert("HOVERSHELL_USER_ZDOTDIR".to_string(), user_zdotdir);
+            launch.environment.insert("ZDOTDIR".to_string(), zdotdir.to_string_lossy().to_string());
+        }
+        "bash" => {
+            let script = write_script(&dir, "hovershell.bash", BASH_SCRIPT).await?;
+            // bash ignores --rcfile in a login shell, so login sessions start
+            // as plain interactive shells and the script sources the login
+            // files itself
+            let login = |arg: &String| arg == "-l" || arg == "--login";
+            if launch.args.iter().any(login) {
+                launch.args.retain(|arg| !login(arg));
+                launch.environment.insert(BASH_LOGIN_VAR.to_string(), "1".to_string());
+            }
+            launch.args.splice(0..0, ["--rcfile".to_string(), script.to_string_lossy().to_string()]);
+        }
+        "fish" => {
+            let script = write_script(&dir, "hovershell.fish", FISH_SCRIPT).await?;
+            launch.args.splice(0..0, [
+                "--init-command".to_string(),
+                format!("source '{}'", script.to_string_lossy().replace('\'', "\\'")),
+            ]);
+        }
+        _ => {}
+    }
+
+    Ok(launch)
+}
+
+async fn write_script(dir: &Path, name: &str, content: &str) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(name);
+    tokio::fs::write(&path, content).await?;
+    Ok(path)
+}
+
+// Extracts the path from an OSC 7 `file://host/path` URL
+pub fn parse_file_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc(integration: &mut ShellIntegration, screen: &Emulator, sequence: &str, line: u64, col: usize) -> Option<CommandBlock> {
+        let params: Vec<String> = sequence.split(';').map(str::to_string).collect();
+        integration.handle_osc(&params, line, col, screen)
+    }
+
+    #[test]
+    fn keeps_the_command_line_as_typed() {
+        let mut screen = Emulator::new(80, 24, 100);
+        let mut integration = ShellIntegration::new();
+
+        osc(&mut integration, &screen, "133;A", 0, 0);
+        osc(&mut integration, &screen, "133;C;cmdline_url=%20export%20TOKEN%3Dabc", 0, 0);
+        let block = osc(&mut integration, &screen, "133;D;0", 1, 0).unwrap();
+        assert_eq!(block.command, " export TOKEN=abc");
+
+        // Without a reported command line it is read after the prompt
+        screen.process(b"$ ");
+        osc(&mut integration, &screen, "133;A", 0, 0);
+        osc(&mut integration, &screen, "133;B", 0, 2);
+        screen.process(b" vault login\r\n");
+        osc(&mut integration, &screen, "133;C", 1, 0);
+        let block = osc(&mut integration, &screen, "133;D;0", 1, 0).unwrap();
+        assert_eq!(block.command, " vault login");
+
+        assert_eq!(integration.history(), vec!["export TOKEN=abc", "vault login"]);
+    }
+}
@@ -11,6 +11,7 @@ use tokio::sync::RwLock;
 pub struct TrayManager {
     tray_menu: Option<SystemTrayMenu>,
     app_handle: Option<AppHandle>,
+    status: Option<String>,
 }
 
 impl TrayManager {
@@ -20,6 +21,7 @@ impl TrayManager {
         Ok(Self {
             tray_menu: None,
             app_handle: None,
+            status: None,
         })
     }
 
@@ -233,12 +235,28 @@ impl TrayManager {
         Ok(())
     }
 
+    // Shows a short status in the tray tooltip. An empty status restores the
+    // plain tooltip.
     pub async fn update_status(&mut self, status: &str) -> Result<()> {
-        // TODO: Update tray status
         info!("Updating tray status: {}", status);
+        self.status = (!status.is_empty()).then(|| status.to_string());
+
+        if let Some(app_handle) = &self.app_handle {
+            let tooltip = match &self.status {
+                Some(status) => format!("HoverShell - {}", status),
+                None => "HoverShell".to_string(),
+            };
+            app_handle.tray_handle()
+                .set_tooltip(&tooltip)
+                .map_err(|e| HoverShellError::Tray(format!("Failed to update tray status: {}", e)))?;
+        }
         Ok(())
     }
 
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
     pub async fn get_current_menu(&self) -> Option<&SystemTrayMenu> {
         self.tray_menu.as_ref()
     }
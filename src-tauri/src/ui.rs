@@ -585,10 +585,7 @@ impl UIManager {
 
     async fn load_custom_themes(&mut self) -> Result<()> {
         // TODO: Load custom themes from ~/.hovershell/themes/
-        let themes_dir = dirs::home_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join(".hovershell")
-            .join("themes");
+        let themes_dir = Config::config_dir().join("themes");
 
         if themes_dir.exists() {
             // TODO: Implement custom theme loading
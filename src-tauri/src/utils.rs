@@ -1,5 +1,6 @@
 use crate::error::{HoverShellError, Result};
 use log::{error, info};
+use regex::Regex;
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -14,9 +15,7 @@ pub use crypto_utils::*;
 pub use network_utils::*;
 
 pub fn get_config_dir() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".hovershell")
+    crate::config::Config::config_dir()
 }
 
 pub fn get_plugins_dir() -> PathBuf {
@@ -86,6 +85,55 @@ pub fn format_duration(seconds: u64) -> String {
     }
 }
 
+// Translates the glob syntax used by workspace and routing rules into an
+// anchored regex
+pub fn glob_to_regex(pattern: &str) -> std::result::Result<Regex, regex::Error> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    let mut in_group = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directories at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => regex.push('['),
+            ']' => regex.push(']'),
+            '{' => {
+                in_group = true;
+                regex.push_str("(?:");
+            }
+            '}' if in_group => {
+                in_group = false;
+                regex.push(')');
+            }
+            ',' if in_group => regex.push('|'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    Regex::new(&regex)
+}
+
+pub fn expand_home(path: &str) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => dirs::home_dir()
+            .map(|home| format!("{}{}", home.to_string_lossy(), rest))
+            .unwrap_or_else(|| path.to_string()),
+        _ => path.to_string(),
+    }
+}
+
 pub fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
@@ -421,4 +469,39 @@ pub fn camel_to_snake(s: &str) -> String {
     }
     
     result
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        glob_to_regex(pattern).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn glob_double_star_spans_directories() {
+        assert!(matches("**/*.rs", "main.rs"));
+        assert!(matches("**/*.rs", "src/terminal/main.rs"));
+        assert!(!matches("**/*.rs", "main.rsx"));
+        assert!(matches("src/**", "src/a/b.txt"));
+        assert!(!matches("*.rs", "src/main.rs"));
+    }
+
+    #[test]
+    fn glob_braces_are_alternatives() {
+        assert!(matches("*.{js,ts}", "index.js"));
+        assert!(matches("*.{js,ts}", "index.ts"));
+        assert!(!matches("*.{js,ts}", "index.py"));
+        assert!(matches("a,b", "a,b"));
+    }
+
+    #[test]
+    fn glob_question_mark_is_one_character() {
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file12.txt"));
+        assert!(!matches("file?.txt", "file.txt"));
+        assert!(!matches("file?.txt", "file/.txt"));
+        assert!(matches("v1.0", "v1.0"));
+        assert!(!matches("v1.0", "v1x0"));
+    }
+}
@@ -32,6 +32,20 @@ pub async fn write_file_bytes(path: &Path, content: &[u8]) -> Result<()> {
     Ok(())
 }
 
+// Writes to a temporary file next to `path` and renames it over, so a crash
+// mid-write can't leave a truncated file behind
+pub async fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut tmp_path = path.as_os_str().to_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    tokio::fs::write(&tmp_path, content).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
 pub async fn file_exists(path: &Path) -> bool {
     tokio::fs::metadata(path).await.is_ok()
 }
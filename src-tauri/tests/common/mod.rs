@@ -0,0 +1,43 @@
+// Helpers shared by the integration tests. Not every test file uses all of
+// them.
+#![allow(dead_code)]
+
+use hovershell::config::{Config, ProviderConfig};
+use std::path::PathBuf;
+use std::sync::Once;
+
+static CONFIG_DIR: Once = Once::new();
+
+// Usage, history, conversations and the shell integration scripts are
+// written under the config directory, so keep them out of the real one.
+// Call before anything reads the directory.
+pub fn isolate_config_dir() {
+    CONFIG_DIR.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("hovershell-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Config::set_config_dir(dir).unwrap();
+    });
+}
+
+pub fn fixture(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .to_string_lossy()
+        .to_string()
+}
+
+// An enabled mock provider; `settings` becomes its `config`
+pub fn mock(id: &str, settings: serde_json::Value) -> ProviderConfig {
+    ProviderConfig {
+        id: id.to_string(),
+        name: id.to_string(),
+        provider_type: "mock".to_string(),
+        base_url: None,
+        model: None,
+        api_key: None,
+        default: false,
+        enabled: true,
+        config: settings,
+    }
+}
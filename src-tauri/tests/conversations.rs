@@ -0,0 +1,100 @@
+// Conversation threads against a mock provider with a small context window
+
+mod common;
+
+use hovershell::config::{Config, ConversationOverflow, ConversationsConfig};
+use hovershell::conversations::{fit_context, send_message, Conversation, ConversationManager, ExportFormat};
+use hovershell::providers::{ProviderManager, RequestOptions};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use common::{fixture, isolate_config_dir, mock};
+
+async fn setup(overflow: ConversationOverflow) -> (Config, ProviderManager, RwLock<ConversationManager>) {
+    isolate_config_dir();
+
+    let mut provider = mock("mock", json!({ "fixture": fixture("mock_conversation.yaml"), "context_window": 250 }));
+    provider.default = true;
+    let config = Config {
+        providers: vec![provider],
+        conversations: ConversationsConfig {
+            overflow,
+            reserve_tokens: 50,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut providers = ProviderManager::new().await.unwrap();
+    providers.load_from_config(&config).await.unwrap();
+    let mut conversations = ConversationManager::new().await.unwrap();
+    conversations.initialize(&config).await.unwrap();
+    (config, providers, RwLock::new(conversations))
+}
+
+async fn ask(conversations: &RwLock<ConversationManager>, providers: &ProviderManager, id: &str, count: usize) {
+    for i in 0..count {
+        let question = format!("question {} {}", i, "detail ".repeat(40));
+        let answer = send_message(conversations, providers, id, &question, None, &RequestOptions::default()).await.unwrap();
+        assert_eq!(answer.content, format!("answer to {}", question));
+        assert_eq!(answer.provider_id.as_deref(), Some("mock"));
+        assert!(answer.usage.is_some());
+    }
+}
+
+#[tokio::test]
+async fn truncates_and_persists_threads() {
+    let (config, providers, conversations) = setup(ConversationOverflow::Truncate).await;
+    let id = conversations.write().await.create(None).await.unwrap().id;
+    conversations.write().await.append(&id, "system", "Answer briefly.", None, None).await.unwrap();
+    assert!(conversations.write().await.append(&id, "robot", "?", None, None).await.is_err());
+
+    ask(&conversations, &providers, &id, 4).await;
+
+    let conversation = conversations.read().await.get(&id).unwrap().clone();
+    assert_eq!(conversation.messages.len(), 9);
+    assert!(conversation.summary.is_none());
+    let fitted = fit_context(&conversation, 200);
+    assert_eq!(fitted.messages[0].content, "Answer briefly.");
+    assert!(!fitted.dropped.is_empty());
+    assert_eq!(fitted.messages.last().unwrap().role, "assistant");
+
+    let listed = conversations.read().await.list();
+    let info = listed.iter().find(|info| info.id == id).unwrap();
+    assert!(info.title.starts_with("question 0 detail"));
+    assert_eq!(info.message_count, 9);
+
+    // A fresh manager reads the thread back from disk
+    let reloaded = RwLock::new(ConversationManager::new().await.unwrap());
+    reloaded.write().await.initialize(&config).await.unwrap();
+    reloaded.write().await.rename(&id, "Long questions").await.unwrap();
+    assert_eq!(reloaded.read().await.get(&id).unwrap().display_title(), "Long questions");
+
+    reloaded.write().await.delete(&id).await.unwrap();
+    assert!(reloaded.read().await.get(&id).is_err());
+    assert!(reloaded.write().await.delete(&id).await.is_err());
+}
+
+#[tokio::test]
+async fn summarizes_and_exports() {
+    let (_, providers, conversations) = setup(ConversationOverflow::Summarize).await;
+    let id = conversations.write().await.create(Some("Summaries".to_string())).await.unwrap().id;
+
+    ask(&conversations, &providers, &id, 4).await;
+
+    let conversation = conversations.read().await.get(&id).unwrap().clone();
+    assert_eq!(conversation.summary.as_deref(), Some("The user asked several long questions."));
+    assert!(conversation.summarized_count > 0);
+    let fitted = fit_context(&conversation, 200);
+    assert_eq!(fitted.messages[0].role, "system");
+    assert!(fitted.messages[0].content.contains("The user asked several long questions."));
+
+    let markdown = conversations.read().await.export(&id, ExportFormat::Markdown).unwrap();
+    assert!(markdown.starts_with("# Summaries\n"));
+    assert_eq!(markdown.matches("\n## User · ").count(), 4);
+    assert_eq!(markdown.matches("· mock ·").count(), 4);
+
+    let exported = conversations.read().await.export(&id, ExportFormat::Json).unwrap();
+    let parsed: Conversation = serde_json::from_str(&exported).unwrap();
+    assert_eq!(parsed.messages.len(), 8);
+}
@@ -0,0 +1,343 @@
+// Exercises ProviderManager end to end against mock providers scripted by
+// the YAML files in tests/fixtures
+
+mod common;
+
+use futures_util::StreamExt;
+use hovershell::app::PanelVisibility;
+use hovershell::config::{Config, ProviderConfig, RoutingRule};
+use hovershell::providers::router::RouteQuery;
+use hovershell::providers::streaming::StreamChunk;
+use hovershell::providers::{ChatMessage, ProviderManager, RequestOptions};
+use hovershell::HoverShellError;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use common::{fixture, isolate_config_dir, mock};
+
+// "primary" is the default and "backup" the only fallback
+fn config() -> Config {
+    let mut primary = mock("primary", json!({ "fixture": fixture("mock_primary.yaml"), "context_window": 50 }));
+    primary.default = true;
+
+    let mut config = Config {
+        providers: vec![
+            primary,
+            mock("backup", json!({ "fixture": fixture("mock_backup.yaml") })),
+        ],
+        ..Default::default()
+    };
+    config.failover.initial_backoff_ms = 10;
+    config.failover.max_backoff_ms = 1000;
+    config.failover.fallback_chain = vec!["backup".to_string()];
+    config
+}
+
+async fn manager(config: &Config) -> ProviderManager {
+    isolate_config_dir();
+    let mut manager = ProviderManager::new().await.unwrap();
+    manager.load_from_config(config).await.unwrap();
+    manager
+}
+
+async fn collect(manager: &ProviderManager, prompt: &str, provider_id: Option<&str>) -> (Vec<String>, Option<HoverShellError>, u32) {
+    let mut stream = manager.stream(prompt, provider_id, &RequestOptions::default()).await.unwrap();
+    let mut text = Vec::new();
+    let mut tokens = 0;
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(StreamChunk::Text(chunk)) => text.push(chunk),
+            Ok(StreamChunk::Usage(usage)) => tokens += usage.total_tokens,
+            Err(e) => return (text, Some(e), tokens),
+        }
+    }
+    (text, None, tokens)
+}
+
+#[tokio::test]
+async fn answers_from_fixture() {
+    let manager = manager(&config()).await;
+    let options = RequestOptions::default();
+
+    let response = manager.execute("List files here", &options).await.unwrap();
+    assert_eq!(response.content, "ls -la");
+    let usage = response.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (12, 3, 15));
+    assert_eq!(response.metadata["provider_id"], "primary");
+    assert_eq!(response.metadata["attempts"], 1);
+
+    let response = manager.execute("anything else", &options).await.unwrap();
+    assert_eq!(response.content, "primary: anything else");
+
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: "hello".to_string(),
+        timestamp: chrono::Utc::now(),
+    }];
+    let response = manager.chat(messages, Some("backup"), &options).await.unwrap();
+    assert_eq!(response.content, "backup: hello");
+}
+
+#[tokio::test]
+async fn echoes_without_fixture() {
+    let mut config = config();
+    config.providers.push(mock("echo", json!({})));
+    let manager = manager(&config).await;
+
+    let response = manager.execute_with_provider("say this back", "echo", &RequestOptions::default()).await.unwrap();
+    assert_eq!(response.content, "say this back");
+    assert_eq!(manager.list_models(Some("echo")).await.unwrap(), vec!["mock"]);
+}
+
+#[tokio::test]
+async fn rejects_invalid_fixture() {
+    isolate_config_dir();
+    let mut manager = ProviderManager::new().await.unwrap();
+
+    let invalid = mock("invalid", json!({ "fixture": fixture("mock_invalid.yaml") }));
+    assert!(matches!(manager.add_provider_from_config(&invalid).await, Err(HoverShellError::Config(_))));
+
+    let missing = mock("missing", json!({ "fixture": fixture("does_not_exist.yaml") }));
+    assert!(matches!(manager.add_provider_from_config(&missing).await, Err(HoverShellError::Config(_))));
+}
+
+#[tokio::test]
+async fn routes_by_prefix_and_workspace() {
+    let mut config = config();
+    config.providers.push(ProviderConfig {
+        provider_type: "openai-compatible".to_string(),
+        base_url: Some("https://llm.example.com".to_string()),
+        ..mock("hosted", json!({}))
+    });
+    config.routing.rules = vec![
+        RoutingRule {
+            name: "backup-prefix".to_string(),
+            prefix: Some("@backup".to_string()),
+            provider: Some("backup".to_string()),
+            ..Default::default()
+        },
+        RoutingRule {
+            name: "hosted-prefix".to_string(),
+            prefix: Some("@hosted".to_string()),
+            provider: Some("hosted".to_string()),
+            ..Default::default()
+        },
+        RoutingRule {
+            name: "private".to_string(),
+            workspace: Some("/work/private".to_string()),
+            deny_tags: vec!["cloud".to_string()],
+            ..Default::default()
+        },
+    ];
+    let manager = manager(&config).await;
+
+    let response = manager.execute("@backup what is up", &RequestOptions::default()).await.unwrap();
+    assert_eq!(response.content, "backup: what is up");
+
+    // The hosted provider is tagged cloud, so the private workspace goes to
+    // the next usable candidate
+    let decision = manager.route(&RouteQuery {
+        prompt: "@hosted summarize".to_string(),
+        working_directory: Some("/work/private/app".to_string()),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(decision.provider_id, "primary");
+    assert_eq!(decision.prompt, "summarize");
+    assert_eq!(decision.matched_rules, vec!["hosted-prefix", "private"]);
+    assert_eq!(decision.skipped[0].provider_id, "hosted");
+
+    let requested = manager.route(&RouteQuery {
+        prompt: "summarize".to_string(),
+        provider_id: Some("hosted".to_string()),
+        working_directory: Some("/work/private".to_string()),
+        ..Default::default()
+    });
+    assert!(requested.is_err());
+}
+
+#[tokio::test]
+async fn routes_around_limits_and_capabilities() {
+    let mut config = config();
+    config.providers.push(mock("no-stream", json!({ "capabilities": ["text", "chat"] })));
+    let manager = manager(&config).await;
+
+    // About 100 tokens, over the primary's context window of 50
+    let long_prompt = "x".repeat(400);
+    let response = manager.execute(&long_prompt, &RequestOptions::default()).await.unwrap();
+    assert_eq!(response.metadata["provider_id"], "backup");
+
+    let decision = manager.route(&RouteQuery {
+        prompt: long_prompt,
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(decision.reason, "first usable fallback");
+    assert_eq!(decision.estimated_tokens, 100);
+
+    assert!(manager.stream("hi", Some("no-stream"), &RequestOptions::default()).await.is_err());
+}
+
+#[tokio::test]
+async fn retries_transient_errors() {
+    let manager = manager(&config()).await;
+
+    let response = manager.execute("flaky request", &RequestOptions::default()).await.unwrap();
+    assert_eq!(response.content, "recovered");
+    assert_eq!(response.metadata["provider_id"], "primary");
+    assert_eq!(response.metadata["attempts"], 2);
+    assert!(!response.metadata.contains_key("fallback_from"));
+}
+
+#[tokio::test]
+async fn falls_back_on_failure() {
+    let manager = manager(&config()).await;
+    let options = RequestOptions::default();
+
+    let response = manager.execute("locked out", &options).await.unwrap();
+    assert_eq!(response.content, "backup: locked out");
+    assert_eq!(response.metadata["attempts"], 2);
+    assert_eq!(response.metadata["fallback_from"], "primary");
+
+    // Retry-After is above max_backoff_ms, so there's no point waiting
+    let started = Instant::now();
+    let response = manager.execute("slow down", &options).await.unwrap();
+    assert_eq!(response.metadata["provider_id"], "backup");
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn returns_last_error_without_fallback() {
+    let mut config = config();
+    config.failover.fallback_chain.clear();
+    let manager = manager(&config).await;
+
+    let error = manager.execute("locked out", &RequestOptions::default()).await.unwrap_err();
+    match error {
+        HoverShellError::ProviderAuth { provider, message } => {
+            assert_eq!(provider, "primary");
+            assert_eq!(message, "Invalid API key");
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn streams_chunks_with_timing() {
+    let manager = manager(&config()).await;
+
+    let (text, error, tokens) = collect(&manager, "List files", None).await;
+    assert_eq!(text, vec!["ls", " -la"]);
+    assert!(error.is_none());
+    assert_eq!(tokens, 15);
+
+    let started = Instant::now();
+    let (text, _, _) = collect(&manager, "story time", None).await;
+    assert_eq!(text, vec!["once ", "upon ", "a ", "time"]);
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert!(manager.active_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn cancels_open_stream() {
+    let manager = manager(&config()).await;
+    let options = RequestOptions {
+        request_id: Some("story".to_string()),
+        ..Default::default()
+    };
+
+    let mut stream = manager.stream("story time", None, &options).await.unwrap();
+    assert!(matches!(stream.next().await, Some(Ok(StreamChunk::Text(_)))));
+    assert_eq!(manager.active_requests().await, vec!["story"]);
+
+    assert!(manager.requests().cancel("story").await);
+    let error = stream.next().await.unwrap().unwrap_err();
+    assert!(error.to_string().contains("cancelled"));
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn hiding_panel_cancels_requests() {
+    let manager = manager(&config()).await;
+    let panel = PanelVisibility::new(manager.requests());
+    panel.show();
+    let options = RequestOptions {
+        request_id: Some("hidden".to_string()),
+        ..Default::default()
+    };
+
+    let mut stream = manager.stream("story time", None, &options).await.unwrap();
+    assert!(matches!(stream.next().await, Some(Ok(StreamChunk::Text(_)))));
+
+    assert_eq!(panel.hide().await, 1);
+    assert!(!panel.is_visible());
+    let error = stream.next().await.unwrap().unwrap_err();
+    assert!(error.to_string().contains("cancelled"));
+    assert!(manager.active_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn forgets_dropped_stream() {
+    let manager = manager(&config()).await;
+    let options = RequestOptions {
+        request_id: Some("abandoned".to_string()),
+        ..Default::default()
+    };
+
+    let mut stream = manager.stream("story time", None, &options).await.unwrap();
+    assert!(matches!(stream.next().await, Some(Ok(StreamChunk::Text(_)))));
+    drop(stream);
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(manager.active_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn streams_fail_over_only_before_text() {
+    let manager = manager(&config()).await;
+
+    let (text, error, _) = collect(&manager, "locked out", None).await;
+    assert_eq!(text.concat(), "backup: locked out");
+    assert!(error.is_none());
+
+    let (text, error, tokens) = collect(&manager, "cut off", None).await;
+    assert_eq!(text, vec!["one ", "two "]);
+    assert!(matches!(error, Some(HoverShellError::ProviderServer { status: 502, .. })));
+    assert_eq!(tokens, 0);
+}
+
+#[tokio::test]
+async fn cancels_pending_request() {
+    let manager = Arc::new(RwLock::new(manager(&config()).await));
+    let requests = manager.read().await.requests();
+
+    let request = tokio::spawn({
+        let manager = manager.clone();
+        async move {
+            let options = RequestOptions {
+                request_id: Some("slow".to_string()),
+                ..Default::default()
+            };
+            manager.read().await.execute("wait for it", &options).await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // A writer queued behind the running call blocks new readers of the
+    // manager, but not the request handle
+    let writer = tokio::spawn({
+        let manager = manager.clone();
+        async move {
+            drop(manager.write().await);
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(requests.active().await, vec!["slow"]);
+    assert!(requests.cancel("slow").await);
+
+    let error = request.await.unwrap().unwrap_err();
+    assert!(error.to_string().contains("cancelled"));
+    writer.await.unwrap();
+    assert!(manager.read().await.active_requests().await.is_empty());
+}
@@ -0,0 +1,160 @@
+// Drives real shells through TerminalManager on a pseudo-terminal
+
+mod common;
+
+use hovershell::config::{TerminalConfig, TerminalProfile};
+use hovershell::terminal::profile::{self, ResolvedProfile};
+use hovershell::terminal::recording::playback_delay;
+use hovershell::terminal::shell_integration::{prepare_launch, BASH_LOGIN_VAR};
+use hovershell::terminal::TerminalManager;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use common::isolate_config_dir;
+
+fn sh_profile() -> ResolvedProfile {
+    ResolvedProfile {
+        name: None,
+        shell: "/bin/sh".to_string(),
+        args: Vec::new(),
+        login_shell: false,
+        environment: HashMap::new(),
+        working_directory: std::env::temp_dir().to_string_lossy().to_string(),
+    }
+}
+
+// Polls the transcript until a line contains `text`
+async fn wait_for_line(manager: &TerminalManager, session_id: &str, text: &str) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let output = manager.get_output(session_id, None, None).await.unwrap();
+        if output.lines.iter().any(|line| line.text.contains(text)) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resize_reaches_the_shell() {
+    let mut manager = TerminalManager::new().await.unwrap();
+    let id = manager.create_session(None, sh_profile()).await.unwrap();
+
+    manager.resize_terminal(&id, 132, 40).await.unwrap();
+    manager.write_input(&id, b"stty size\n").await.unwrap();
+    assert!(wait_for_line(&manager, &id, "40 132").await);
+
+    // A restarted shell comes back at the last reported size
+    manager.stop_shell_process(&id).await.unwrap();
+    manager.resize_terminal(&id, 100, 30).await.unwrap();
+    manager.start_shell_process(&id).await.unwrap();
+    manager.write_input(&id, b"stty size\n").await.unwrap();
+    assert!(wait_for_line(&manager, &id, "30 100").await);
+
+    assert!(manager.resize_terminal(&id, 0, 30).await.is_err());
+    manager.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close_does_not_wait_for_stubborn_shells() {
+    let mut manager = TerminalManager::new().await.unwrap();
+    let id = manager.create_session(None, sh_profile()).await.unwrap();
+
+    // Quoted so the typed line doesn't match before the trap is set
+    manager.write_input(&id, b"trap '' HUP TERM; echo tr''apped\n").await.unwrap();
+    assert!(wait_for_line(&manager, &id, "trapped").await);
+
+    // The shell outlives SIGTERM, but its termination runs in the background
+    let started = Instant::now();
+    manager.close_session(&id).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // Shutdown still waits for it to be killed
+    manager.shutdown().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn playback_speed_is_bounded() {
+    let mut manager = TerminalManager::new().await.unwrap();
+    for speed in [0.0, 0.01, f64::NAN] {
+        assert!(manager.play_recording(Path::new("missing.cast"), speed).await.is_err());
+    }
+
+    assert_eq!(playback_delay(1.0, 3.0, 2.0, None), Duration::from_secs(1));
+    assert_eq!(playback_delay(0.0, 30.0, 1.0, Some(2.5)), Duration::from_millis(2500));
+    assert_eq!(playback_delay(0.0, 1e300, 0.1, None), Duration::MAX);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn login_bash_keeps_integration() {
+    isolate_config_dir();
+    let home = std::env::temp_dir().join(format!("hovershell-bash-login-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    std::fs::write(home.join(".bash_profile"), "export FROM_PROFILE=yes\n").unwrap();
+
+    let mut config = TerminalConfig::default();
+    config.profiles.insert("login".to_string(), TerminalProfile {
+        shell: Some("bash".to_string()),
+        login_shell: Some(true),
+        environment: HashMap::from([("HOME".to_string(), home.to_string_lossy().to_string())]),
+        working_directory: Some(home.to_string_lossy().to_string()),
+        ..Default::default()
+    });
+    let resolved = profile::resolve(&config, &[], None, Some("login"));
+    assert_eq!(resolved.shell_args(), vec!["-l"]);
+
+    // --rcfile only works without -l, so the script reads the login files
+    let launch = prepare_launch(&resolved.shell, &resolved.shell_args()).await.unwrap();
+    assert_eq!(launch.args.len(), 2);
+    assert_eq!(launch.args[0], "--rcfile");
+    assert_eq!(launch.environment.get(BASH_LOGIN_VAR).map(String::as_str), Some("1"));
+
+    let mut manager = TerminalManager::new().await.unwrap();
+    let id = manager.create_session(None, resolved).await.unwrap();
+    manager.write_input(&id, b"echo \"$FROM_PROFILE:$HOVERSHELL_INTEGRATION_LOADED\"\n").await.unwrap();
+    assert!(wait_for_line(&manager, &id, "yes:1").await);
+
+    manager.shutdown().await.unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bash_reports_space_prefixed_commands() {
+    isolate_config_dir();
+    let home = std::env::temp_dir().join(format!("hovershell-bash-space-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    std::fs::write(home.join(".bashrc"), "HISTCONTROL=ignorespace\n").unwrap();
+
+    let mut profile = sh_profile();
+    profile.shell = "bash".to_string();
+    profile.environment.insert("HOME".to_string(), home.to_string_lossy().to_string());
+    let mut manager = TerminalManager::new().await.unwrap();
+    let id = manager.create_session(None, profile).await.unwrap();
+
+    // bash leaves the second line out of its history, so `history 1` still
+    // shows the first one
+    manager.write_input(&id, b"echo first\n").await.unwrap();
+    assert!(wait_for_line(&manager, &id, "first").await);
+    manager.write_input(&id, b" echo second\n").await.unwrap();
+    assert!(wait_for_line(&manager, &id, "second").await);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut commands = Vec::new();
+    while Instant::now() < deadline {
+        commands = manager.get_command_blocks(&id).await.unwrap().into_iter()
+            .filter(|block| block.is_finished())
+            .map(|block| block.command)
+            .collect::<Vec<_>>();
+        if commands.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(commands, vec!["echo first", " echo second"]);
+
+    manager.shutdown().await.unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+}
@@ -1,20 +1,26 @@
-import { useEffect, useState } from 'react';
+import React, { useCallback, useEffect, useState } from 'react';
+import { appWindow, LogicalSize } from '@tauri-apps/api/window';
 import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/tauri';
 import { Terminal } from './components/Terminal';
 import { Sidebar } from './components/Sidebar';
 import { StatusBar } from './components/StatusBar';
 import { CommandPalette } from './components/CommandPalette';
 import { useAppStore } from './stores/appStore';
-import { useTerminalStore } from './stores/terminalStore';
+import { useTerminalStore, FocusDirection } from './stores/terminalStore';
 import { useConfigStore } from './stores/configStore';
 import { useHotkeys } from 'react-hotkeys-hook';
 
+// Smallest panel height scroll-to-resize will shrink to, in logical pixels
+const MIN_PANEL_HEIGHT = 120;
+
 function App() {
   const [isVisible, setIsVisible] = useState(false);
   const [isCommandPaletteOpen, setIsCommandPaletteOpen] = useState(false);
   
   const { 
     loadConfig, 
+    config,
     currentTheme,
     applyTheme 
   } = useConfigStore();
@@ -24,13 +30,25 @@ function App() {
     activeTerminalId, 
     setActiveTerminal,
     createTerminal,
-    closeTerminal 
+    closeTerminal,
+    sendInput,
+    writeInput,
+    resizeTerminal,
+    splitPane,
+    focusPane,
+    subscribeToOutput,
+    subscribeToCwdChanges,
+    subscribeToSessionExits,
+    subscribeToJobUpdates,
+    loadJobs,
+    runBackgroundJob
   } = useTerminalStore();
   
   const { 
     loadProviders,
     removeProvider,
-    setDefaultProvider 
+    setDefaultProvider,
+    subscribeToStreams
   } = useAppStore();
 
   // Load initial data
@@ -47,6 +65,46 @@ function App() {
     initializeApp();
   }, []);
 
+  // Stream terminal output, directory changes, shell exits, background
+  // job updates and AI responses pushed by the backend
+  useEffect(() => {
+    const unlistenOutput = subscribeToOutput();
+    const unlistenCwd = subscribeToCwdChanges();
+    const unlistenExits = subscribeToSessionExits();
+    const unlistenJobs = subscribeToJobUpdates();
+    const unlistenStreams = subscribeToStreams();
+    loadJobs();
+
+    return () => {
+      unlistenOutput.then(fn => fn());
+      unlistenCwd.then(fn => fn());
+      unlistenExits.then(fn => fn());
+      unlistenJobs.then(fn => fn());
+      unlistenStreams.then(fns => fns.forEach(fn => fn()));
+    };
+  }, []);
+
+  // Showing and hiding go through the backend, which hides the native
+  // window and cancels AI requests nobody is left to read
+  const hidePanel = useCallback(async () => {
+    setIsVisible(false);
+    try {
+      await invoke('hide_window');
+    } catch (error) {
+      console.error('Failed to hide window:', error);
+    }
+  }, []);
+
+  const togglePanel = useCallback(async () => {
+    try {
+      const visible = await appWindow.isVisible();
+      setIsVisible(!visible);
+      await invoke(visible ? 'hide_window' : 'show_window');
+    } catch (error) {
+      console.error('Failed to toggle window:', error);
+    }
+  }, []);
+
   // Listen for hotkey events
   useEffect(() => {
     const unlisten = listen('hotkey-triggered', (event) => {
@@ -64,6 +122,13 @@ function App() {
     setIsCommandPaletteOpen(true);
   });
 
+  // Ctrl-R style recall across all sessions' history. Ctrl-R itself goes to
+  // the shell for its own reverse search, so this uses Cmd-R, which the
+  // terminal doesn't forward (and which would otherwise reload the webview).
+  useHotkeys('cmd+r', () => {
+    setIsCommandPaletteOpen(true);
+  }, { preventDefault: true });
+
   useHotkeys('cmd+t', () => {
     createTerminal();
   });
@@ -90,21 +155,59 @@ function App() {
     }
   });
 
+  // Pane bindings only fire while the panel has focus; the backend leaves
+  // them out of the global shortcuts so other apps keep keys like Ctrl+D
+  const paneHotkeyOptions = { enableOnFormTags: true, preventDefault: true };
+  const paneHotkeyDeps = [activeTerminalId, splitPane, focusPane];
+  const paneHotkeys = config?.triggers.hotkeys;
+
+  useHotkeys(paneHotkeys?.split_horizontal ?? 'cmd+d', () => {
+    handleHotkeyCallback('split_horizontal');
+  }, paneHotkeyOptions, paneHotkeyDeps);
+
+  useHotkeys(paneHotkeys?.split_vertical ?? 'cmd+shift+d', () => {
+    handleHotkeyCallback('split_vertical');
+  }, paneHotkeyOptions, paneHotkeyDeps);
+
+  useHotkeys(paneHotkeys?.focus_pane_left ?? 'cmd+alt+left', () => {
+    handleHotkeyCallback('focus_pane_left');
+  }, paneHotkeyOptions, paneHotkeyDeps);
+
+  useHotkeys(paneHotkeys?.focus_pane_right ?? 'cmd+alt+right', () => {
+    handleHotkeyCallback('focus_pane_right');
+  }, paneHotkeyOptions, paneHotkeyDeps);
+
+  useHotkeys(paneHotkeys?.focus_pane_up ?? 'cmd+alt+up', () => {
+    handleHotkeyCallback('focus_pane_up');
+  }, paneHotkeyOptions, paneHotkeyDeps);
+
+  useHotkeys(paneHotkeys?.focus_pane_down ?? 'cmd+alt+down', () => {
+    handleHotkeyCallback('focus_pane_down');
+  }, paneHotkeyOptions, paneHotkeyDeps);
+
+  // Escape belongs to the shell (vim, less, readline) while the terminal
+  // has focus; elsewhere it closes the palette or hides the panel
   useHotkeys('escape', () => {
-    setIsCommandPaletteOpen(false);
-    setIsVisible(false);
-  });
+    if (document.activeElement?.closest('.terminal')) {
+      return;
+    }
+    if (isCommandPaletteOpen) {
+      setIsCommandPaletteOpen(false);
+    } else {
+      hidePanel();
+    }
+  }, [isCommandPaletteOpen, hidePanel]);
 
   const handleHotkeyCallback = (callback: string) => {
     switch (callback) {
       case 'toggle_window':
-        setIsVisible(!isVisible);
+        togglePanel();
         break;
       case 'paste_run':
         // TODO: Implement paste and run
         break;
       case 'quick_hide':
-        setIsVisible(false);
+        hidePanel();
         break;
       case 'new_tab':
         createTerminal();
@@ -128,6 +231,20 @@ function App() {
           setActiveTerminal(terminals[prevIndex].id);
         }
         break;
+      case 'split_horizontal':
+      case 'split_vertical':
+        if (activeTerminalId) {
+          splitPane(activeTerminalId, callback === 'split_horizontal' ? 'horizontal' : 'vertical');
+        }
+        break;
+      case 'focus_pane_left':
+      case 'focus_pane_right':
+      case 'focus_pane_up':
+      case 'focus_pane_down':
+        if (activeTerminalId) {
+          focusPane(activeTerminalId, callback.replace('focus_pane_', '') as FocusDirection);
+        }
+        break;
       default:
         console.log('Unknown hotkey callback:', callback);
     }
@@ -147,6 +264,17 @@ function App() {
             closeTerminal(activeTerminalId);
           }
           break;
+        case 'history.run':
+          if (activeTerminalId && args[0]) {
+            sendInput(activeTerminalId, args[0]);
+          }
+          break;
+        case 'job.run':
+          if (args[0]) {
+            const cwd = terminals.find(t => t.id === activeTerminalId)?.working_directory;
+            runBackgroundJob(args[0], cwd);
+          }
+          break;
         case 'config.theme':
           if (args[0]) {
             applyTheme(args[0]);
@@ -173,6 +301,29 @@ function App() {
     }
   };
 
+  // Kept stable so the terminal's resize observer isn't recreated on
+  // every render
+  const handleTerminalResize = useCallback((cols: number, rows: number) => {
+    const terminalId = activeTerminalId || terminals[0]?.id;
+    if (terminalId) {
+      resizeTerminal(terminalId, cols, rows);
+    }
+  }, [activeTerminalId, terminals[0]?.id, resizeTerminal]);
+
+  // Scrolling over the panel's bottom edge grows or shrinks the panel; the
+  // terminal then reports its new grid size through onResize
+  const handleEdgeWheel = useCallback(async (e: React.WheelEvent) => {
+    const edges = config?.triggers.edges;
+    if (!edges?.scroll_resize) {
+      return;
+    }
+
+    const scale = await appWindow.scaleFactor();
+    const size = (await appWindow.innerSize()).toLogical(scale);
+    const height = Math.max(MIN_PANEL_HEIGHT, size.height + e.deltaY * edges.sensitivity);
+    await appWindow.setSize(new LogicalSize(size.width, height));
+  }, [config]);
+
   if (!isVisible) {
     return null;
   }
@@ -189,10 +340,11 @@ function App() {
           {terminals.length > 0 ? (
             <Terminal 
               terminal={terminals.find(t => t.id === activeTerminalId) || terminals[0]}
-              onInput={(input) => {
-                // TODO: Handle terminal input
-                console.log('Terminal input:', input);
+              onInput={(data) => {
+                const terminal = terminals.find(t => t.id === activeTerminalId) || terminals[0];
+                writeInput(terminal.id, data);
               }}
+              onResize={handleTerminalResize}
             />
           ) : (
             <div className="flex-1 flex items-center justify-center">
@@ -214,6 +366,9 @@ function App() {
       {/* Status bar */}
       <StatusBar />
       
+      {/* Bottom edge, for scroll-to-resize */}
+      <div className="h-1 cursor-ns-resize" onWheel={handleEdgeWheel} />
+      
       {/* Command palette */}
       {isCommandPaletteOpen && (
         <CommandPalette
@@ -20,8 +20,10 @@ import {
   ArrowUpDown,
   Hash,
   Download,
-  FileDiff
+  FileDiff,
+  History
 } from 'lucide-react';
+import { useTerminalStore } from '../stores/terminalStore';
 
 interface Command {
   id: string;
@@ -43,6 +45,7 @@ export const CommandPalette: React.FC<CommandPaletteProps> = ({ onClose, onComma
   const [selectedIndex, setSelectedIndex] = useState(0);
   const [commands, setCommands] = useState<Command[]>([]);
   const inputRef = useRef<HTMLInputElement>(null);
+  const { searchHistory } = useTerminalStore();
 
   const allCommands: Command[] = [
     // Terminal commands
@@ -374,18 +377,57 @@ export const CommandPalette: React.FC<CommandPaletteProps> = ({ onClose, onComma
     },
   ];
 
-  // Filter commands based on query
+  // Filter commands based on query, followed by matching commands from the
+  // shell history of every session
   useEffect(() => {
     if (!query.trim()) {
       setCommands(allCommands);
-    } else {
-      const filtered = allCommands.filter(command => {
-        const searchText = `${command.title} ${command.description} ${command.keywords.join(' ')}`.toLowerCase();
-        return searchText.includes(query.toLowerCase());
-      });
-      setCommands(filtered);
+      setSelectedIndex(0);
+      return;
     }
+
+    const filtered = allCommands.filter(command => {
+      const searchText = `${command.title} ${command.description} ${command.keywords.join(' ')}`.toLowerCase();
+      return searchText.includes(query.toLowerCase());
+    });
+    filtered.push({
+      id: 'job.run',
+      title: `Run in Background: ${query.trim()}`,
+      description: 'Run the command detached and notify when it finishes',
+      category: 'Jobs',
+      icon: Activity,
+      keywords: [],
+      action: () => onCommand('job.run', [query.trim()]),
+    });
+    setCommands(filtered);
     setSelectedIndex(0);
+
+    let cancelled = false;
+    const timer = setTimeout(async () => {
+      const history = await searchHistory(query, { limit: 20 });
+      if (cancelled) {
+        return;
+      }
+      const historyCommands: Command[] = history.map(entry => ({
+        id: `history.${entry.command}`,
+        title: entry.command,
+        description: [
+          `Run ${entry.count} time${entry.count !== 1 ? 's' : ''}`,
+          entry.working_directory,
+          entry.git_branch,
+        ].filter(Boolean).join(' · '),
+        category: 'History',
+        icon: History,
+        keywords: [],
+        action: () => onCommand('history.run', [entry.command]),
+      }));
+      setCommands([...filtered, ...historyCommands]);
+    }, 100);
+
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
   }, [query]);
 
   // Focus input on mount
@@ -1,24 +1,92 @@
-import React, { useRef, useEffect, useState } from 'react';
-import { TerminalSession } from '../stores/terminalStore';
+import React, { useRef, useEffect, memo } from 'react';
+import { TerminalSession, useTerminalStore } from '../stores/terminalStore';
+import { emptyBuffer } from '../utils/terminalOutput';
 
 interface TerminalProps {
   terminal: TerminalSession;
   onInput: (input: string) => void;
+  onResize?: (cols: number, rows: number) => void;
 }
 
-export const Terminal: React.FC<TerminalProps> = ({ terminal, onInput }) => {
+// Approximate cell metrics for the terminal font (14px, line-height 1.4)
+const CELL_WIDTH = 8.4;
+const CELL_HEIGHT = 19.6;
+
+// Bytes a key sends to the shell, as an xterm would
+const KEY_SEQUENCES: Record<string, string> = {
+  Enter: '\r',
+  Backspace: '\x7f',
+  Tab: '\t',
+  Escape: '\x1b',
+  ArrowUp: '\x1b[A',
+  ArrowDown: '\x1b[B',
+  ArrowRight: '\x1b[C',
+  ArrowLeft: '\x1b[D',
+  Home: '\x1b[H',
+  End: '\x1b[F',
+  Delete: '\x1b[3~',
+  PageUp: '\x1b[5~',
+  PageDown: '\x1b[6~',
+};
+
+const keyToInput = (e: React.KeyboardEvent): string | null => {
+  if (e.metaKey) {
+    return null;
+  }
+
+  if (e.ctrlKey && e.key.length === 1) {
+    const code = e.key.toUpperCase().charCodeAt(0);
+    // Ctrl-A..Ctrl-Z and Ctrl-[ \ ] ^ _ map to control characters
+    return code >= 64 && code <= 95 ? String.fromCharCode(code - 64) : null;
+  }
+
+  const sequence = KEY_SEQUENCES[e.key];
+  if (sequence) {
+    return e.altKey ? '\x1b' + sequence : sequence;
+  }
+
+  if (e.key.length === 1) {
+    return e.altKey ? '\x1b' + e.key : e.key;
+  }
+
+  return null;
+};
+
+// Lines only re-render when their own text changes, not on every chunk
+const TerminalLine = memo(({ text }: { text: string }) => (
+  <div className="terminal-line">{text}</div>
+));
+
+const EMPTY_BUFFER = emptyBuffer();
+
+export const Terminal: React.FC<TerminalProps> = ({ terminal, onInput, onResize }) => {
   const terminalRef = useRef<HTMLDivElement>(null);
-  const [input, setInput] = useState('');
-  const [cursorPosition, setCursorPosition] = useState(0);
-  const [commandHistory, setCommandHistory] = useState<string[]>([]);
-  const [historyIndex, setHistoryIndex] = useState(-1);
+  const buffer = useTerminalStore(state => state.buffers[terminal.id]) ?? EMPTY_BUFFER;
 
   // Auto-scroll to bottom when output changes
   useEffect(() => {
     if (terminalRef.current) {
       terminalRef.current.scrollTop = terminalRef.current.scrollHeight;
     }
-  }, [terminal.output]);
+  }, [buffer]);
+
+  // Report the grid size whenever the panel is resized
+  useEffect(() => {
+    const element = terminalRef.current;
+    if (!element || !onResize) {
+      return;
+    }
+
+    const observer = new ResizeObserver(entries => {
+      const { width, height } = entries[0].contentRect;
+      const cols = Math.max(1, Math.floor(width / CELL_WIDTH));
+      const rows = Math.max(1, Math.floor(height / CELL_HEIGHT));
+      onResize(cols, rows);
+    });
+
+    observer.observe(element);
+    return () => observer.disconnect();
+  }, [terminal.id, onResize]);
 
   // Focus terminal on mount
   useEffect(() => {
@@ -27,143 +95,38 @@ export const Terminal: React.FC<TerminalProps> = ({ terminal, onInput }) => {
     }
   }, [terminal.id]);
 
+  // The shell echoes what it receives, so nothing is edited locally. Keys
+  // that go to the shell stop here so app hotkeys (Escape, Ctrl-R) don't
+  // fire as well.
   const handleKeyDown = (e: React.KeyboardEvent) => {
-    switch (e.key) {
-      case 'Enter':
-        e.preventDefault();
-        if (input.trim()) {
-          onInput(input);
-          setCommandHistory(prev => [...prev, input]);
-          setHistoryIndex(-1);
-          setInput('');
-          setCursorPosition(0);
-        }
-        break;
-      
-      case 'ArrowUp':
-        e.preventDefault();
-        if (commandHistory.length > 0) {
-          const newIndex = historyIndex === -1 
-            ? commandHistory.length - 1 
-            : Math.max(0, historyIndex - 1);
-          setHistoryIndex(newIndex);
-          setInput(commandHistory[newIndex]);
-          setCursorPosition(commandHistory[newIndex].length);
-        }
-        break;
-      
-      case 'ArrowDown':
-        e.preventDefault();
-        if (historyIndex !== -1) {
-          const newIndex = historyIndex + 1;
-          if (newIndex >= commandHistory.length) {
-            setHistoryIndex(-1);
-            setInput('');
-            setCursorPosition(0);
-          } else {
-            setHistoryIndex(newIndex);
-            setInput(commandHistory[newIndex]);
-            setCursorPosition(commandHistory[newIndex].length);
-          }
-        }
-        break;
-      
-      case 'ArrowLeft':
-        e.preventDefault();
-        setCursorPosition(prev => Math.max(0, prev - 1));
-        break;
-      
-      case 'ArrowRight':
-        e.preventDefault();
-        setCursorPosition(prev => Math.min(input.length, prev + 1));
-        break;
-      
-      case 'Home':
-        e.preventDefault();
-        setCursorPosition(0);
-        break;
-      
-      case 'End':
-        e.preventDefault();
-        setCursorPosition(input.length);
-        break;
-      
-      case 'Backspace':
-        e.preventDefault();
-        if (cursorPosition > 0) {
-          const newInput = input.slice(0, cursorPosition - 1) + input.slice(cursorPosition);
-          setInput(newInput);
-          setCursorPosition(cursorPosition - 1);
-        }
-        break;
-      
-      case 'Delete':
-        e.preventDefault();
-        if (cursorPosition < input.length) {
-          const newInput = input.slice(0, cursorPosition) + input.slice(cursorPosition + 1);
-          setInput(newInput);
-        }
-        break;
-      
-      case 'Tab':
-        e.preventDefault();
-        // TODO: Implement tab completion
-        break;
-      
-      case 'c':
-        if (e.metaKey || e.ctrlKey) {
-          e.preventDefault();
-          // TODO: Implement copy
+    if ((e.metaKey || e.ctrlKey) && e.key === 'v') {
+      e.preventDefault();
+      e.stopPropagation();
+      navigator.clipboard.readText().then(text => {
+        if (text) {
+          onInput(text);
         }
-        break;
-      
-      case 'v':
-        if (e.metaKey || e.ctrlKey) {
-          e.preventDefault();
-          // TODO: Implement paste
-        }
-        break;
-      
-      default:
-        if (e.key.length === 1 && !e.metaKey && !e.ctrlKey && !e.altKey) {
-          e.preventDefault();
-          const newInput = input.slice(0, cursorPosition) + e.key + input.slice(cursorPosition);
-          setInput(newInput);
-          setCursorPosition(cursorPosition + 1);
-        }
-        break;
+      });
+      return;
     }
-  };
 
-  const handleClick = () => {
-    if (terminalRef.current) {
-      terminalRef.current.focus();
+    if (e.metaKey && e.key === 'c') {
+      // TODO: Implement copy
+      return;
     }
-  };
 
-  const formatOutput = (output: string) => {
-    return output.split('\n').map((line, index) => (
-      <div key={index} className="terminal-line">
-        {line}
-      </div>
-    ));
+    const data = keyToInput(e);
+    if (data !== null) {
+      e.preventDefault();
+      e.stopPropagation();
+      onInput(data);
+    }
   };
 
-  const renderCursor = () => {
-    if (input.length === 0) {
-      return <span className="cursor">█</span>;
+  const handleClick = () => {
+    if (terminalRef.current) {
+      terminalRef.current.focus();
     }
-    
-    const beforeCursor = input.slice(0, cursorPosition);
-    const afterCursor = input.slice(cursorPosition);
-    
-    return (
-      <>
-        {beforeCursor}
-        <span className="cursor">█</span>
-        {afterCursor}
-      </>
-    );
   };
 
   return (
@@ -176,20 +139,9 @@ export const Terminal: React.FC<TerminalProps> = ({ terminal, onInput }) => {
     >
       {/* Terminal output */}
       <div className="terminal-output mb-2">
-        {formatOutput(terminal.output)}
-      </div>
-      
-      {/* Current working directory */}
-      <div className="terminal-prompt mb-2">
-        <span className="text-primary-color">user@host</span>
-        <span className="text-secondary-color">:</span>
-        <span className="text-accent-color">{terminal.working_directory}</span>
-        <span className="text-secondary-color">$</span>
-      </div>
-      
-      {/* Input line */}
-      <div className="terminal-input">
-        {renderCursor()}
+        {buffer.lines.map((line, offset) => (
+          <TerminalLine key={buffer.firstIndex + offset} text={line} />
+        ))}
       </div>
       
       <style>{`
@@ -205,30 +157,6 @@ export const Terminal: React.FC<TerminalProps> = ({ terminal, onInput }) => {
           white-space: pre-wrap;
           word-break: break-all;
         }
-        
-        .terminal-prompt {
-          font-weight: bold;
-        }
-        
-        .terminal-input {
-          display: inline-block;
-          min-width: 1ch;
-        }
-        
-        .cursor {
-          background-color: var(--fg-color);
-          color: var(--bg-color);
-          animation: blink 1s infinite;
-        }
-        
-        @keyframes blink {
-          0%, 50% { opacity: 1; }
-          51%, 100% { opacity: 0; }
-        }
-        
-        .terminal:focus .cursor {
-          animation: blink 1s infinite;
-        }
       `}</style>
     </div>
   );
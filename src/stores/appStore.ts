@@ -1,5 +1,6 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
 
 export interface Provider {
   id: string;
@@ -30,22 +31,199 @@ export interface WorkspaceInfo {
   language?: string;
 }
 
+export interface StreamChunkEvent {
+  stream_id: string;
+  index: number;
+  content: string;
+}
+
+export interface UsageInfo {
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  cost: number | null;
+}
+
+// What execute_command returns. The metadata names the provider that
+// answered (`provider_id`), how many `attempts` it took and, after a
+// fallback, which provider failed first (`fallback_from`).
+export interface ProviderResponse {
+  content: string;
+  usage: UsageInfo | null;
+  metadata: Record<string, any>;
+}
+
+// Command errors arrive as `{ Variant: payload }`. The provider ones carry
+// enough detail for the UI to react to them.
+export type ProviderError =
+  | { ProviderAuth: { provider: string; message: string } }
+  | { RateLimited: { provider: string; message: string; retry_after: number | null } }
+  | { ContextTooLong: { provider: string; message: string } }
+  | { ModelNotFound: { provider: string; message: string } }
+  | { ProviderServer: { provider: string; status: number; message: string } };
+
+export type CommandError = ProviderError | { [kind: string]: string };
+
+const PROVIDER_ERROR_KINDS = ['ProviderAuth', 'RateLimited', 'ContextTooLong', 'ModelNotFound', 'ProviderServer'];
+
+export const asProviderError = (error: unknown): ProviderError | null => {
+  if (!error || typeof error !== 'object') return null;
+  const kind = Object.keys(error)[0];
+  return PROVIDER_ERROR_KINDS.includes(kind) ? error as ProviderError : null;
+};
+
+export const describeError = (error: unknown): string => {
+  if (!error || typeof error !== 'object') return String(error);
+  const [kind, detail] = Object.entries(error)[0] ?? [];
+  if (typeof detail === 'string') return `${kind}: ${detail}`;
+  if (detail && typeof detail === 'object' && 'message' in detail) return `${kind}: ${detail.message}`;
+  return JSON.stringify(error);
+};
+
+export interface StreamEndEvent {
+  stream_id: string;
+  provider_id: string | null;
+  error: CommandError | null;
+  cancelled: boolean;
+  usage: UsageInfo | null;
+}
+
+export interface UsageRecord {
+  date: string;
+  provider_id: string;
+  requests: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  cost: number;
+}
+
+export interface UsageQuery {
+  provider_id?: string;
+  from?: string;
+  to?: string;
+}
+
+export interface ProviderSpend {
+  provider_id: string;
+  requests: number;
+  total_tokens: number;
+  cost: number;
+  budget: number | null;
+}
+
+export interface UsageSummary {
+  month: string;
+  requests: number;
+  total_tokens: number;
+  cost: number;
+  budget: number | null;
+  remaining: number | null;
+  providers: ProviderSpend[];
+}
+
+export interface RequestOptions {
+  request_id?: string;
+  scope?: string;
+  working_directory?: string;
+}
+
+export interface RouteQuery {
+  prompt: string;
+  context?: string;
+  provider_id?: string;
+  capability?: 'text' | 'chat' | 'streaming';
+  working_directory?: string;
+}
+
+export interface RouteDecision {
+  provider_id: string;
+  reason: string;
+  fallbacks: string[];
+  prompt: string;
+  estimated_tokens: number;
+  matched_rules: string[];
+  skipped: { provider_id: string; reason: string }[];
+}
+
+export interface ConversationMessage {
+  id: string;
+  role: 'system' | 'user' | 'assistant';
+  content: string;
+  timestamp: string;
+  provider_id: string | null;
+  usage: UsageInfo | null;
+}
+
+export interface Conversation {
+  id: string;
+  title: string | null;
+  created_at: string;
+  updated_at: string;
+  messages: ConversationMessage[];
+  summary: string | null;
+  summarized_count: number;
+}
+
+export interface ConversationInfo {
+  id: string;
+  title: string;
+  created_at: string;
+  updated_at: string;
+  message_count: number;
+  total_tokens: number;
+  last_provider_id: string | null;
+}
+
+export type ExportFormat = 'markdown' | 'json';
+
+export interface AIStream {
+  id: string;
+  providerId: string | null;
+  content: string;
+  done: boolean;
+  cancelled: boolean;
+  error: CommandError | null;
+  usage: UsageInfo | null;
+}
+
 interface AppState {
   providers: Provider[];
   defaultProvider: string | null;
   systemInfo: SystemInfo | null;
   workspaceInfo: WorkspaceInfo | null;
+  streams: Record<string, AIStream>;
+  pendingRequests: string[];
+  usageSummary: UsageSummary | null;
+  conversations: ConversationInfo[];
+  activeConversation: Conversation | null;
   isLoading: boolean;
   error: string | null;
+  providerError: ProviderError | null;
   
   // Actions
   loadProviders: () => Promise<void>;
+  listModels: (providerId?: string) => Promise<string[]>;
   addProvider: (provider: Omit<Provider, 'id'>) => Promise<void>;
   removeProvider: (id: string) => Promise<void>;
   setDefaultProvider: (id: string) => Promise<void>;
   getSystemInfo: () => Promise<void>;
   getWorkspaceInfo: (path: string) => Promise<void>;
-  executeCommand: (command: string, providerId?: string) => Promise<string>;
+  executeCommand: (command: string, providerId?: string, options?: RequestOptions) => Promise<ProviderResponse>;
+  streamResponse: (prompt: string, context?: string, providerId?: string, options?: RequestOptions) => Promise<string | null>;
+  cancelRequest: (requestId: string) => Promise<void>;
+  explainRoute: (query: RouteQuery) => Promise<RouteDecision | null>;
+  loadUsageSummary: () => Promise<void>;
+  getUsage: (query?: UsageQuery) => Promise<UsageRecord[]>;
+  loadConversations: () => Promise<void>;
+  createConversation: (title?: string) => Promise<Conversation | null>;
+  openConversation: (conversationId: string) => Promise<void>;
+  renameConversation: (conversationId: string, title: string) => Promise<void>;
+  deleteConversation: (conversationId: string) => Promise<void>;
+  sendConversationMessage: (content: string, providerId?: string, options?: RequestOptions) => Promise<ConversationMessage | null>;
+  exportConversation: (conversationId: string, format: ExportFormat, filePath?: string) => Promise<string | null>;
+  clearStream: (streamId: string) => void;
+  subscribeToStreams: () => Promise<UnlistenFn[]>;
   clearError: () => void;
 }
 
@@ -54,8 +232,14 @@ export const useAppStore = create<AppState>((set, get) => ({
   defaultProvider: null,
   systemInfo: null,
   workspaceInfo: null,
+  streams: {},
+  pendingRequests: [],
+  usageSummary: null,
+  conversations: [],
+  activeConversation: null,
   isLoading: false,
   error: null,
+  providerError: null,
 
   loadProviders: async () => {
     set({ isLoading: true, error: null });
@@ -122,17 +306,211 @@ export const useAppStore = create<AppState>((set, get) => ({
     }
   },
 
-  executeCommand: async (command, providerId) => {
-    set({ isLoading: true, error: null });
+  executeCommand: async (command, providerId, options) => {
+    // The ID is picked here so the request can be cancelled while it runs
+    const requestId = options?.request_id || crypto.randomUUID();
+    set(state => ({ isLoading: true, error: null, providerError: null, pendingRequests: [...state.pendingRequests, requestId] }));
+    const settle = () => set(state => ({
+      pendingRequests: state.pendingRequests.filter(id => id !== requestId)
+    }));
     try {
-      const result = await invoke<string>('execute_command', { command, providerId });
+      const result = await invoke<ProviderResponse>('execute_command', {
+        command,
+        providerId,
+        options: { ...options, request_id: requestId }
+      });
+      settle();
       set({ isLoading: false });
       return result;
     } catch (error) {
-      set({ error: error as string, isLoading: false });
+      settle();
+      set({ error: describeError(error), providerError: asProviderError(error), isLoading: false });
       throw error;
     }
   },
 
-  clearError: () => set({ error: null }),
+  streamResponse: async (prompt, context, providerId, options) => {
+    set({ error: null, providerError: null });
+    try {
+      const id = await invoke<string>('stream_ai_response', { prompt, context, providerId, options });
+      set(state => ({
+        streams: {
+          ...state.streams,
+          // Chunks may arrive before the command returns
+          [id]: state.streams[id] || { id, providerId: null, content: '', done: false, cancelled: false, error: null, usage: null }
+        }
+      }));
+      return id;
+    } catch (error) {
+      set({ error: describeError(error), providerError: asProviderError(error) });
+      return null;
+    }
+  },
+
+  cancelRequest: async (requestId) => {
+    try {
+      await invoke<boolean>('cancel_ai_request', { requestId });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  listModels: async (providerId) => {
+    try {
+      return await invoke<string[]>('list_provider_models', { providerId });
+    } catch (error) {
+      set({ error: describeError(error) });
+      return [];
+    }
+  },
+
+  explainRoute: async (query) => {
+    try {
+      return await invoke<RouteDecision>('explain_ai_route', { query });
+    } catch (error) {
+      set({ error: describeError(error) });
+      return null;
+    }
+  },
+
+  loadUsageSummary: async () => {
+    try {
+      const usageSummary = await invoke<UsageSummary>('get_ai_usage_summary');
+      set({ usageSummary });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  getUsage: async (query) => {
+    try {
+      return await invoke<UsageRecord[]>('get_ai_usage', { query });
+    } catch (error) {
+      set({ error: error as string });
+      return [];
+    }
+  },
+
+  loadConversations: async () => {
+    try {
+      const conversations = await invoke<ConversationInfo[]>('list_conversations');
+      set({ conversations });
+    } catch (error) {
+      set({ error: describeError(error) });
+    }
+  },
+
+  createConversation: async (title) => {
+    try {
+      const conversation = await invoke<Conversation>('create_conversation', { title });
+      set({ activeConversation: conversation });
+      await get().loadConversations();
+      return conversation;
+    } catch (error) {
+      set({ error: describeError(error) });
+      return null;
+    }
+  },
+
+  openConversation: async (conversationId) => {
+    try {
+      const conversation = await invoke<Conversation>('get_conversation', { conversationId });
+      set({ activeConversation: conversation });
+    } catch (error) {
+      set({ error: describeError(error) });
+    }
+  },
+
+  renameConversation: async (conversationId, title) => {
+    try {
+      await invoke('rename_conversation', { conversationId, title });
+      if (get().activeConversation?.id === conversationId) {
+        await get().openConversation(conversationId);
+      }
+      await get().loadConversations();
+    } catch (error) {
+      set({ error: describeError(error) });
+    }
+  },
+
+  deleteConversation: async (conversationId) => {
+    try {
+      await invoke('delete_conversation', { conversationId });
+      set(state => ({
+        activeConversation: state.activeConversation?.id === conversationId ? null : state.activeConversation
+      }));
+      await get().loadConversations();
+    } catch (error) {
+      set({ error: describeError(error) });
+    }
+  },
+
+  // Sends to the open conversation; the question and answer are stored
+  // together once the answer arrives
+  sendConversationMessage: async (content, providerId, options) => {
+    const conversation = get().activeConversation;
+    if (!conversation) {
+      return null;
+    }
+    const requestId = options?.request_id || crypto.randomUUID();
+    set(state => ({ isLoading: true, error: null, providerError: null, pendingRequests: [...state.pendingRequests, requestId] }));
+    const settle = () => set(state => ({
+      pendingRequests: state.pendingRequests.filter(id => id !== requestId)
+    }));
+    try {
+      const answer = await invoke<ConversationMessage>('send_conversation_message', {
+        conversationId: conversation.id,
+        content,
+        providerId,
+        options: { ...options, request_id: requestId }
+      });
+      settle();
+      set({ isLoading: false });
+      await get().openConversation(conversation.id);
+      await get().loadConversations();
+      return answer;
+    } catch (error) {
+      settle();
+      set({ error: describeError(error), providerError: asProviderError(error), isLoading: false });
+      return null;
+    }
+  },
+
+  exportConversation: async (conversationId, format, filePath) => {
+    try {
+      return await invoke<string>('export_conversation', { conversationId, format, filePath });
+    } catch (error) {
+      set({ error: describeError(error) });
+      return null;
+    }
+  },
+
+  clearStream: (streamId) => {
+    set(state => {
+      const { [streamId]: _, ...streams } = state.streams;
+      return { streams };
+    });
+  },
+
+  subscribeToStreams: async () => {
+    const empty = (id: string): AIStream => ({ id, providerId: null, content: '', done: false, cancelled: false, error: null, usage: null });
+    return Promise.all([
+      listen<StreamChunkEvent>('ai-stream-chunk', (event) => {
+        const { stream_id, content } = event.payload;
+        set(state => {
+          const stream = state.streams[stream_id] || empty(stream_id);
+          return { streams: { ...state.streams, [stream_id]: { ...stream, content: stream.content + content } } };
+        });
+      }),
+      listen<StreamEndEvent>('ai-stream-end', (event) => {
+        const { stream_id, provider_id, error, cancelled, usage } = event.payload;
+        set(state => {
+          const stream = state.streams[stream_id] || empty(stream_id);
+          return { streams: { ...state.streams, [stream_id]: { ...stream, providerId: provider_id, done: true, cancelled, error, usage } } };
+        });
+      }),
+    ]);
+  },
+
+  clearError: () => set({ error: null, providerError: null }),
 }));
\ No newline at end of file
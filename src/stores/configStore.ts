@@ -24,6 +24,12 @@ export interface HotkeyConfig {
   close_tab: string;
   next_tab: string;
   prev_tab: string;
+  split_horizontal: string;
+  split_vertical: string;
+  focus_pane_left: string;
+  focus_pane_right: string;
+  focus_pane_up: string;
+  focus_pane_down: string;
 }
 
 export interface EdgeConfig {
@@ -49,6 +55,25 @@ export interface TerminalConfig {
   cursor_style: string;
   bell_sound: boolean;
   auto_close: boolean;
+  restore_sessions: boolean;
+  shell_args?: string[];
+  login_shell?: boolean;
+  profiles?: Record<string, TerminalProfile>;
+  command_notifications?: CommandNotificationsConfig;
+}
+
+export interface CommandNotificationsConfig {
+  enabled: boolean;
+  threshold: number;
+  update_tray_status: boolean;
+}
+
+export interface TerminalProfile {
+  shell?: string;
+  args?: string[];
+  login_shell?: boolean;
+  environment?: Record<string, string>;
+  working_directory?: string;
 }
 
 export interface SecurityConfig {
@@ -57,6 +82,52 @@ export interface SecurityConfig {
   minimal_scopes: boolean;
   auto_lock: boolean;
   lock_timeout: number;
+  redaction_patterns?: string[];
+}
+
+export interface HistoryConfig {
+  enabled: boolean;
+  max_entries: number;
+  ignore_space_prefixed: boolean;
+  exclude_patterns: string[];
+}
+
+export interface JobsConfig {
+  notify_on_finish: boolean;
+  long_running_threshold: number;
+  max_finished_jobs: number;
+}
+
+export interface UsageConfig {
+  enabled: boolean;
+  monthly_budget: number | null;
+}
+
+export interface FailoverConfig {
+  max_retries: number;
+  initial_backoff_ms: number;
+  max_backoff_ms: number;
+  fallback_chain: string[];
+}
+
+export interface RoutingRule {
+  name: string;
+  prefix?: string | null;
+  workspace?: string | null;
+  capability?: string | null;
+  min_prompt_tokens?: number | null;
+  provider?: string | null;
+  deny_tags?: string[];
+}
+
+export interface RoutingConfig {
+  rules: RoutingRule[];
+}
+
+export interface ConversationsConfig {
+  overflow: 'truncate' | 'summarize';
+  default_context_window: number;
+  reserve_tokens: number;
 }
 
 export interface Config {
@@ -67,6 +138,12 @@ export interface Config {
   plugins: Record<string, any>;
   workspace_rules: any[];
   security: SecurityConfig;
+  history?: HistoryConfig;
+  jobs?: JobsConfig;
+  usage?: UsageConfig;
+  failover?: FailoverConfig;
+  routing?: RoutingConfig;
+  conversations?: ConversationsConfig;
 }
 
 export interface ThemeInfo {
@@ -117,6 +194,12 @@ const defaultConfig: Config = {
       close_tab: 'cmd+w',
       next_tab: 'cmd+shift+]',
       prev_tab: 'cmd+shift+[',
+      split_horizontal: 'cmd+d',
+      split_vertical: 'cmd+shift+d',
+      focus_pane_left: 'cmd+alt+left',
+      focus_pane_right: 'cmd+alt+right',
+      focus_pane_up: 'cmd+alt+up',
+      focus_pane_down: 'cmd+alt+down',
     },
     edges: {
       reveal: true,
@@ -137,6 +220,12 @@ const defaultConfig: Config = {
     cursor_style: 'block',
     bell_sound: true,
     auto_close: false,
+    restore_sessions: true,
+    command_notifications: {
+      enabled: true,
+      threshold: 10,
+      update_tray_status: true,
+    },
   },
   plugins: {},
   workspace_rules: [],
@@ -147,6 +236,35 @@ const defaultConfig: Config = {
     auto_lock: false,
     lock_timeout: 300,
   },
+  history: {
+    enabled: true,
+    max_entries: 10000,
+    ignore_space_prefixed: true,
+    exclude_patterns: [],
+  },
+  jobs: {
+    notify_on_finish: true,
+    long_running_threshold: 300,
+    max_finished_jobs: 50,
+  },
+  usage: {
+    enabled: true,
+    monthly_budget: null,
+  },
+  failover: {
+    max_retries: 2,
+    initial_backoff_ms: 500,
+    max_backoff_ms: 10000,
+    fallback_chain: [],
+  },
+  routing: {
+    rules: [],
+  },
+  conversations: {
+    overflow: 'truncate',
+    default_context_window: 8192,
+    reserve_tokens: 1024,
+  },
 };
 
 export const useConfigStore = create<ConfigState>((set, get) => ({
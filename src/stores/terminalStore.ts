@@ -1,5 +1,8 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { useConfigStore } from './configStore';
+import { OutputBuffer, appendOutput, bufferFromText, clearBuffer, emptyBuffer } from '../utils/terminalOutput';
 
 export interface TerminalSession {
   id: string;
@@ -9,25 +12,144 @@ export interface TerminalSession {
   output: string;
 }
 
+export interface OutputRange {
+  first_index: number;
+  next_index: number;
+  lines: { index: number; text: string; timestamp: string }[];
+}
+
+export interface TerminalOutputEvent {
+  session_id: string;
+  sequence: number;
+  data: string;
+}
+
+export type SplitDirection = 'horizontal' | 'vertical';
+export type FocusDirection = 'left' | 'right' | 'up' | 'down';
+
+export type LayoutNode =
+  | { type: 'pane'; session_id: string }
+  | {
+      type: 'split';
+      id: string;
+      direction: SplitDirection;
+      ratio: number;
+      first: LayoutNode;
+      second: LayoutNode;
+    };
+
+export interface PaneLayout {
+  id: string;
+  root: LayoutNode;
+  focused_session: string;
+}
+
+export interface CwdChangedEvent {
+  session_id: string;
+  working_directory: string;
+}
+
+export interface HistoryEntry {
+  id: string;
+  command: string;
+  working_directory: string | null;
+  session_id: string;
+  exit_code: number | null;
+  duration_ms: number | null;
+  timestamp: string;
+  git_branch: string | null;
+}
+
+export interface RankedCommand {
+  command: string;
+  count: number;
+  last_used: string;
+  last_exit_code: number | null;
+  working_directory: string | null;
+  git_branch: string | null;
+  score: number;
+  positions: number[];
+}
+
+export interface HistorySearchOptions {
+  working_directory?: string;
+  session_id?: string;
+  successful_only?: boolean;
+  limit?: number;
+}
+
+export type JobStatus = 'running' | 'succeeded' | 'failed' | 'cancelled';
+
+export interface Job {
+  id: string;
+  command: string;
+  working_directory: string;
+  status: JobStatus;
+  pid: number | null;
+  exit_code: number | null;
+  signal: number | null;
+  started_at: string;
+  finished_at: string | null;
+  duration_ms: number | null;
+  output_path: string;
+}
+
+export interface SessionExitedEvent {
+  session_id: string;
+  exit_code: number | null;
+  signal: number | null;
+  auto_closed: boolean;
+}
+
 interface TerminalState {
   terminals: TerminalSession[];
+  // Rendered output per session, capped to terminal.scrollback_lines
+  buffers: Record<string, OutputBuffer>;
+  layouts: PaneLayout[];
+  jobs: Job[];
   activeTerminalId: string | null;
   isLoading: boolean;
   error: string | null;
   
   // Actions
   loadTerminals: () => Promise<void>;
-  createTerminal: (title?: string, workingDirectory?: string) => Promise<void>;
+  createTerminal: (title?: string, workingDirectory?: string, profile?: string) => Promise<void>;
   closeTerminal: (id: string) => Promise<void>;
   setActiveTerminal: (id: string) => Promise<void>;
   sendInput: (terminalId: string, input: string) => Promise<void>;
+  writeInput: (terminalId: string, data: string) => Promise<void>;
+  resizeTerminal: (terminalId: string, cols: number, rows: number) => Promise<void>;
   clearOutput: (terminalId: string) => Promise<void>;
   executeCommand: (terminalId: string, command: string) => Promise<void>;
+  loadLayouts: () => Promise<void>;
+  splitPane: (terminalId: string, direction: SplitDirection) => Promise<void>;
+  focusPane: (terminalId: string, direction: FocusDirection) => Promise<void>;
+  subscribeToOutput: () => Promise<UnlistenFn>;
+  resyncOutput: (terminalId: string, sequence: number) => Promise<void>;
+  subscribeToCwdChanges: () => Promise<UnlistenFn>;
+  subscribeToSessionExits: () => Promise<UnlistenFn>;
+  loadJobs: () => Promise<void>;
+  runBackgroundJob: (command: string, workingDirectory?: string) => Promise<void>;
+  cancelJob: (jobId: string) => Promise<void>;
+  getJobOutput: (jobId: string) => Promise<string>;
+  subscribeToJobUpdates: () => Promise<UnlistenFn>;
+  searchHistory: (query: string, options?: HistorySearchOptions) => Promise<RankedCommand[]>;
+  getDirectoryHistory: (workingDirectory: string, limit?: number) => Promise<HistoryEntry[]>;
   clearError: () => void;
 }
 
+const scrollbackLimit = () =>
+  useConfigStore.getState().config?.terminal.scrollback_lines || 10000;
+
+// Sessions whose buffer is being rebuilt from the backend transcript, with
+// the latest sequence seen while the fetch is in flight
+const resyncing = new Map<string, number>();
+
 export const useTerminalStore = create<TerminalState>((set, get) => ({
   terminals: [],
+  buffers: {},
+  layouts: [],
+  jobs: [],
   activeTerminalId: null,
   isLoading: false,
   error: null,
@@ -37,8 +159,17 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
     try {
       const terminals = await invoke<TerminalSession[]>('get_terminal_state');
       const activeTerminal = terminals.find(t => t.is_active);
+      // Seed buffers for sessions not seen yet and drop closed ones; the
+      // backend output is the emulator transcript, so it is already free of
+      // escape sequences
+      const previous = get().buffers;
+      const buffers: Record<string, OutputBuffer> = {};
+      for (const t of terminals) {
+        buffers[t.id] = previous[t.id] ?? bufferFromText(t.output, 0, scrollbackLimit());
+      }
       set({ 
         terminals, 
+        buffers,
         activeTerminalId: activeTerminal?.id || terminals[0]?.id || null,
         isLoading: false 
       });
@@ -47,23 +178,13 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
     }
   },
 
-  createTerminal: async (title, workingDirectory) => {
+  createTerminal: async (title, workingDirectory, profile) => {
     set({ isLoading: true, error: null });
     try {
-      // Create a new terminal session
-      const newTerminal: TerminalSession = {
-        id: `terminal_${Date.now()}`,
-        title: title || `Terminal ${get().terminals.length + 1}`,
-        working_directory: workingDirectory || '/',
-        is_active: false,
-        output: '',
-      };
-      
-      set(state => ({
-        terminals: [...state.terminals, newTerminal],
-        activeTerminalId: newTerminal.id,
-        isLoading: false
-      }));
+      const id = await invoke<string>('create_terminal', { title, workingDirectory, profile });
+      await get().loadTerminals();
+      await get().loadLayouts();
+      set({ activeTerminalId: id, isLoading: false });
     } catch (error) {
       set({ error: error as string, isLoading: false });
     }
@@ -72,14 +193,12 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
   closeTerminal: async (id) => {
     set({ isLoading: true, error: null });
     try {
-      set(state => {
-        const terminals = state.terminals.filter(t => t.id !== id);
-        const activeTerminalId = state.activeTerminalId === id 
-          ? terminals[0]?.id || null 
-          : state.activeTerminalId;
-        
-        return { terminals, activeTerminalId, isLoading: false };
-      });
+      // The backend picks the next active session, so reload rather than
+      // filtering locally
+      await invoke('close_terminal', { terminalId: id });
+      await get().loadTerminals();
+      await get().loadLayouts();
+      set({ isLoading: false });
     } catch (error) {
       set({ error: error as string, isLoading: false });
     }
@@ -106,29 +225,38 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
     try {
       await invoke('send_terminal_input', { terminalId, input });
       
-      // Update terminal output
-      set(state => ({
-        terminals: state.terminals.map(t => 
-          t.id === terminalId 
-            ? { ...t, output: t.output + `$ ${input}\n` }
-            : t
-        ),
-        isLoading: false
-      }));
+      // Output (including the echoed input) arrives through terminal-output events
+      set({ isLoading: false });
     } catch (error) {
       set({ error: error as string, isLoading: false });
     }
   },
 
+  writeInput: async (terminalId, data) => {
+    try {
+      // Raw keystrokes, passed to the shell unchanged
+      await invoke('write_terminal_input', { terminalId, data });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  resizeTerminal: async (terminalId, cols, rows) => {
+    try {
+      await invoke('resize_terminal', { terminalId, cols, rows });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
   clearOutput: async (terminalId) => {
     set({ isLoading: true, error: null });
     try {
       set(state => ({
-        terminals: state.terminals.map(t => 
-          t.id === terminalId 
-            ? { ...t, output: '' }
-            : t
-        ),
+        buffers: {
+          ...state.buffers,
+          [terminalId]: clearBuffer(state.buffers[terminalId] ?? emptyBuffer())
+        },
         isLoading: false
       }));
     } catch (error) {
@@ -143,11 +271,10 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
       const result = `Executing: ${command}\n`;
       
       set(state => ({
-        terminals: state.terminals.map(t => 
-          t.id === terminalId 
-            ? { ...t, output: t.output + result }
-            : t
-        ),
+        buffers: {
+          ...state.buffers,
+          [terminalId]: appendOutput(state.buffers[terminalId] ?? emptyBuffer(), result, scrollbackLimit())
+        },
         isLoading: false
       }));
     } catch (error) {
@@ -155,5 +282,186 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
     }
   },
 
+  loadLayouts: async () => {
+    try {
+      const layouts = await invoke<PaneLayout[]>('get_pane_layouts');
+      set({ layouts });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  splitPane: async (terminalId, direction) => {
+    try {
+      await invoke<PaneLayout>('split_pane', { terminalId, direction });
+      await get().loadTerminals();
+      await get().loadLayouts();
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  focusPane: async (terminalId, direction) => {
+    try {
+      const focused = await invoke<string | null>('focus_pane', { terminalId, direction });
+      if (focused) {
+        await get().setActiveTerminal(focused);
+      }
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  subscribeToOutput: () => {
+    return listen<TerminalOutputEvent>('terminal-output', (event) => {
+      const { session_id, sequence, data } = event.payload;
+      const pending = resyncing.get(session_id);
+      if (pending !== undefined) {
+        // The emulator processes a chunk before it is emitted, so the
+        // transcript being fetched already includes it
+        resyncing.set(session_id, Math.max(pending, sequence));
+        return;
+      }
+
+      const buffer = get().buffers[session_id] ?? emptyBuffer();
+      // Sequences restart at 1 when the shell is restarted
+      if (sequence !== 1 && sequence <= buffer.sequence) {
+        console.warn(`Dropping stale output for ${session_id}: ${sequence} <= ${buffer.sequence}`);
+        return;
+      }
+      if (sequence !== 1 && buffer.sequence !== 0 && sequence !== buffer.sequence + 1) {
+        console.warn(`Output gap for ${session_id}: expected ${buffer.sequence + 1}, got ${sequence}`);
+        get().resyncOutput(session_id, sequence);
+        return;
+      }
+
+      set(state => ({
+        buffers: {
+          ...state.buffers,
+          [session_id]: {
+            ...appendOutput(state.buffers[session_id] ?? emptyBuffer(), data, scrollbackLimit()),
+            sequence
+          }
+        }
+      }));
+    });
+  },
+
+  resyncOutput: async (terminalId, sequence) => {
+    resyncing.set(terminalId, sequence);
+    try {
+      const range = await invoke<OutputRange>('get_terminal_output', { terminalId });
+      const text = range.lines.map(line => line.text).join('\n');
+      const latest = resyncing.get(terminalId) ?? sequence;
+      set(state => ({
+        buffers: {
+          ...state.buffers,
+          [terminalId]: bufferFromText(text, latest, scrollbackLimit())
+        }
+      }));
+    } catch (error) {
+      set({ error: error as string });
+    } finally {
+      resyncing.delete(terminalId);
+    }
+  },
+
+  subscribeToCwdChanges: () => {
+    return listen<CwdChangedEvent>('cwd-changed', (event) => {
+      const { session_id, working_directory } = event.payload;
+      set(state => ({
+        terminals: state.terminals.map(t =>
+          t.id === session_id ? { ...t, working_directory } : t
+        )
+      }));
+    });
+  },
+
+  subscribeToSessionExits: () => {
+    return listen<SessionExitedEvent>('session-exited', (event) => {
+      const { session_id, auto_closed } = event.payload;
+      if (!auto_closed) {
+        return;
+      }
+      set(state => {
+        const terminals = state.terminals.filter(t => t.id !== session_id);
+        const buffers = { ...state.buffers };
+        delete buffers[session_id];
+        return {
+          terminals,
+          buffers,
+          activeTerminalId: state.activeTerminalId === session_id
+            ? terminals[0]?.id || null
+            : state.activeTerminalId
+        };
+      });
+      get().loadLayouts();
+    });
+  },
+
+  loadJobs: async () => {
+    try {
+      const jobs = await invoke<Job[]>('list_jobs');
+      set({ jobs });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  runBackgroundJob: async (command, workingDirectory) => {
+    try {
+      const job = await invoke<Job>('run_background_job', { command, workingDirectory });
+      set(state => ({ jobs: [job, ...state.jobs.filter(j => j.id !== job.id)] }));
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  cancelJob: async (jobId) => {
+    try {
+      await invoke('cancel_job', { jobId });
+    } catch (error) {
+      set({ error: error as string });
+    }
+  },
+
+  getJobOutput: async (jobId) => {
+    try {
+      return await invoke<string>('get_job_output', { jobId });
+    } catch (error) {
+      set({ error: error as string });
+      return '';
+    }
+  },
+
+  subscribeToJobUpdates: () => {
+    return listen<Job>('job-updated', (event) => {
+      const job = event.payload;
+      set(state => ({
+        jobs: state.jobs.some(j => j.id === job.id)
+          ? state.jobs.map(j => j.id === job.id ? job : j)
+          : [job, ...state.jobs]
+      }));
+    });
+  },
+
+  searchHistory: async (query, options) => {
+    try {
+      return await invoke<RankedCommand[]>('search_history', { query, options });
+    } catch (error) {
+      set({ error: error as string });
+      return [];
+    }
+  },
+
+  getDirectoryHistory: async (workingDirectory, limit) => {
+    try {
+      return await invoke<HistoryEntry[]>('get_directory_history', { workingDirectory, limit });
+    } catch (error) {
+      set({ error: error as string });
+      return [];
+    }
+  },
+
   clearError: () => set({ error: null }),
 }));
\ No newline at end of file
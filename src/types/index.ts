@@ -66,6 +66,12 @@ export interface HotkeyConfig {
   close_tab: string;
   next_tab: string;
   prev_tab: string;
+  split_horizontal: string;
+  split_vertical: string;
+  focus_pane_left: string;
+  focus_pane_right: string;
+  focus_pane_up: string;
+  focus_pane_down: string;
 }
 
 export interface EdgeConfig {
@@ -91,6 +97,25 @@ export interface TerminalConfig {
   cursor_style: string;
   bell_sound: boolean;
   auto_close: boolean;
+  restore_sessions: boolean;
+  shell_args?: string[];
+  login_shell?: boolean;
+  profiles?: Record<string, TerminalProfile>;
+  command_notifications?: CommandNotificationsConfig;
+}
+
+export interface CommandNotificationsConfig {
+  enabled: boolean;
+  threshold: number;
+  update_tray_status: boolean;
+}
+
+export interface TerminalProfile {
+  shell?: string;
+  args?: string[];
+  login_shell?: boolean;
+  environment?: Record<string, string>;
+  working_directory?: string;
 }
 
 export interface SecurityConfig {
@@ -99,6 +124,52 @@ export interface SecurityConfig {
   minimal_scopes: boolean;
   auto_lock: boolean;
   lock_timeout: number;
+  redaction_patterns?: string[];
+}
+
+export interface HistoryConfig {
+  enabled: boolean;
+  max_entries: number;
+  ignore_space_prefixed: boolean;
+  exclude_patterns: string[];
+}
+
+export interface JobsConfig {
+  notify_on_finish: boolean;
+  long_running_threshold: number;
+  max_finished_jobs: number;
+}
+
+export interface UsageConfig {
+  enabled: boolean;
+  monthly_budget: number | null;
+}
+
+export interface FailoverConfig {
+  max_retries: number;
+  initial_backoff_ms: number;
+  max_backoff_ms: number;
+  fallback_chain: string[];
+}
+
+export interface RoutingRule {
+  name: string;
+  prefix?: string | null;
+  workspace?: string | null;
+  capability?: string | null;
+  min_prompt_tokens?: number | null;
+  provider?: string | null;
+  deny_tags?: string[];
+}
+
+export interface RoutingConfig {
+  rules: RoutingRule[];
+}
+
+export interface ConversationsConfig {
+  overflow: 'truncate' | 'summarize';
+  default_context_window: number;
+  reserve_tokens: number;
 }
 
 export interface Config {
@@ -109,6 +180,12 @@ export interface Config {
   plugins: Record<string, any>;
   workspace_rules: any[];
   security: SecurityConfig;
+  history?: HistoryConfig;
+  jobs?: JobsConfig;
+  usage?: UsageConfig;
+  failover?: FailoverConfig;
+  routing?: RoutingConfig;
+  conversations?: ConversationsConfig;
 }
 
 export interface ThemeInfo {
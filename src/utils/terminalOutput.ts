@@ -0,0 +1,147 @@
+/**
+ * Client-side buffer for streamed terminal output
+ */
+
+export interface OutputBuffer {
+  // Absolute index of lines[0], so rendered lines keep stable keys as the
+  // front of the buffer is trimmed
+  firstIndex: number;
+  lines: string[];
+  // Cursor column on the last line, for carriage returns and erase-line
+  column: number;
+  // Last terminal-output sequence applied, 0 before any event
+  sequence: number;
+  // Parser state carried across events, since escapes can be split
+  escape: EscapeState;
+}
+
+type EscapeState = 'ground' | 'escape' | 'csi' | 'string' | 'string-escape';
+
+export const emptyBuffer = (): OutputBuffer => ({
+  firstIndex: 0,
+  lines: [''],
+  column: 0,
+  sequence: 0,
+  escape: 'ground',
+});
+
+/**
+ * Build a buffer from already-rendered text, such as the backend transcript
+ */
+export function bufferFromText(text: string, sequence: number, maxLines: number): OutputBuffer {
+  const lines = text.split('\n');
+  const buffer: OutputBuffer = {
+    ...emptyBuffer(),
+    lines,
+    column: lines[lines.length - 1].length,
+    sequence,
+  };
+  return trimBuffer(buffer, maxLines);
+}
+
+/**
+ * Drop all lines, keeping the sequence and indexes moving forward
+ */
+export function clearBuffer(buffer: OutputBuffer): OutputBuffer {
+  return {
+    ...buffer,
+    firstIndex: buffer.firstIndex + buffer.lines.length,
+    lines: [''],
+    column: 0,
+  };
+}
+
+/**
+ * Apply a chunk of raw shell output. Escape sequences are dropped rather than
+ * shown; carriage return, backspace, tab and erase-line are interpreted so
+ * prompts and progress bars redraw in place.
+ */
+export function appendOutput(buffer: OutputBuffer, data: string, maxLines: number): OutputBuffer {
+  const lines = buffer.lines.slice();
+  let line = lines.pop() ?? '';
+  let column = buffer.column;
+  let escape = buffer.escape;
+  let params = '';
+
+  const write = (text: string) => {
+    line = line.slice(0, column) + text + line.slice(column + text.length);
+    column += text.length;
+  };
+
+  for (const ch of data) {
+    switch (escape) {
+      case 'escape':
+        if (ch === '[') {
+          escape = 'csi';
+          params = '';
+        } else if (ch === ']' || ch === 'P' || ch === '^' || ch === '_') {
+          escape = 'string';
+        } else if (ch < '0') {
+          // Intermediate bytes, e.g. the charset selection in ESC ( B
+        } else {
+          escape = 'ground';
+        }
+        continue;
+      case 'csi':
+        if (ch >= '@' && ch <= '~') {
+          // Erase in line; 0 (the default) clears from the cursor onward
+          if (ch === 'K' && (params === '' || params === '0')) {
+            line = line.slice(0, column);
+          }
+          escape = 'ground';
+        } else {
+          params += ch;
+        }
+        continue;
+      case 'string':
+        if (ch === '\x07') {
+          escape = 'ground';
+        } else if (ch === '\x1b') {
+          escape = 'string-escape';
+        }
+        continue;
+      case 'string-escape':
+        escape = ch === '\\' ? 'ground' : 'string';
+        continue;
+    }
+
+    switch (ch) {
+      case '\x1b':
+        escape = 'escape';
+        break;
+      case '\n':
+        lines.push(line);
+        line = '';
+        column = 0;
+        break;
+      case '\r':
+        column = 0;
+        break;
+      case '\b':
+        column = Math.max(0, column - 1);
+        break;
+      case '\t':
+        write(' '.repeat(8 - (column % 8)));
+        break;
+      default:
+        if (ch >= ' ' && ch !== '\x7f') {
+          write(ch);
+        }
+    }
+  }
+
+  lines.push(line);
+  return trimBuffer({ ...buffer, lines, column, escape }, maxLines);
+}
+
+function trimBuffer(buffer: OutputBuffer, maxLines: number): OutputBuffer {
+  const excess = buffer.lines.length - Math.max(1, maxLines);
+  if (excess <= 0) {
+    return buffer;
+  }
+  return {
+    ...buffer,
+    firstIndex: buffer.firstIndex + excess,
+    lines: buffer.lines.slice(excess),
+  };
+}